@@ -0,0 +1,397 @@
+// Type-aware editors for method arguments and property values.
+//
+// Every `.argument-editor` is driven by its `data-signature` and writes its value, in the JSON
+// encoding understood by the server, to the textarea that follows it. Plain inputs with a
+// `data-signature` are validated too. Locations in error messages use the same notation as the
+// server, e.g. `options["key"].value[2]`.
+(function () {
+  "use strict";
+
+  var BASIC = "ybnqiuxtdsogh";
+  var INTEGER_RANGES = {
+    y: ["0", "255"],
+    n: ["-32768", "32767"],
+    q: ["0", "65535"],
+    i: ["-2147483648", "2147483647"],
+    u: ["0", "4294967295"],
+    x: ["-9223372036854775808", "9223372036854775807"],
+    t: ["0", "18446744073709551615"],
+  };
+  var NAMES = {
+    y: "byte", b: "boolean", n: "int16", q: "uint16", i: "int32", u: "uint32", x: "int64",
+    t: "uint64", d: "double", s: "string", o: "object path", g: "signature",
+    h: "file descriptor", v: "variant",
+  };
+  // Quoted or annotated basic values in GVariant text format, checked by the server
+  var GVARIANT_BASIC = /^(['"]|(byte|int16|uint16|int32|uint32|int64|uint64|double|boolean|objectpath|signature|handle|string)\s)|^@|^[-+]?0[xX]/;
+  var VARIANT_TYPES = ["s", "b", "i", "u", "x", "t", "d", "y", "n", "q", "o", "g", "as", "a{sv}"];
+
+  // Mirrors src/signature.rs
+  function parseTypes(signature) {
+    var position = 0;
+    function fail(message) {
+      throw new Error("invalid signature " + JSON.stringify(signature) + " at position " +
+        position + ": " + message);
+    }
+    function parseType() {
+      var code = signature[position];
+      if (code === undefined) fail("unexpected end of signature");
+      if ((BASIC + "v").indexOf(code) >= 0) {
+        position++;
+        return { code: code };
+      }
+      if (code === "a") {
+        position++;
+        if (signature[position] === "{") {
+          position++;
+          var key = parseType();
+          if (BASIC.indexOf(key.code) < 0) fail("dict keys must be basic types");
+          if (signature[position] === "}") fail("dict entries must have a value type");
+          var value = parseType();
+          if (signature[position] !== "}") fail("dict entries must have exactly two types");
+          position++;
+          return { code: "dict", key: key, value: value };
+        }
+        return { code: "array", element: parseType() };
+      }
+      if (code === "(") {
+        position++;
+        var fields = [];
+        while (signature[position] !== ")") {
+          if (position >= signature.length) fail("unterminated struct");
+          fields.push(parseType());
+        }
+        if (!fields.length) fail("structs must have at least one field");
+        position++;
+        return { code: "struct", fields: fields };
+      }
+      fail("unexpected " + JSON.stringify(code));
+    }
+    var types = [];
+    while (position < signature.length) types.push(parseType());
+    return types;
+  }
+
+  function parseSignature(signature) {
+    var types = parseTypes(signature);
+    if (types.length !== 1) {
+      throw new Error("invalid signature " + JSON.stringify(signature) +
+        ": expected a single complete type");
+    }
+    return types[0];
+  }
+
+  function describe(type) {
+    switch (type.code) {
+      case "array": return "array of " + describe(type.element);
+      case "dict": return "dict of " + describe(type.key) + " to " + describe(type.value);
+      case "struct": return "struct of (" + type.fields.map(describe).join(", ") + ")";
+      default: return NAMES[type.code];
+    }
+  }
+
+  // Returns the JSON value for `text`, or pushes an error and returns undefined.
+  function parseBasic(type, text, location, errors) {
+    function fail(message) {
+      errors.push(location + ": " + message);
+    }
+    var code = type.code;
+    if (code === "b") {
+      if (text === "true" || text === "1") return true;
+      if (text === "false" || text === "0") return false;
+      return fail("expected true or false but got " + JSON.stringify(text));
+    }
+    if (INTEGER_RANGES[code]) {
+      var trimmed = text.trim();
+      if (!/^-?[0-9]+$/.test(trimmed)) {
+        return fail("expected " + NAMES[code] + " but got " + JSON.stringify(text));
+      }
+      var value = BigInt(trimmed);
+      var range = INTEGER_RANGES[code];
+      if (value < BigInt(range[0]) || value > BigInt(range[1])) {
+        return fail(NAMES[code] + " must be between " + range[0] + " and " + range[1]);
+      }
+      // Large 64-bit values would lose precision as JSON numbers
+      return Number.isSafeInteger(Number(value)) ? Number(value) : trimmed;
+    }
+    if (code === "d") {
+      if (text.trim() === "" || isNaN(Number(text))) {
+        return fail("expected double but got " + JSON.stringify(text));
+      }
+      return Number(text);
+    }
+    if (code === "o") {
+      if (!/^\/$|^(\/[A-Za-z0-9_]+)+$/.test(text)) {
+        return fail("expected an object path like /com/example/Object");
+      }
+      return text;
+    }
+    if (code === "g") {
+      try {
+        parseTypes(text);
+      } catch (e) {
+        return fail(e.message);
+      }
+      return text;
+    }
+    if (code === "h") return fail("file descriptors cannot be sent");
+    return text;
+  }
+
+  function signatureOf(type) {
+    switch (type.code) {
+      case "array": return "a" + signatureOf(type.element);
+      case "dict": return "a{" + signatureOf(type.key) + signatureOf(type.value) + "}";
+      case "struct": return "(" + type.fields.map(signatureOf).join("") + ")";
+      default: return type.code;
+    }
+  }
+
+  function element(tag, className, text) {
+    var node = document.createElement(tag);
+    if (className) node.className = className;
+    if (text !== undefined) node.textContent = text;
+    return node;
+  }
+
+  function button(text, onClick) {
+    var node = element("button", "editor-button", text);
+    node.type = "button";
+    node.addEventListener("click", onClick);
+    return node;
+  }
+
+  // An editor is { node, value(location, errors) }.
+  function createEditor(type, initial) {
+    switch (type.code) {
+      case "array": return arrayEditor(type, initial);
+      case "dict": return dictEditor(type, initial);
+      case "struct": return structEditor(type, initial);
+      case "v": return variantEditor(initial);
+      default: return basicEditor(type, initial);
+    }
+  }
+
+  function basicEditor(type, initial) {
+    var input;
+    if (type.code === "b") {
+      input = element("select");
+      ["false", "true"].forEach(function (option) {
+        var node = element("option", null, option);
+        node.value = option;
+        input.appendChild(node);
+      });
+      input.value = initial === true ? "true" : "false";
+    } else {
+      input = element("input");
+      input.type = "text";
+      input.placeholder = describe(type);
+      if (initial !== undefined && initial !== null) input.value = String(initial);
+    }
+    return {
+      node: input,
+      value: function (location, errors) {
+        return parseBasic(type, input.value, location, errors);
+      },
+    };
+  }
+
+  function rowsEditor(createRow, initialRows, addLabel) {
+    var node = element("div", "editor-rows");
+    var list = element("div");
+    var rows = [];
+    node.appendChild(list);
+
+    function add(initial) {
+      var row = createRow(initial);
+      var rowNode = element("div", "editor-row");
+      row.nodes.forEach(function (child) {
+        rowNode.appendChild(child);
+      });
+      rowNode.appendChild(button("✕", function () {
+        rows.splice(rows.indexOf(row), 1);
+        list.removeChild(rowNode);
+      }));
+      rows.push(row);
+      list.appendChild(rowNode);
+    }
+
+    initialRows.forEach(add);
+    node.appendChild(button(addLabel, function () {
+      add(undefined);
+    }));
+    return { node: node, rows: rows };
+  }
+
+  function arrayEditor(type, initial) {
+    var editors = rowsEditor(function (value) {
+      var editor = createEditor(type.element, value);
+      return { nodes: [editor.node], editor: editor };
+    }, Array.isArray(initial) ? initial : [], "+ element");
+    return {
+      node: editors.node,
+      value: function (location, errors) {
+        return editors.rows.map(function (row, i) {
+          return row.editor.value(location + "[" + i + "]", errors);
+        });
+      },
+    };
+  }
+
+  function dictEditor(type, initial) {
+    var entries = initial && typeof initial === "object" && !Array.isArray(initial) ?
+      Object.keys(initial).map(function (key) { return [key, initial[key]]; }) : [];
+    var editors = rowsEditor(function (entry) {
+      var key = basicEditor(type.key, entry && entry[0]);
+      var value = createEditor(type.value, entry && entry[1]);
+      return { nodes: [key.node, element("span", null, " → "), value.node], key: key, value: value };
+    }, entries, "+ entry");
+    return {
+      node: editors.node,
+      value: function (location, errors) {
+        var result = {};
+        editors.rows.forEach(function (row, i) {
+          var key = row.key.value(location + "[key " + i + "]", errors);
+          if (key === undefined) return;
+          var text = String(key);
+          var entryLocation = location + "[" + JSON.stringify(text) + "]";
+          if (Object.prototype.hasOwnProperty.call(result, text)) {
+            errors.push(entryLocation + ": duplicate key");
+          }
+          result[text] = row.value.value(entryLocation, errors);
+        });
+        return result;
+      },
+    };
+  }
+
+  function structEditor(type, initial) {
+    var node = element("fieldset", "editor-struct");
+    var fields = type.fields.map(function (fieldType, i) {
+      var editor = createEditor(fieldType, Array.isArray(initial) ? initial[i] : undefined);
+      var label = element("div", "editor-field");
+      label.appendChild(element("span", null, i + ": "));
+      label.appendChild(editor.node);
+      node.appendChild(label);
+      return editor;
+    });
+    return {
+      node: node,
+      value: function (location, errors) {
+        return fields.map(function (editor, i) {
+          return editor.value(location + "." + i, errors);
+        });
+      },
+    };
+  }
+
+  function variantEditor(initial) {
+    var node = element("span", "editor-variant");
+    var picker = element("select");
+    var custom = element("input");
+    var holder = element("span");
+    var inner = null;
+    var innerType = null;
+
+    VARIANT_TYPES.concat(["other"]).forEach(function (signature) {
+      var option = element("option", null, signature === "other" ? "other…" : signature);
+      option.value = signature;
+      picker.appendChild(option);
+    });
+    custom.type = "text";
+    custom.placeholder = "signature";
+    custom.size = 8;
+
+    function rebuild(value) {
+      var signature = picker.value === "other" ? custom.value : picker.value;
+      custom.style.display = picker.value === "other" ? "" : "none";
+      holder.textContent = "";
+      inner = null;
+      innerType = null;
+      try {
+        innerType = parseSignature(signature);
+      } catch (e) {
+        holder.appendChild(element("em", null, e.message));
+        return;
+      }
+      inner = createEditor(innerType, value);
+      holder.appendChild(inner.node);
+    }
+
+    var initialType = initial && typeof initial.type === "string" ? initial.type : "s";
+    if (VARIANT_TYPES.indexOf(initialType) >= 0) {
+      picker.value = initialType;
+    } else {
+      picker.value = "other";
+      custom.value = initialType;
+    }
+    picker.addEventListener("change", function () { rebuild(undefined); });
+    custom.addEventListener("change", function () { rebuild(undefined); });
+    node.appendChild(picker);
+    node.appendChild(custom);
+    node.appendChild(holder);
+    rebuild(initial ? initial.value : undefined);
+
+    return {
+      node: node,
+      value: function (location, errors) {
+        if (!inner) {
+          errors.push(location + ".type: invalid signature");
+          return undefined;
+        }
+        return { type: signatureOf(innerType), value: inner.value(location + ".value", errors) };
+      },
+    };
+  }
+
+  function attach(form) {
+    var editors = [];
+
+    form.querySelectorAll(".argument-editor").forEach(function (holder) {
+      var textarea = holder.nextElementSibling;
+      var type;
+      try {
+        type = parseSignature(holder.dataset.signature);
+      } catch (e) {
+        return;
+      }
+      var initial;
+      try {
+        initial = textarea.value ? JSON.parse(textarea.value) : undefined;
+      } catch (e) {
+        // GVariant text, e.g. pasted from gdbus, is left for the server to check
+        return;
+      }
+      var editor = createEditor(type, initial);
+      holder.appendChild(editor.node);
+      textarea.style.display = "none";
+      editors.push({ name: holder.dataset.name, editor: editor, textarea: textarea });
+    });
+
+    form.addEventListener("submit", function (event) {
+      var errors = [];
+      editors.forEach(function (entry) {
+        var value = entry.editor.value(entry.name, errors);
+        entry.textarea.value = JSON.stringify(value);
+      });
+      form.querySelectorAll("input[data-signature]").forEach(function (input) {
+        if (GVARIANT_BASIC.test(input.value)) return;
+        parseBasic({ code: input.dataset.signature }, input.value, input.dataset.name, errors);
+      });
+
+      var report = form.querySelector(".editor-errors");
+      report.textContent = "";
+      if (errors.length) {
+        event.preventDefault();
+        errors.forEach(function (error) {
+          report.appendChild(element("div", null, error));
+        });
+      }
+    });
+  }
+
+  // Only argument forms have somewhere to report errors, others like the policy form are left alone
+  document.querySelectorAll("form.call-form").forEach(function (form) {
+    if (form.querySelector(".editor-errors")) attach(form);
+  });
+})();
@@ -0,0 +1,820 @@
+use std::{
+    collections::BTreeSet,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+use serde::Serialize;
+
+/// Where packages and administrators put the policies of system services.
+pub const POLICY_DIRECTORIES: [&str; 2] = ["/usr/share/dbus-1/system.d", "/etc/dbus-1/system.d"];
+
+/// Who a `<policy>` element applies to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyContext {
+    Default,
+    Mandatory,
+    User(String),
+    Group(String),
+    AtConsole(bool),
+}
+
+impl PolicyContext {
+    /// Policies are applied in this order, so later ones override earlier ones.
+    fn rank(&self) -> u8 {
+        match self {
+            PolicyContext::Default => 0,
+            PolicyContext::Group(_) => 1,
+            PolicyContext::User(_) => 2,
+            PolicyContext::AtConsole(_) => 3,
+            PolicyContext::Mandatory => 4,
+        }
+    }
+
+    pub fn applies_to(&self, identity: &Identity) -> bool {
+        match self {
+            PolicyContext::Default | PolicyContext::Mandatory => true,
+            PolicyContext::User(user) => identity
+                .user
+                .as_ref()
+                .is_some_and(|(name, uid)| user == "*" || user == name || *user == uid.to_string()),
+            PolicyContext::Group(group) => {
+                group == "*"
+                    || identity
+                        .groups
+                        .iter()
+                        .any(|(name, gid)| group == name || *group == gid.to_string())
+            }
+            PolicyContext::AtConsole(at_console) => *at_console == identity.at_console,
+        }
+    }
+}
+
+impl fmt::Display for PolicyContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyContext::Default => write!(f, "default"),
+            PolicyContext::Mandatory => write!(f, "mandatory"),
+            PolicyContext::User(user) => write!(f, "user {user}"),
+            PolicyContext::Group(group) => write!(f, "group {group}"),
+            PolicyContext::AtConsole(true) => write!(f, "at console"),
+            PolicyContext::AtConsole(false) => write!(f, "not at console"),
+        }
+    }
+}
+
+/// An `<allow>` or `<deny>` element.
+#[derive(Debug, Clone, Serialize)]
+pub struct PolicyRule {
+    /// Position in the whole configuration, unique and in the order the bus reads rules.
+    pub id: usize,
+    pub allow: bool,
+    pub context: PolicyContext,
+    /// The attributes as written, e.g. `send_destination` and `send_interface`.
+    pub attributes: Vec<(String, String)>,
+    pub file: PathBuf,
+    pub line: usize,
+}
+
+/// What a rule is about, decided by its attributes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleKind {
+    Own,
+    Send,
+    Receive,
+    /// `user` and `group` rules deciding who may connect at all.
+    Connect,
+    Other,
+}
+
+impl PolicyRule {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn kind(&self) -> RuleKind {
+        let has_prefix = |prefix: &str| self.attributes.iter().any(|(k, _)| k.starts_with(prefix));
+        if has_prefix("own") {
+            RuleKind::Own
+        } else if has_prefix("send_") {
+            RuleKind::Send
+        } else if has_prefix("receive_") {
+            RuleKind::Receive
+        } else if self.attribute("user").is_some() || self.attribute("group").is_some() {
+            RuleKind::Connect
+        } else {
+            RuleKind::Other
+        }
+    }
+
+    /// Whether the rule is about owning the name.
+    pub fn matches_own(&self, name: &str) -> bool {
+        self.kind() == RuleKind::Own
+            && self
+                .attribute("own")
+                .is_none_or(|own| own == "*" || own == name)
+            && self
+                .attribute("own_prefix")
+                .is_none_or(|prefix| has_name_prefix(name, prefix))
+    }
+
+    /// Whether the rule names the service as destination, by name, prefix or `*`.
+    ///
+    /// Rules without a destination apply to every service but are not about this one.
+    pub fn names_destination(&self, service_name: &str) -> bool {
+        self.kind() == RuleKind::Send
+            && (self
+                .attribute("send_destination")
+                .is_some_and(|d| d == "*" || d == service_name)
+                || self
+                    .attribute("send_destination_prefix")
+                    .is_some_and(|prefix| has_name_prefix(service_name, prefix)))
+    }
+
+    /// Whether the rule names the service as sender, by name or `*`.
+    pub fn names_sender(&self, service_name: &str) -> bool {
+        self.kind() == RuleKind::Receive
+            && self
+                .attribute("receive_sender")
+                .is_some_and(|s| s == "*" || s == service_name)
+    }
+
+    /// Whether the rule applies to a method call sent to a service.
+    ///
+    /// The bus matches `send_destination` against every name of the receiving connection, here
+    /// only the name the call is addressed to is considered.
+    pub fn matches_send(&self, call: &MethodCallTarget) -> bool {
+        if self.kind() != RuleKind::Send {
+            return false;
+        }
+        self.attributes
+            .iter()
+            .all(|(key, value)| match key.as_str() {
+                "send_destination" => value == "*" || *value == call.destination,
+                "send_destination_prefix" => has_name_prefix(&call.destination, value),
+                "send_interface" => value == "*" || *value == call.interface,
+                "send_member" => value == "*" || *value == call.member,
+                "send_path" => *value == call.path,
+                "send_type" => value == "*" || value == "method_call",
+                // The bus only consults this for replies, method calls are matched regardless
+                "send_requested_reply" => true,
+                // Method calls are never errors or broadcasts
+                "send_broadcast" => value == "false",
+                "send_error" => false,
+                // Better to miss a rule than to make one look broader than it is
+                key if key.starts_with("send_") => false,
+                // Logging and file descriptor limits do not restrict matching
+                _ => true,
+            })
+    }
+}
+
+/// Where a method call goes, as far as the policy is concerned.
+#[derive(Debug, Clone)]
+pub struct MethodCallTarget {
+    pub destination: String,
+    pub path: String,
+    pub interface: String,
+    pub member: String,
+}
+
+/// A user as the bus sees it when deciding which policies apply.
+#[derive(Debug, Clone)]
+pub struct Identity {
+    /// Name and ID of the user, `None` when only group membership is evaluated.
+    pub user: Option<(String, u32)>,
+    /// Names and IDs of all groups of the user.
+    pub groups: Vec<(String, u32)>,
+    pub at_console: bool,
+}
+
+impl Identity {
+    /// The user and groups this process runs as.
+    pub fn current() -> Option<Self> {
+        let status = fs::read_to_string("/proc/self/status").ok()?;
+        let field = |name: &str| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix(name))
+                .map(|rest| {
+                    rest.split_whitespace()
+                        .filter_map(|id| id.parse::<u32>().ok())
+                        .collect::<Vec<_>>()
+                })
+        };
+        // The effective ID is the second of real, effective, saved and filesystem ID
+        let uid = *field("Uid:")?.get(1)?;
+        let gid = *field("Gid:")?.get(1)?;
+        let mut gids = field("Groups:").unwrap_or_default();
+        if !gids.contains(&gid) {
+            gids.insert(0, gid);
+        }
+
+        let groups = read_groups();
+        Some(Self {
+            user: Some((
+                read_users()
+                    .into_iter()
+                    .find(|user| user.uid == uid)
+                    .map(|user| user.name)
+                    .unwrap_or_else(|| uid.to_string()),
+                uid,
+            )),
+            groups: gids
+                .into_iter()
+                .map(|gid| {
+                    let name = groups
+                        .iter()
+                        .find(|group| group.gid == gid)
+                        .map(|group| group.name.clone())
+                        .unwrap_or_else(|| gid.to_string());
+                    (name, gid)
+                })
+                .collect(),
+            at_console: false,
+        })
+    }
+
+    /// A user from `/etc/passwd`, by name or ID, with its primary and supplementary groups.
+    pub fn for_user(name: &str) -> Option<Self> {
+        let user = read_users()
+            .into_iter()
+            .find(|user| user.name == name || user.uid.to_string() == name)?;
+        let groups = read_groups()
+            .into_iter()
+            .filter(|group| group.gid == user.gid || group.members.contains(&user.name))
+            .map(|group| (group.name, group.gid))
+            .collect();
+        Some(Self {
+            user: Some((user.name, user.uid)),
+            groups,
+            at_console: false,
+        })
+    }
+
+    /// Any member of a group from `/etc/group`, by name or ID.
+    ///
+    /// Only the policies of the group and the default and mandatory ones apply.
+    pub fn for_group(name: &str) -> Option<Self> {
+        let group = read_groups()
+            .into_iter()
+            .find(|group| group.name == name || group.gid.to_string() == name)?;
+        Some(Self {
+            user: None,
+            groups: vec![(group.name, group.gid)],
+            at_console: false,
+        })
+    }
+}
+
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.user {
+            Some((name, uid)) => write!(f, "user {name} ({uid})")?,
+            None => write!(f, "any member")?,
+        }
+        let groups: Vec<String> = self
+            .groups
+            .iter()
+            .map(|(name, gid)| format!("{name} ({gid})"))
+            .collect();
+        write!(f, " of groups {}", groups.join(", "))
+    }
+}
+
+/// Who to evaluate the policy for, as picked on an object page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicySubject {
+    /// The user this process runs as.
+    Current,
+    User(String),
+    Group(String),
+}
+
+impl PolicySubject {
+    /// From a kind (`self`, `user` or `group`) and, unless it is `self`, a name.
+    pub fn from_parts(kind: &str, name: Option<&str>) -> Option<Self> {
+        let name = name.map(str::trim).filter(|name| !name.is_empty());
+        match (kind, name) {
+            ("self", _) => Some(PolicySubject::Current),
+            ("user", Some(name)) => Some(PolicySubject::User(name.to_string())),
+            ("group", Some(name)) => Some(PolicySubject::Group(name.to_string())),
+            _ => None,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PolicySubject::Current => "self",
+            PolicySubject::User(_) => "user",
+            PolicySubject::Group(_) => "group",
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            PolicySubject::Current => "",
+            PolicySubject::User(name) | PolicySubject::Group(name) => name,
+        }
+    }
+
+    pub fn identity(&self) -> Option<Identity> {
+        match self {
+            PolicySubject::Current => Identity::current(),
+            PolicySubject::User(name) => Identity::for_user(name),
+            PolicySubject::Group(name) => Identity::for_group(name),
+        }
+    }
+}
+
+struct UserEntry {
+    name: String,
+    uid: u32,
+    gid: u32,
+}
+
+struct GroupEntry {
+    name: String,
+    gid: u32,
+    members: Vec<String>,
+}
+
+fn read_users() -> Vec<UserEntry> {
+    read_colon_separated("/etc/passwd", |fields| {
+        Some(UserEntry {
+            name: fields.first()?.to_string(),
+            uid: fields.get(2)?.parse().ok()?,
+            gid: fields.get(3)?.parse().ok()?,
+        })
+    })
+}
+
+fn read_groups() -> Vec<GroupEntry> {
+    read_colon_separated("/etc/group", |fields| {
+        Some(GroupEntry {
+            name: fields.first()?.to_string(),
+            gid: fields.get(2)?.parse().ok()?,
+            members: fields
+                .get(3)
+                .map(|members| {
+                    members
+                        .split(',')
+                        .filter(|m| !m.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    })
+}
+
+fn read_colon_separated<T>(path: &str, parse: impl Fn(&[&str]) -> Option<T>) -> Vec<T> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| parse(&line.split(':').collect::<Vec<_>>()))
+        .collect()
+}
+
+/// The outcome of checking a message against the policy.
+#[derive(Debug, Clone)]
+pub struct Verdict<'a> {
+    pub allowed: bool,
+    /// The rule that decided, `None` if no rule matched and the message is denied by default.
+    pub rule: Option<&'a PolicyRule>,
+}
+
+/// The security policy of the bus, as read from its configuration files.
+#[derive(Debug, Clone, Default)]
+pub struct BusPolicy {
+    /// Every file read, in order.
+    pub files: Vec<PathBuf>,
+    pub rules: Vec<PolicyRule>,
+    /// Files that could not be read or parsed.
+    pub errors: Vec<String>,
+}
+
+impl BusPolicy {
+    /// Read a bus configuration file and everything it includes.
+    ///
+    /// Without the main file, only the service policies in [`POLICY_DIRECTORIES`] are read.
+    pub fn load(config_file: &Path) -> Self {
+        let mut policy = Self::default();
+        let mut visited = BTreeSet::new();
+        if config_file.exists() {
+            policy.load_file(config_file, false, &mut visited);
+        } else {
+            for directory in POLICY_DIRECTORIES {
+                for file in config_files(Path::new(directory)) {
+                    policy.load_file(&file, false, &mut visited);
+                }
+            }
+        }
+        policy
+    }
+
+    /// The rules that apply to `identity`, in the order the bus applies them.
+    fn rules_for(&self, identity: &Identity) -> impl Iterator<Item = &PolicyRule> + use<'_> {
+        let mut rules: Vec<&PolicyRule> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.context.applies_to(identity))
+            .collect();
+        rules.sort_by_key(|rule| (rule.context.rank(), rule.id));
+        rules.into_iter()
+    }
+
+    /// Decide whether `identity` may make a method call, the last matching rule wins.
+    pub fn check_send(&self, identity: &Identity, call: &MethodCallTarget) -> Verdict<'_> {
+        let rule = self
+            .rules_for(identity)
+            .filter(|rule| rule.matches_send(call))
+            .last();
+        Verdict {
+            allowed: rule.is_some_and(|rule| rule.allow),
+            rule,
+        }
+    }
+
+    fn load_file(&mut self, path: &Path, ignore_missing: bool, visited: &mut BTreeSet<PathBuf>) {
+        if !visited.insert(path.to_path_buf()) {
+            return;
+        }
+        let xml = match fs::read_to_string(path) {
+            Ok(xml) => xml,
+            Err(_) if ignore_missing => return,
+            Err(e) => {
+                self.errors
+                    .push(format!("Failed to read {}: {e}", path.display()));
+                return;
+            }
+        };
+        self.files.push(path.to_path_buf());
+
+        if let Err(e) = self.parse(path, &xml, visited) {
+            self.errors
+                .push(format!("Failed to parse {}: {e}", path.display()));
+        }
+    }
+
+    fn parse(
+        &mut self,
+        path: &Path,
+        xml: &str,
+        visited: &mut BTreeSet<PathBuf>,
+    ) -> Result<(), quick_xml::Error> {
+        let directory = path.parent().unwrap_or(Path::new("/"));
+        let mut reader = Reader::from_str(xml);
+        reader.config_mut().trim_text(true);
+
+        let mut context = None;
+        // Set while reading the text of an include element
+        let mut include: Option<(bool, bool)> = None;
+        loop {
+            // Trimmed whitespace is skipped together with the next event, so skip it here too
+            let position = reader.buffer_position() as usize;
+            let rest = xml.get(position..).unwrap_or_default();
+            let position = position + rest.len() - rest.trim_start().len();
+            match reader.read_event()? {
+                Event::Start(element) | Event::Empty(element)
+                    if element.name().as_ref() == b"policy" =>
+                {
+                    context = policy_context(&element);
+                }
+                Event::End(element) if element.name().as_ref() == b"policy" => context = None,
+                Event::Start(element) | Event::Empty(element)
+                    if matches!(element.name().as_ref(), b"allow" | b"deny") =>
+                {
+                    let Some(context) = &context else {
+                        continue;
+                    };
+                    self.rules.push(PolicyRule {
+                        id: self.rules.len(),
+                        allow: element.name().as_ref() == b"allow",
+                        context: context.clone(),
+                        attributes: attributes(&element),
+                        file: path.to_path_buf(),
+                        line: xml[..position].matches('\n').count() + 1,
+                    });
+                }
+                Event::Start(element) if element.name().as_ref() == b"include" => {
+                    let attributes = attributes(&element);
+                    let attribute = |name: &str| {
+                        attributes
+                            .iter()
+                            .any(|(key, value)| key == name && value == "yes")
+                    };
+                    include = Some((
+                        false,
+                        attribute("ignore_missing") || attribute("if_selinux_enabled"),
+                    ));
+                }
+                Event::Start(element) if element.name().as_ref() == b"includedir" => {
+                    include = Some((true, true));
+                }
+                Event::Text(text) => {
+                    let Some((is_directory, ignore_missing)) = include.take() else {
+                        continue;
+                    };
+                    let included = directory.join(text.unescape()?.trim());
+                    if is_directory {
+                        for file in config_files(&included) {
+                            self.load_file(&file, false, visited);
+                        }
+                    } else {
+                        self.load_file(&included, ignore_missing, visited);
+                    }
+                }
+                Event::End(element)
+                    if matches!(element.name().as_ref(), b"include" | b"includedir") =>
+                {
+                    include = None;
+                }
+                Event::Eof => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn policy_context(element: &BytesStart) -> Option<PolicyContext> {
+    attributes(element)
+        .into_iter()
+        .find_map(|(key, value)| match key.as_str() {
+            "context" if value == "default" => Some(PolicyContext::Default),
+            "context" if value == "mandatory" => Some(PolicyContext::Mandatory),
+            "user" => Some(PolicyContext::User(value)),
+            "group" => Some(PolicyContext::Group(value)),
+            "at_console" => Some(PolicyContext::AtConsole(value == "true")),
+            _ => None,
+        })
+}
+
+fn attributes(element: &BytesStart) -> Vec<(String, String)> {
+    element
+        .attributes()
+        .filter_map(Result::ok)
+        .filter_map(|attribute| {
+            let key = String::from_utf8_lossy(attribute.key.as_ref()).into_owned();
+            let value = attribute.unescape_value().ok()?.into_owned();
+            Some((key, value))
+        })
+        .collect()
+}
+
+/// The `*.conf` files of an `includedir`, in the order the bus reads them.
+fn config_files(directory: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(directory)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "conf")
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Whether `name` is `prefix` or starts with `prefix` followed by a `.`.
+fn has_name_prefix(name: &str, prefix: &str) -> bool {
+    name.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `files` to a fresh directory and load the first one as the bus configuration.
+    fn load(test: &str, files: &[(&str, &str)]) -> (PathBuf, BusPolicy) {
+        let directory =
+            std::env::temp_dir().join(format!("dbus_explorer-{}-{test}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        for (name, content) in files {
+            let path = directory.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        let policy = BusPolicy::load(&directory.join(files[0].0));
+        (directory, policy)
+    }
+
+    fn config(policies: &str) -> String {
+        format!("<busconfig>\n{policies}\n</busconfig>\n")
+    }
+
+    fn identity() -> Identity {
+        Identity {
+            user: Some(("alice".to_string(), 1000)),
+            groups: vec![("staff".to_string(), 50)],
+            at_console: true,
+        }
+    }
+
+    fn call(destination: &str) -> MethodCallTarget {
+        MethodCallTarget {
+            destination: destination.to_string(),
+            path: "/com/example".to_string(),
+            interface: "com.example.Thing".to_string(),
+            member: "Do".to_string(),
+        }
+    }
+
+    #[test]
+    fn includes() {
+        let (directory, policy) = load(
+            "includes",
+            &[
+                (
+                    "system.conf",
+                    "<busconfig>\n  <include>extra.conf</include>\n  <include ignore_missing=\"yes\">missing.conf</include>\n  <include>gone.conf</include>\n  <includedir>system.d</includedir>\n</busconfig>\n",
+                ),
+                // Includes the main file again, which must not loop
+                (
+                    "extra.conf",
+                    "<busconfig><include>system.conf</include></busconfig>",
+                ),
+                ("system.d/b.conf", "<busconfig/>"),
+                ("system.d/a.conf", "<busconfig/>"),
+                ("system.d/notes.txt", "not a policy"),
+            ],
+        );
+
+        let files: Vec<PathBuf> = policy
+            .files
+            .iter()
+            .map(|f| f.strip_prefix(&directory).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            files,
+            [
+                "system.conf",
+                "extra.conf",
+                "system.d/a.conf",
+                "system.d/b.conf"
+            ]
+            .map(PathBuf::from)
+        );
+        assert_eq!(policy.errors.len(), 1, "{:?}", policy.errors);
+        assert!(policy.errors[0].contains("gone.conf"));
+        let _ = fs::remove_dir_all(directory);
+    }
+
+    #[test]
+    fn contexts_are_applied_in_order() {
+        // Listed against the order the bus applies them in, which must not matter
+        let (directory, policy) = load(
+            "contexts",
+            &[(
+                "system.conf",
+                &config(
+                    r#"<policy at_console="true"><allow send_destination="com.example.Console"/></policy>
+<policy user="alice"><deny send_destination="com.example.Console"/><allow send_destination="com.example.User"/></policy>
+<policy group="staff"><deny send_destination="com.example.User"/><allow send_destination="com.example.Group"/></policy>
+<policy context="default"><deny send_destination="com.example.Group"/><allow send_destination="*"/></policy>
+<policy context="mandatory"><deny send_destination="com.example.Mandatory"/></policy>
+<policy user="bob"><deny send_destination="*"/></policy>"#,
+                ),
+            )],
+        );
+
+        let verdict = |destination: &str| {
+            let verdict = policy.check_send(&identity(), &call(destination));
+            (verdict.allowed, verdict.rule.map(|r| r.context.clone()))
+        };
+        assert_eq!(
+            verdict("com.example.Group"),
+            (true, Some(PolicyContext::Group("staff".to_string())))
+        );
+        assert_eq!(
+            verdict("com.example.User"),
+            (true, Some(PolicyContext::User("alice".to_string())))
+        );
+        assert_eq!(
+            verdict("com.example.Console"),
+            (true, Some(PolicyContext::AtConsole(true)))
+        );
+        assert_eq!(
+            verdict("com.example.Mandatory"),
+            (false, Some(PolicyContext::Mandatory))
+        );
+        assert_eq!(
+            verdict("com.example.Other"),
+            (true, Some(PolicyContext::Default))
+        );
+        let _ = fs::remove_dir_all(directory);
+    }
+
+    #[test]
+    fn last_match_wins() {
+        let (directory, policy) = load(
+            "last_match",
+            &[(
+                "system.conf",
+                &config(
+                    r#"<policy context="default">
+<deny send_destination="com.example.Thing"/>
+<allow send_destination="com.example.Thing" send_member="Do"/>
+<deny send_destination="com.example.Thing" send_member="Do" send_path="/com/example"/>
+<allow send_destination="com.example.Thing" send_member="Other"/>
+</policy>"#,
+                ),
+            )],
+        );
+
+        let verdict = policy.check_send(&identity(), &call("com.example.Thing"));
+        assert!(!verdict.allowed);
+        assert_eq!(verdict.rule.map(|r| r.id), Some(2));
+
+        let mut elsewhere = call("com.example.Thing");
+        elsewhere.path = "/".to_string();
+        let verdict = policy.check_send(&identity(), &elsewhere);
+        assert!(verdict.allowed);
+        assert_eq!(verdict.rule.map(|r| r.id), Some(1));
+
+        let verdict = policy.check_send(&identity(), &call("com.example.Unknown"));
+        assert!(!verdict.allowed);
+        assert!(verdict.rule.is_none());
+        let _ = fs::remove_dir_all(directory);
+    }
+
+    #[test]
+    fn destination_prefixes() {
+        let (directory, policy) = load(
+            "prefixes",
+            &[(
+                "system.conf",
+                &config(
+                    r#"<policy context="default"><allow send_destination_prefix="com.example"/></policy>"#,
+                ),
+            )],
+        );
+
+        let allowed =
+            |destination: &str| policy.check_send(&identity(), &call(destination)).allowed;
+        assert!(allowed("com.example"));
+        assert!(allowed("com.example.Thing"));
+        assert!(allowed("com.example.Thing.Sub"));
+        assert!(!allowed("com.examples"));
+        assert!(!allowed("com.exam"));
+        assert!(policy.rules[0].names_destination("com.example.Thing"));
+        assert!(!policy.rules[0].names_destination("org.example"));
+        let _ = fs::remove_dir_all(directory);
+    }
+
+    #[test]
+    fn send_attributes() {
+        let rule = |attributes: &[(&str, &str)]| PolicyRule {
+            id: 0,
+            allow: true,
+            context: PolicyContext::Default,
+            attributes: attributes
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            file: PathBuf::new(),
+            line: 1,
+        };
+        let thing = call("com.example.Thing");
+        assert!(rule(&[("send_broadcast", "false")]).matches_send(&thing));
+        assert!(!rule(&[("send_broadcast", "true")]).matches_send(&thing));
+        assert!(!rule(&[("send_error", "*")]).matches_send(&thing));
+        for requested in ["true", "false"] {
+            let attributes = [
+                ("send_destination", "com.example.Thing"),
+                ("send_requested_reply", requested),
+            ];
+            assert!(rule(&attributes).matches_send(&thing), "{requested:?}");
+        }
+        assert!(!rule(&[("send_path_prefix", "/com")]).matches_send(&thing));
+        assert!(rule(&[("send_type", "method_call"), ("log", "true")]).matches_send(&thing));
+        assert!(!rule(&[("send_type", "signal")]).matches_send(&thing));
+        assert!(!rule(&[("receive_sender", "*")]).matches_send(&thing));
+    }
+
+    #[test]
+    fn line_numbers() {
+        let (directory, policy) = load(
+            "lines",
+            &[(
+                "system.conf",
+                "<!DOCTYPE busconfig>\n<busconfig>\n  <!-- a comment\n       over two lines -->\n  <policy context=\"default\">\n    <allow own=\"com.example\"/>\n\n    <deny\n        send_destination=\"com.example\"/>\n  </policy>\n  <policy user=\"root\"><allow own=\"*\"/><deny own=\"org.example\"/></policy>\n</busconfig>\n",
+            )],
+        );
+
+        let lines: Vec<usize> = policy.rules.iter().map(|r| r.line).collect();
+        assert_eq!(lines, [6, 8, 11, 11]);
+        let _ = fs::remove_dir_all(directory);
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use dbus::{arg::messageitem::MessageItem, blocking::Connection};
+
+use crate::{
+    dbus_calls::{call_method, vardict_reply},
+    dbus_introspection::list_names,
+};
+
+const BUS_NAME: &str = "org.freedesktop.DBus";
+const BUS_PATH: &str = "/org/freedesktop/DBus";
+const STATS_INTERFACE: &str = "org.freedesktop.DBus.Debug.Stats";
+
+/// Statistics of the bus daemon, as reported by `org.freedesktop.DBus.Debug.Stats`.
+#[derive(Debug, Clone)]
+pub struct BusStats {
+    pub bus: BTreeMap<String, MessageItem>,
+    /// Sorted by number of match rules, most first.
+    pub connections: Vec<ConnectionStats>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConnectionStats {
+    pub unique_name: String,
+    /// The well-known names the connection owns.
+    pub names: Vec<String>,
+    pub stats: std::result::Result<BTreeMap<String, MessageItem>, String>,
+    pub match_rules: Vec<String>,
+}
+
+impl ConnectionStats {
+    /// A numeric statistic, 0 if it is not reported.
+    pub fn number(&self, key: &str) -> u64 {
+        self.stats
+            .as_ref()
+            .ok()
+            .and_then(|stats| stats.get(key))
+            .and_then(number)
+            .unwrap_or_default()
+    }
+}
+
+/// Read the statistics of the bus and of every connection to it.
+///
+/// Fails if the bus daemon was built without statistics or does not let us read them.
+pub fn read_bus_stats(conn: &Connection) -> Result<BusStats> {
+    let bus = vardict_reply(
+        call_method(conn, BUS_NAME, BUS_PATH, STATS_INTERFACE, "GetStats", &[])?,
+        "GetStats",
+    )?;
+    let mut match_rules = read_match_rules(conn)?;
+
+    let names = list_names(conn)?;
+    let mut owned_names: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for name in names.iter().filter(|name| !name.starts_with(':')) {
+        let owner = call_method(
+            conn,
+            BUS_NAME,
+            BUS_PATH,
+            BUS_NAME,
+            "GetNameOwner",
+            &[MessageItem::Str(name.clone())],
+        );
+        // The bus owns its own name but has no connection
+        if let Ok([MessageItem::Str(owner)]) = owner.as_deref() {
+            owned_names
+                .entry(owner.clone())
+                .or_default()
+                .push(name.clone());
+        }
+    }
+
+    let mut connections: Vec<ConnectionStats> = names
+        .into_iter()
+        .filter(|name| name.starts_with(':'))
+        .map(|unique_name| {
+            let stats = call_method(
+                conn,
+                BUS_NAME,
+                BUS_PATH,
+                STATS_INTERFACE,
+                "GetConnectionStats",
+                &[MessageItem::Str(unique_name.clone())],
+            )
+            .and_then(|reply| vardict_reply(reply, "GetConnectionStats"))
+            .map_err(|e| format!("{e:#}"));
+            ConnectionStats {
+                names: owned_names.remove(&unique_name).unwrap_or_default(),
+                match_rules: match_rules.remove(&unique_name).unwrap_or_default(),
+                stats,
+                unique_name,
+            }
+        })
+        .collect();
+    connections.sort_by(|a, b| {
+        b.match_rules
+            .len()
+            .cmp(&a.match_rules.len())
+            .then_with(|| a.unique_name.cmp(&b.unique_name))
+    });
+
+    Ok(BusStats { bus, connections })
+}
+
+fn read_match_rules(conn: &Connection) -> Result<BTreeMap<String, Vec<String>>> {
+    let reply = call_method(
+        conn,
+        BUS_NAME,
+        BUS_PATH,
+        STATS_INTERFACE,
+        "GetAllMatchRules",
+        &[],
+    )?;
+
+    let Some(MessageItem::Dict(dict)) = reply.into_iter().next() else {
+        return Err(anyhow!("Unexpected reply to GetAllMatchRules"));
+    };
+    dict.into_vec()
+        .into_iter()
+        .map(|(name, rules)| match (name, rules) {
+            (MessageItem::Str(name), MessageItem::Array(rules)) => {
+                let rules = rules
+                    .into_vec()
+                    .into_iter()
+                    .filter_map(|rule| match rule {
+                        MessageItem::Str(rule) => Some(rule),
+                        _ => None,
+                    })
+                    .collect();
+                Ok((name, rules))
+            }
+            other => Err(anyhow!(
+                "Unexpected entry in reply to GetAllMatchRules: {other:?}"
+            )),
+        })
+        .collect()
+}
+
+/// The value of an unsigned statistic.
+pub fn number(item: &MessageItem) -> Option<u64> {
+    match item {
+        MessageItem::UInt32(n) => Some(u64::from(*n)),
+        MessageItem::UInt64(n) => Some(*n),
+        MessageItem::Int32(n) => u64::try_from(*n).ok(),
+        MessageItem::Int64(n) => u64::try_from(*n).ok(),
+        _ => None,
+    }
+}
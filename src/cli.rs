@@ -0,0 +1,425 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Parser, Subcommand};
+use dbus::blocking::Connection;
+use serde::Serialize;
+
+use crate::{
+    conformance::{compare_interfaces, load_expected_interfaces},
+    dbus_calls::{call_method, convert_inputs, get_property},
+    dbus_introspection::{
+        analyze_service, discover_services, get_service_names_only, introspect_object,
+        ArgumentInfo, ObjectInfo,
+    },
+    error::AppError,
+    gvariant::gvariant_text,
+    mock::{load_mock_services, load_responses, run_mock},
+    recorder::{replay, Recording, StepKind},
+    systemd::resolve_unit,
+    test_runner::{junit_xml, load_suite, run_suite},
+    utils::{
+        validate_interface_name, validate_member_name, validate_object_path, validate_service_name,
+    },
+    value_view::typed_value_to_json,
+};
+
+/// Introspect D-Bus APIs from the comfort of your browser or shell.
+#[derive(Debug, Parser)]
+pub struct Cli {
+    /// Print JSON instead of human-readable text
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the web server (default)
+    Serve,
+    /// List well-known names on the system bus
+    List,
+    /// List the objects and interfaces of a service
+    Tree { service: String },
+    /// Show the interfaces of one object
+    Introspect {
+        service: String,
+        path: String,
+        /// Print the raw introspection XML
+        #[arg(long)]
+        xml: bool,
+    },
+    /// Call a method, arguments are converted according to the introspected signature
+    Call {
+        service: String,
+        path: String,
+        interface: String,
+        method: String,
+        /// Negative numbers are arguments too, not options
+        #[arg(allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Read the value of a property
+    GetProperty {
+        service: String,
+        path: String,
+        interface: String,
+        property: String,
+    },
+    /// Compare an object with interface XML files and fail if it does not conform
+    Check {
+        service: String,
+        path: String,
+        /// Introspection XML files with the expected interfaces
+        #[arg(required = true)]
+        xml_files: Vec<PathBuf>,
+    },
+    /// Make the steps of a recording again and fail if any outcome differs from the recorded one
+    Replay {
+        /// A recording exported from the web server
+        file: PathBuf,
+    },
+    /// Run smoke tests described in YAML or JSON files and fail if any of them fails
+    Test {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Also write a JUnit XML report to this file
+        #[arg(long)]
+        junit: Option<PathBuf>,
+    },
+    /// Stand in for services recorded in a snapshot, or for one object described by an XML file
+    Mock {
+        /// A JSON snapshot, or an introspection XML file ending in .xml
+        file: PathBuf,
+        /// Only export this service; the name to export an XML file under
+        #[arg(long)]
+        service: Option<String>,
+        /// The object path to export an XML file at
+        #[arg(long)]
+        path: Option<String>,
+        /// Canned replies and property values in YAML or JSON
+        #[arg(long)]
+        responses: Option<PathBuf>,
+        /// Export on the session bus instead of the system bus
+        #[arg(long)]
+        session: bool,
+    },
+    /// Dump all services, objects and interfaces as JSON
+    Snapshot {
+        /// Only include services whose name contains this text
+        #[arg(long)]
+        filter: Option<String>,
+    },
+}
+
+/// Run a command other than `serve`.
+pub fn run(command: Command, json: bool) -> Result<()> {
+    // Mocks may run on the session bus, so they must not require the system bus
+    if let Command::Mock {
+        file,
+        service,
+        path,
+        responses,
+        session,
+    } = command
+    {
+        let services = load_mock_services(&file, service.as_deref(), path.as_deref())?;
+        let responses = load_responses(responses.as_ref())?;
+        return run_mock(services, responses, session);
+    }
+
+    let conn = Connection::new_system().context("Failed to connect to the system bus")?;
+
+    match command {
+        Command::Serve => unreachable!("serve is handled by main"),
+        Command::Mock { .. } => unreachable!("mock is handled above"),
+        Command::List => {
+            let names = get_service_names_only(&conn)?;
+            if json {
+                print_json(&names)?;
+            } else {
+                for name in names {
+                    println!("{name}");
+                }
+            }
+        }
+        Command::Tree { service } => {
+            validate_service_name(&service)?;
+            let mut service_info = analyze_service(&conn, &service);
+            resolve_unit(&conn, &mut service_info);
+            service_info.objects.sort_by(|a, b| a.path.cmp(&b.path));
+            if json {
+                print_json(&service_info)?;
+            } else {
+                if let Some(owner) = &service_info.owner {
+                    println!("{} ({owner})", service_info.name);
+                } else {
+                    println!("{}", service_info.name);
+                }
+                if let Some(unit) = &service_info.unit {
+                    println!(
+                        "  unit: {} ({}, {})",
+                        unit.name, unit.active_state, unit.sub_state
+                    );
+                }
+                if let Some(error) = &service_info.error {
+                    println!("  error: {error}");
+                }
+                for object in &service_info.objects {
+                    println!("  {}", object.path);
+                    if let Some(error) = &object.error {
+                        println!("    error: {error}");
+                    }
+                    for interface in &object.interfaces {
+                        println!("    {}", interface.name);
+                    }
+                }
+            }
+        }
+        Command::Introspect { service, path, xml } => {
+            validate_location(&service, &path)?;
+            let object = introspect_object(&conn, &service, &path)
+                .ok_or_else(|| anyhow!("Object not found: {service}:{path}"))?;
+            if json {
+                print_json(&object)?;
+            } else if xml {
+                println!("{}", object.xml.as_deref().unwrap_or_default());
+            } else {
+                print_object(&object);
+            }
+        }
+        Command::Call {
+            service,
+            path,
+            interface,
+            method,
+            args,
+        } => {
+            validate_location(&service, &path)?;
+            validate_interface_name(&interface)?;
+            validate_member_name(&method)?;
+            let object = introspect_object(&conn, &service, &path)
+                .ok_or_else(|| anyhow!("Object not found: {service}:{path}"))?;
+            let method_info = object
+                .interfaces
+                .iter()
+                .filter(|i| i.name == interface)
+                .flat_map(|i| &i.methods)
+                .find(|m| m.name == method)
+                .ok_or_else(|| anyhow!("Method not found: {interface}.{method}"))?;
+
+            let arguments = convert_inputs(method_info, &args)?;
+
+            let reply = call_method(&conn, &service, &path, &interface, &method, &arguments)?;
+            if json {
+                print_json(&reply.iter().map(typed_value_to_json).collect::<Vec<_>>())?;
+            } else {
+                for item in &reply {
+                    println!("{}", gvariant_text(item, true));
+                }
+            }
+        }
+        Command::GetProperty {
+            service,
+            path,
+            interface,
+            property,
+        } => {
+            validate_location(&service, &path)?;
+            validate_interface_name(&interface)?;
+            validate_member_name(&property)?;
+            let value = get_property(&conn, &service, &path, &interface, &property)?;
+            if json {
+                print_json(&typed_value_to_json(&value))?;
+            } else {
+                println!("{}", gvariant_text(&value, true));
+            }
+        }
+        Command::Check {
+            service,
+            path,
+            xml_files,
+        } => {
+            validate_location(&service, &path)?;
+            let expected = load_expected_interfaces(&xml_files)?;
+            let object = introspect_object(&conn, &service, &path)
+                .ok_or_else(|| anyhow!("Object not found: {service}:{path}"))?;
+            if let Some(error) = &object.error {
+                return Err(anyhow!("Failed to introspect {service}:{path}: {error}"));
+            }
+
+            let differences = compare_interfaces(&expected, &object.interfaces);
+            if json {
+                print_json(&differences)?;
+            } else {
+                for difference in &differences {
+                    println!("{difference}");
+                }
+            }
+            if !differences.is_empty() {
+                return Err(anyhow!(
+                    "{service}:{path} does not conform, {} difference(s)",
+                    differences.len()
+                ));
+            }
+            if !json {
+                println!(
+                    "{service}:{path} conforms to {} interface(s)",
+                    expected.len()
+                );
+            }
+        }
+        Command::Replay { file } => {
+            let recording: Recording = serde_json::from_str(
+                &std::fs::read_to_string(&file)
+                    .with_context(|| format!("Failed to read {}", file.display()))?,
+            )
+            .with_context(|| format!("Failed to parse {}", file.display()))?;
+
+            let replayed = replay(&conn, &recording);
+            if json {
+                print_json(&replayed)?;
+            } else {
+                for (i, step) in replayed.iter().enumerate() {
+                    let expected = &step.expected;
+                    let kind = match expected.kind {
+                        StepKind::Call => "call",
+                        StepKind::SetProperty => "set",
+                    };
+                    let outcome = if step.divergences.is_empty() {
+                        "ok"
+                    } else {
+                        "diverged"
+                    };
+                    println!(
+                        "{} {kind} {} {} {}.{}: {outcome}",
+                        i + 1,
+                        expected.service,
+                        expected.path,
+                        expected.interface,
+                        expected.member
+                    );
+                    for divergence in &step.divergences {
+                        println!("    {divergence}");
+                    }
+                }
+            }
+
+            let diverged = replayed
+                .iter()
+                .filter(|step| !step.divergences.is_empty())
+                .count();
+            if diverged > 0 {
+                return Err(anyhow!(
+                    "{diverged} of {} step(s) diverged from the recording",
+                    replayed.len()
+                ));
+            }
+        }
+        Command::Test { files, junit } => {
+            let suites = files
+                .iter()
+                .map(|file| load_suite(file))
+                .collect::<Result<Vec<_>>>()?;
+            let reports: Vec<_> = suites.iter().map(|suite| run_suite(&conn, suite)).collect();
+
+            if let Some(junit) = &junit {
+                std::fs::write(junit, junit_xml(&reports))
+                    .with_context(|| format!("Failed to write {}", junit.display()))?;
+            }
+            if json {
+                print_json(&reports)?;
+            } else {
+                for report in &reports {
+                    println!("{}", report.name);
+                    for test in &report.tests {
+                        match test.failure() {
+                            None => println!("  PASS {} ({:.1} ms)", test.name, test.duration_ms),
+                            Some(step) => {
+                                println!("  FAIL {}", test.name);
+                                println!("    {}", step.description);
+                                println!("    {}", step.failure.as_deref().unwrap_or_default());
+                            }
+                        }
+                    }
+                }
+            }
+
+            let tests: usize = reports.iter().map(|r| r.tests.len()).sum();
+            let failures: usize = reports.iter().map(|r| r.failures()).sum();
+            if failures > 0 {
+                return Err(anyhow!("{failures} of {tests} test(s) failed"));
+            }
+        }
+        Command::Snapshot { filter } => {
+            let services = discover_services(&conn, filter.as_deref())?;
+            print_json(&services)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_location(service: &str, path: &str) -> Result<()> {
+    validate_service_name(service)?;
+    validate_object_path(path)?;
+    Ok(())
+}
+
+/// Print a failed command as JSON, with the D-Bus error behind it if there is one.
+pub fn print_error_json(error: &anyhow::Error) {
+    let json = match error.chain().find_map(|e| e.downcast_ref::<AppError>()) {
+        Some(app_error) => app_error.to_json(),
+        None => serde_json::json!({ "message": format!("{error:#}") }),
+    };
+    println!("{json:#}");
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn print_object(object: &ObjectInfo) {
+    println!("{}", object.path);
+    if let Some(error) = &object.error {
+        println!("  error: {error}");
+    }
+
+    for interface in &object.interfaces {
+        println!("  interface {}", interface.name);
+        for method in &interface.methods {
+            let arguments = format_arguments(&method.arguments);
+            if method.return_values.is_empty() {
+                println!("    method {}({arguments})", method.name);
+            } else {
+                let returns = format_arguments(&method.return_values);
+                println!("    method {}({arguments}) → {returns}", method.name);
+            }
+        }
+        for property in &interface.properties {
+            println!(
+                "    property {}: {} [{}]",
+                property.name, property.type_name, property.access
+            );
+        }
+        for signal in &interface.signals {
+            let arguments = format_arguments(&signal.arguments);
+            println!("    signal {}({arguments})", signal.name);
+        }
+    }
+}
+
+fn format_arguments(arguments: &[ArgumentInfo]) -> String {
+    arguments
+        .iter()
+        .map(|arg| format!("{}: {}", argument_name(arg), arg.type_name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn argument_name(arg: &ArgumentInfo) -> &str {
+    arg.name.as_deref().unwrap_or("_")
+}
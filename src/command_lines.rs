@@ -0,0 +1,654 @@
+use dbus::arg::messageitem::MessageItem;
+
+use crate::{
+    dbus_introspection::{ArgumentInfo, MethodInfo, PropertyInfo, SignalInfo},
+    gvariant::{gvariant_string, gvariant_text},
+};
+
+/// Equivalent invocations of the common D-Bus command line tools.
+#[derive(Debug, Clone)]
+pub struct CommandLines {
+    pub busctl: String,
+    /// `None` when the signature cannot be expressed with `dbus-send`.
+    pub dbus_send: Option<String>,
+    pub gdbus: String,
+}
+
+/// An argument is either a concrete value or a placeholder named after the argument.
+#[derive(Debug, Clone)]
+enum Argument<'a> {
+    Value(&'a MessageItem),
+    Placeholder(String, &'a str),
+}
+
+impl Argument<'_> {
+    fn type_name(&self) -> String {
+        match self {
+            Argument::Value(item) => item.signature().to_string(),
+            Argument::Placeholder(_, type_name) => type_name.to_string(),
+        }
+    }
+}
+
+pub fn method_command_lines(
+    service_name: &str,
+    object_path: &str,
+    interface: &str,
+    method: &MethodInfo,
+    values: Option<&[MessageItem]>,
+) -> CommandLines {
+    let arguments = arguments(&method.arguments, values);
+    let member = format!("{interface}.{}", method.name);
+
+    CommandLines {
+        busctl: format!(
+            "busctl --system call {} {} {} {}{}",
+            shell_quote(service_name),
+            shell_quote(object_path),
+            shell_quote(interface),
+            shell_quote(&method.name),
+            busctl_arguments(&arguments)
+        ),
+        dbus_send: dbus_send_arguments(&arguments).map(|args| {
+            format!(
+                "dbus-send --system --print-reply --dest={} {} {}{args}",
+                shell_quote(service_name),
+                shell_quote(object_path),
+                shell_quote(&member)
+            )
+        }),
+        gdbus: format!(
+            "gdbus call --system --dest {} --object-path {} --method {}{}",
+            shell_quote(service_name),
+            shell_quote(object_path),
+            shell_quote(&member),
+            gdbus_arguments(&arguments)
+        ),
+    }
+}
+
+pub fn get_property_command_lines(
+    service_name: &str,
+    object_path: &str,
+    interface: &str,
+    property: &PropertyInfo,
+) -> CommandLines {
+    CommandLines {
+        busctl: format!(
+            "busctl --system get-property {} {} {} {}",
+            shell_quote(service_name),
+            shell_quote(object_path),
+            shell_quote(interface),
+            shell_quote(&property.name)
+        ),
+        dbus_send: Some(format!(
+            "dbus-send --system --print-reply --dest={} {} org.freedesktop.DBus.Properties.Get {} {}",
+            shell_quote(service_name),
+            shell_quote(object_path),
+            shell_quote(&format!("string:{interface}")),
+            shell_quote(&format!("string:{}", property.name))
+        )),
+        gdbus: format!(
+            "gdbus call --system --dest {} --object-path {} --method org.freedesktop.DBus.Properties.Get {} {}",
+            shell_quote(service_name),
+            shell_quote(object_path),
+            shell_quote(&gvariant_string(interface)),
+            shell_quote(&gvariant_string(&property.name))
+        ),
+    }
+}
+
+pub fn set_property_command_lines(
+    service_name: &str,
+    object_path: &str,
+    interface: &str,
+    property: &PropertyInfo,
+    value: Option<&MessageItem>,
+) -> CommandLines {
+    let argument = match value {
+        Some(item) => Argument::Value(item),
+        None => Argument::Placeholder(property.name.clone(), &property.type_name),
+    };
+
+    CommandLines {
+        busctl: format!(
+            "busctl --system set-property {} {} {} {}{}",
+            shell_quote(service_name),
+            shell_quote(object_path),
+            shell_quote(interface),
+            shell_quote(&property.name),
+            busctl_arguments(std::slice::from_ref(&argument))
+        ),
+        dbus_send: dbus_send_argument(&argument, true).map(|value| {
+            format!(
+                "dbus-send --system --print-reply --dest={} {} org.freedesktop.DBus.Properties.Set {} {} {}",
+                shell_quote(service_name),
+                shell_quote(object_path),
+                shell_quote(&format!("string:{interface}")),
+                shell_quote(&format!("string:{}", property.name)),
+                value
+            )
+        }),
+        gdbus: format!(
+            "gdbus call --system --dest {} --object-path {} --method org.freedesktop.DBus.Properties.Set {} {} {}",
+            shell_quote(service_name),
+            shell_quote(object_path),
+            shell_quote(&gvariant_string(interface)),
+            shell_quote(&gvariant_string(&property.name)),
+            match argument {
+                Argument::Value(item) => shell_quote(&format!("<{}>", gvariant_text(item, true))),
+                Argument::Placeholder(name, _) => format!("<{name}>"),
+            }
+        ),
+    }
+}
+
+pub fn emit_signal_command_lines(
+    object_path: &str,
+    interface: &str,
+    signal: &SignalInfo,
+    values: Option<&[MessageItem]>,
+) -> CommandLines {
+    let arguments = arguments(&signal.arguments, values);
+    let member = format!("{interface}.{}", signal.name);
+
+    CommandLines {
+        busctl: format!(
+            "busctl --system emit {} {} {}{}",
+            shell_quote(object_path),
+            shell_quote(interface),
+            shell_quote(&signal.name),
+            busctl_arguments(&arguments)
+        ),
+        dbus_send: dbus_send_arguments(&arguments).map(|args| {
+            format!(
+                "dbus-send --system --type=signal {} {}{args}",
+                shell_quote(object_path),
+                shell_quote(&member)
+            )
+        }),
+        gdbus: format!(
+            "gdbus emit --system --object-path {} --signal {}{}",
+            shell_quote(object_path),
+            shell_quote(&member),
+            gdbus_arguments(&arguments)
+        ),
+    }
+}
+
+/// `busctl` and `dbus-monitor` invocations that print the messages a match rule matches.
+pub fn monitor_command_lines(rule: &str) -> Vec<String> {
+    vec![
+        format!("busctl --system monitor --match {}", shell_quote(rule)),
+        format!("dbus-monitor --system {}", shell_quote(rule)),
+    ]
+}
+
+fn arguments<'a>(
+    infos: &'a [ArgumentInfo],
+    values: Option<&'a [MessageItem]>,
+) -> Vec<Argument<'a>> {
+    match values {
+        Some(values) => values.iter().map(Argument::Value).collect(),
+        None => infos
+            .iter()
+            .enumerate()
+            .map(|(i, info)| {
+                let name = info.name.clone().unwrap_or_else(|| format!("arg{i}"));
+                Argument::Placeholder(name, &info.type_name)
+            })
+            .collect(),
+    }
+}
+
+fn busctl_arguments(arguments: &[Argument]) -> String {
+    if arguments.is_empty() {
+        return String::new();
+    }
+
+    let signature: String = arguments.iter().map(Argument::type_name).collect();
+    let mut words = vec![shell_quote(&signature)];
+    let mut options_end = false;
+    for argument in arguments {
+        match argument {
+            Argument::Value(item) => {
+                let mut values = Vec::new();
+                busctl_words(item, &mut values);
+                options_end |= values.iter().any(|value| value.starts_with('-'));
+                words.extend(values.iter().map(|value| shell_quote(value)));
+            }
+            Argument::Placeholder(name, _) => words.push(format!("<{name}>")),
+        }
+    }
+    // busctl reads options anywhere on the line, so -5 would be taken for one
+    if options_end {
+        words.insert(0, "--".to_string());
+    }
+    format!(" {}", words.join(" "))
+}
+
+/// busctl takes arrays as a count followed by the elements and variants as a signature
+/// followed by the value. The words are not quoted yet.
+fn busctl_words(item: &MessageItem, words: &mut Vec<String>) {
+    match item {
+        MessageItem::Array(array) => {
+            words.push(array.len().to_string());
+            for element in array.iter() {
+                busctl_words(element, words);
+            }
+        }
+        MessageItem::Dict(dict) => {
+            words.push(dict.len().to_string());
+            for (key, value) in dict.iter() {
+                busctl_words(key, words);
+                busctl_words(value, words);
+            }
+        }
+        MessageItem::Struct(fields) => {
+            for field in fields {
+                busctl_words(field, words);
+            }
+        }
+        MessageItem::Variant(inner) => {
+            words.push(inner.signature().to_string());
+            busctl_words(inner, words);
+        }
+        other => words.push(plain_text(other)),
+    }
+}
+
+fn dbus_send_arguments(arguments: &[Argument]) -> Option<String> {
+    let mut words = Vec::new();
+    for argument in arguments {
+        words.push(dbus_send_argument(argument, false)?);
+    }
+    Some(words.iter().map(|word| format!(" {word}")).collect())
+}
+
+/// dbus-send only understands basic types, arrays and dicts of basic types, and variants of
+/// basic types.
+fn dbus_send_argument(argument: &Argument, as_variant: bool) -> Option<String> {
+    let type_name = argument.type_name();
+    let chars: Vec<char> = type_name.chars().collect();
+    if as_variant && chars.len() != 1 {
+        return None;
+    }
+
+    let (prefix, text) = match (chars.as_slice(), argument) {
+        (['v'], Argument::Value(MessageItem::Variant(inner))) => {
+            return dbus_send_argument(&Argument::Value(inner), true);
+        }
+        (['v'], Argument::Placeholder(name, _)) => {
+            ("variant:".to_string(), format!("<type>:<{name}>"))
+        }
+        ([t], Argument::Value(item)) => (format!("{}:", dbus_send_type(*t)?), plain_text(item)),
+        (['a', t], Argument::Value(MessageItem::Array(array))) => (
+            format!("array:{}:", dbus_send_type(*t)?),
+            dbus_send_elements(array.iter())?,
+        ),
+        (['a', '{', k, v, '}'], Argument::Value(MessageItem::Dict(dict))) => (
+            format!("dict:{}:{}:", dbus_send_type(*k)?, dbus_send_type(*v)?),
+            dbus_send_elements(dict.iter().flat_map(|(k, v)| [k, v]))?,
+        ),
+        ([t], Argument::Placeholder(name, _)) => {
+            (format!("{}:", dbus_send_type(*t)?), format!("<{name}>"))
+        }
+        (['a', t], Argument::Placeholder(name, _)) => (
+            format!("array:{}:", dbus_send_type(*t)?),
+            format!("<{name}>"),
+        ),
+        (['a', '{', k, v, '}'], Argument::Placeholder(name, _)) => (
+            format!("dict:{}:{}:", dbus_send_type(*k)?, dbus_send_type(*v)?),
+            format!("<{name}>"),
+        ),
+        _ => return None,
+    };
+
+    let variant = if as_variant && type_name != "v" {
+        "variant:"
+    } else {
+        ""
+    };
+    Some(shell_quote(&format!("{variant}{prefix}{text}")))
+}
+
+/// Elements joined with commas, `None` if one of them has a comma of its own, as dbus-send
+/// cannot escape it.
+fn dbus_send_elements<'a>(elements: impl Iterator<Item = &'a MessageItem>) -> Option<String> {
+    let texts: Vec<String> = elements.map(plain_text).collect();
+    if texts.iter().any(|text| text.contains(',')) {
+        return None;
+    }
+    Some(texts.join(","))
+}
+
+fn dbus_send_type(type_code: char) -> Option<&'static str> {
+    let name = match type_code {
+        'y' => "byte",
+        'b' => "boolean",
+        'n' => "int16",
+        'q' => "uint16",
+        'i' => "int32",
+        'u' => "uint32",
+        'x' => "int64",
+        't' => "uint64",
+        'd' => "double",
+        's' => "string",
+        'o' => "objpath",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn gdbus_arguments(arguments: &[Argument]) -> String {
+    let mut options_end = false;
+    let words: String = arguments
+        .iter()
+        .map(|argument| match argument {
+            Argument::Value(item) => {
+                let text = gvariant_text(item, false);
+                options_end |= text.starts_with('-');
+                format!(" {}", shell_quote(&text))
+            }
+            Argument::Placeholder(name, _) => format!(" <{name}>"),
+        })
+        .collect();
+    // Like busctl, gdbus would take -5 for an option
+    if options_end {
+        format!(" --{words}")
+    } else {
+        words
+    }
+}
+
+/// The value of a basic type without any quoting or annotation.
+fn plain_text(item: &MessageItem) -> String {
+    match item {
+        MessageItem::Str(s) => s.clone(),
+        MessageItem::ObjectPath(path) => path.to_string(),
+        MessageItem::Signature(signature) => signature.to_string(),
+        MessageItem::Bool(b) => b.to_string(),
+        MessageItem::Byte(n) => n.to_string(),
+        MessageItem::Int16(n) => n.to_string(),
+        MessageItem::UInt16(n) => n.to_string(),
+        MessageItem::Int32(n) => n.to_string(),
+        MessageItem::UInt32(n) => n.to_string(),
+        MessageItem::Int64(n) => n.to_string(),
+        MessageItem::UInt64(n) => n.to_string(),
+        MessageItem::Double(n) => n.to_string(),
+        other => gvariant_text(other, true),
+    }
+}
+
+fn shell_quote(text: &str) -> String {
+    let is_safe = !text.is_empty()
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./:=,@%+".contains(c));
+    if is_safe {
+        text.to_string()
+    } else if !text.contains('\'') {
+        format!("'{text}'")
+    } else if !text.contains(['"', '$', '`', '\\', '!']) {
+        // GVariant strings are single quoted, double quotes keep them readable
+        format!("\"{text}\"")
+    } else {
+        format!("'{}'", text.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::values::parse_argument;
+
+    const BUSCTL: &str = "busctl --system call com.example.Thing /thing com.example.Thing Do";
+    const DBUS_SEND: &str =
+        "dbus-send --system --print-reply --dest=com.example.Thing /thing com.example.Thing.Do";
+    const GDBUS: &str =
+        "gdbus call --system --dest com.example.Thing --object-path /thing --method com.example.Thing.Do";
+
+    fn method(arguments: &[(Option<&str>, &str)]) -> MethodInfo {
+        MethodInfo {
+            name: "Do".to_string(),
+            arguments: arguments
+                .iter()
+                .map(|(name, type_name)| ArgumentInfo {
+                    name: name.map(str::to_string),
+                    type_name: type_name.to_string(),
+                    direction: Some("in".to_string()),
+                    description: None,
+                })
+                .collect(),
+            return_values: Vec::new(),
+            description: None,
+        }
+    }
+
+    /// The arguments part of each command line for a call with `(type, text)` values.
+    fn call(arguments: &[(&str, &str)]) -> (String, Option<String>, String) {
+        let info = method(
+            &arguments
+                .iter()
+                .map(|(t, _)| (None, *t))
+                .collect::<Vec<_>>(),
+        );
+        let values: Vec<MessageItem> = arguments
+            .iter()
+            .map(|(type_name, text)| parse_argument(text, type_name, "arg").unwrap())
+            .collect();
+        let lines = method_command_lines(
+            "com.example.Thing",
+            "/thing",
+            "com.example.Thing",
+            &info,
+            Some(&values),
+        );
+        (
+            lines.busctl.strip_prefix(BUSCTL).unwrap().to_string(),
+            lines
+                .dbus_send
+                .map(|line| line.strip_prefix(DBUS_SEND).unwrap().to_string()),
+            lines.gdbus.strip_prefix(GDBUS).unwrap().to_string(),
+        )
+    }
+
+    #[test]
+    fn shell_quoting() {
+        for (text, quoted) in [
+            ("com.example.Thing", "com.example.Thing"),
+            ("/com/example/thing_1", "/com/example/thing_1"),
+            ("dict:string:int32:a,1", "dict:string:int32:a,1"),
+            ("-5", "-5"),
+            ("", "''"),
+            ("hello world", "'hello world'"),
+            ("a{sv}", "'a{sv}'"),
+            ("'quoted'", "\"'quoted'\""),
+            ("it's $HOME", r"'it'\''s $HOME'"),
+            ("'a\\b'", r"''\''a\b'\'''"),
+        ] {
+            assert_eq!(shell_quote(text), quoted, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn basic_values() {
+        assert_eq!(
+            call(&[]),
+            (String::new(), Some(String::new()), String::new())
+        );
+        assert_eq!(
+            call(&[("s", "hello world"), ("b", "true"), ("o", "/a/b")]),
+            (
+                " sbo 'hello world' true /a/b".to_string(),
+                Some(" 'string:hello world' boolean:true objpath:/a/b".to_string()),
+                " \"'hello world'\" true \"'/a/b'\"".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn negative_numbers() {
+        assert_eq!(
+            call(&[("i", "-5"), ("x", "7")]),
+            (
+                " -- ix -5 7".to_string(),
+                Some(" int32:-5 int64:7".to_string()),
+                " -- -5 7".to_string()
+            )
+        );
+        assert_eq!(
+            call(&[("s", "-x")]),
+            (
+                " -- s -x".to_string(),
+                Some(" string:-x".to_string()),
+                " \"'-x'\"".to_string()
+            )
+        );
+        assert_eq!(
+            call(&[("ai", "[-1, 2]")]),
+            (
+                " -- ai 2 -1 2".to_string(),
+                Some(" array:int32:-1,2".to_string()),
+                " '[-1, 2]'".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn variants() {
+        assert_eq!(
+            call(&[("v", "<uint32 42>")]),
+            (
+                " v u 42".to_string(),
+                Some(" variant:uint32:42".to_string()),
+                " '<uint32 42>'".to_string()
+            )
+        );
+        // dbus-send cannot nest variants in containers
+        assert_eq!(
+            call(&[("av", "[<1>]")]),
+            (" av 1 i 1".to_string(), None, " '[<1>]'".to_string())
+        );
+    }
+
+    #[test]
+    fn arrays_and_dicts() {
+        assert_eq!(
+            call(&[("as", "['x', 'y']"), ("a{si}", "{'a': 1, 'b': 2}")]),
+            (
+                " 'asa{si}' 2 x y 2 a 1 b 2".to_string(),
+                Some(" array:string:x,y dict:string:int32:a,1,b,2".to_string()),
+                " \"['x', 'y']\" \"{'a': 1, 'b': 2}\"".to_string()
+            )
+        );
+        assert_eq!(
+            call(&[("a{sv}", "{'a': <1>}")]),
+            (
+                " 'a{sv}' 1 a i 1".to_string(),
+                None,
+                " \"{'a': <1>}\"".to_string()
+            )
+        );
+        // A comma in an element would split it in two
+        assert_eq!(call(&[("as", "['x', 'y,z']")]).1, None);
+        assert_eq!(call(&[("a{si}", "{'a,b': 1}")]).1, None);
+        assert_eq!(call(&[("a{ss}", "{'a': 'b,c'}")]).1, None);
+    }
+
+    #[test]
+    fn arrays_of_structs() {
+        assert_eq!(
+            call(&[("a(si)", "[('a', 1), ('b', -2)]")]),
+            (
+                " -- 'a(si)' 2 a 1 b -2".to_string(),
+                None,
+                " \"[('a', 1), ('b', -2)]\"".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn placeholders() {
+        let lines = method_command_lines(
+            "com.example.Thing",
+            "/thing",
+            "com.example.Thing",
+            &method(&[(Some("name"), "s"), (None, "au")]),
+            None,
+        );
+        assert_eq!(lines.busctl, format!("{BUSCTL} sau <name> <arg1>"));
+        assert_eq!(
+            lines.dbus_send,
+            Some(format!("{DBUS_SEND} 'string:<name>' 'array:uint32:<arg1>'"))
+        );
+        assert_eq!(lines.gdbus, format!("{GDBUS} <name> <arg1>"));
+
+        let lines = method_command_lines(
+            "com.example.Thing",
+            "/thing",
+            "com.example.Thing",
+            &method(&[(Some("options"), "a{sv}")]),
+            None,
+        );
+        assert_eq!(lines.busctl, format!("{BUSCTL} 'a{{sv}}' <options>"));
+        assert_eq!(lines.dbus_send, None);
+    }
+
+    #[test]
+    fn properties() {
+        let level = PropertyInfo {
+            name: "Level".to_string(),
+            type_name: "i".to_string(),
+            access: "readwrite".to_string(),
+            description: None,
+        };
+        let value = MessageItem::Int32(-3);
+        let lines = set_property_command_lines(
+            "com.example.Thing",
+            "/thing",
+            "com.example.Thing",
+            &level,
+            Some(&value),
+        );
+        assert_eq!(
+            lines.busctl,
+            "busctl --system set-property com.example.Thing /thing com.example.Thing Level -- i -3"
+        );
+        assert_eq!(
+            lines.dbus_send.as_deref(),
+            Some("dbus-send --system --print-reply --dest=com.example.Thing /thing org.freedesktop.DBus.Properties.Set string:com.example.Thing string:Level variant:int32:-3")
+        );
+        assert_eq!(
+            lines.gdbus,
+            "gdbus call --system --dest com.example.Thing --object-path /thing --method org.freedesktop.DBus.Properties.Set \"'com.example.Thing'\" \"'Level'\" '<-3>'"
+        );
+
+        let lines = set_property_command_lines(
+            "com.example.Thing",
+            "/thing",
+            "com.example.Thing",
+            &level,
+            None,
+        );
+        assert!(lines.busctl.ends_with(" Level i <Level>"));
+        assert!(lines.gdbus.ends_with(" <Level>"));
+    }
+
+    #[test]
+    fn monitors() {
+        assert_eq!(
+            monitor_command_lines("type='signal',arg0='it'\\''s'"),
+            vec![
+                r#"busctl --system monitor --match 'type='\''signal'\'',arg0='\''it'\''\'\'''\''s'\'''"#
+                    .to_string(),
+                r#"dbus-monitor --system 'type='\''signal'\'',arg0='\''it'\''\'\'''\''s'\'''"#
+                    .to_string(),
+            ]
+        );
+        assert_eq!(
+            monitor_command_lines("type='signal'")[0],
+            "busctl --system monitor --match \"type='signal'\""
+        );
+    }
+}
@@ -1,9 +1,20 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, path::PathBuf};
+
+use log::LevelFilter;
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub server_addr: SocketAddr,
+    /// The most verbose level logged, e.g. `debug` to also log every D-Bus call.
     pub log_level: String,
+    /// Number of probes to remember per service on the health page.
+    pub health_history_len: usize,
+    /// Number of method calls and property writes to keep on the recording page.
+    pub recording_len: usize,
+    /// Seconds for which the bus gauges of `/metrics` are reused before walking the bus again.
+    pub bus_metrics_interval: u64,
+    /// The configuration of the system bus, read to show its security policy.
+    pub bus_config: PathBuf,
 }
 
 impl Default for Config {
@@ -11,6 +22,10 @@ impl Default for Config {
         Self {
             server_addr: "127.0.0.1:2001".parse().expect("Valid socket address"),
             log_level: "info".to_string(),
+            health_history_len: 20,
+            recording_len: 500,
+            bus_metrics_interval: 60,
+            bus_config: PathBuf::from("/usr/share/dbus-1/system.conf"),
         }
     }
 }
@@ -29,6 +44,33 @@ impl Config {
             config.log_level = level;
         }
 
+        if let Ok(len) = std::env::var("DBUS_EXPLORER_HEALTH_HISTORY") {
+            if let Ok(parsed_len) = len.parse() {
+                config.health_history_len = parsed_len;
+            }
+        }
+
+        if let Ok(len) = std::env::var("DBUS_EXPLORER_RECORDING_LEN") {
+            if let Ok(parsed_len) = len.parse() {
+                config.recording_len = parsed_len;
+            }
+        }
+
+        if let Ok(interval) = std::env::var("DBUS_EXPLORER_BUS_METRICS_INTERVAL") {
+            if let Ok(parsed_interval) = interval.parse() {
+                config.bus_metrics_interval = parsed_interval;
+            }
+        }
+
+        if let Ok(path) = std::env::var("DBUS_EXPLORER_BUS_CONFIG") {
+            config.bus_config = PathBuf::from(path);
+        }
+
         config
     }
+
+    /// `log_level` as a filter, `info` if it is not a level.
+    pub fn log_level_filter(&self) -> LevelFilter {
+        self.log_level.parse().unwrap_or(LevelFilter::Info)
+    }
 }
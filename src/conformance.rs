@@ -0,0 +1,401 @@
+use std::{fmt, fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+
+use crate::{
+    dbus_introspection::{parse_introspection_xml, ArgumentInfo, InterfaceInfo},
+    utils::{validate_interface_name, validate_member_name, validate_signature},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DifferenceKind {
+    Missing,
+    Extra,
+    SignatureMismatch,
+    AccessMismatch,
+}
+
+/// One way in which a live interface differs from its contract.
+#[derive(Debug, Clone, Serialize)]
+pub struct Difference {
+    pub kind: DifferenceKind,
+    pub interface: String,
+    /// "method", "property" or "signal", `None` for the interface as a whole.
+    pub member_kind: Option<&'static str>,
+    pub member: Option<String>,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self.kind {
+            DifferenceKind::Missing => "missing",
+            DifferenceKind::Extra => "extra",
+            DifferenceKind::SignatureMismatch => "signature mismatch in",
+            DifferenceKind::AccessMismatch => "access mismatch in",
+        };
+        match (self.member_kind, &self.member) {
+            (Some(kind), Some(member)) => write!(f, "{what} {kind} {}.{member}", self.interface)?,
+            _ => write!(f, "{what} interface {}", self.interface)?,
+        }
+        if let (Some(expected), Some(actual)) = (&self.expected, &self.actual) {
+            write!(f, ": expected {expected}, found {actual}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Read the interfaces of one or more introspection XML files.
+///
+/// An interface defined in several files must be defined identically.
+pub fn load_expected_interfaces(paths: &[impl AsRef<Path>]) -> Result<Vec<InterfaceInfo>> {
+    let mut interfaces: Vec<InterfaceInfo> = Vec::new();
+
+    for path in paths {
+        let path = path.as_ref();
+        let xml = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let (file_interfaces, _) = parse_introspection_xml(&xml)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+        for interface in file_interfaces {
+            validate_interface(&interface)
+                .with_context(|| format!("{} is not a valid contract", path.display()))?;
+            if let Some(existing) = interfaces.iter().find(|i| i.name == interface.name) {
+                if !compare_interface(existing, &interface).is_empty() {
+                    return Err(anyhow!(
+                        "{} defines {} differently than an earlier file",
+                        path.display(),
+                        interface.name
+                    ));
+                }
+                continue;
+            }
+            interfaces.push(interface);
+        }
+    }
+
+    Ok(interfaces)
+}
+
+/// Check that the names and types of a contract interface follow the specification.
+fn validate_interface(interface: &InterfaceInfo) -> crate::error::Result<()> {
+    validate_interface_name(&interface.name)?;
+
+    let arguments = interface
+        .methods
+        .iter()
+        .flat_map(|m| m.arguments.iter().chain(&m.return_values))
+        .chain(interface.signals.iter().flat_map(|s| &s.arguments));
+    for argument in arguments {
+        validate_signature(&argument.type_name)?;
+    }
+
+    let members = interface
+        .methods
+        .iter()
+        .map(|m| &m.name)
+        .chain(interface.signals.iter().map(|s| &s.name))
+        .chain(interface.properties.iter().map(|p| &p.name));
+    for member in members {
+        validate_member_name(member)?;
+    }
+    for property in &interface.properties {
+        validate_signature(&property.type_name)?;
+    }
+
+    Ok(())
+}
+
+/// Compare the interfaces of an object with the expected ones.
+///
+/// Interfaces the object has beyond the expected ones are not reported, contracts usually
+/// leave out the standard interfaces.
+pub fn compare_interfaces(expected: &[InterfaceInfo], actual: &[InterfaceInfo]) -> Vec<Difference> {
+    expected
+        .iter()
+        .flat_map(
+            |expected| match actual.iter().find(|a| a.name == expected.name) {
+                Some(actual) => compare_interface(expected, actual),
+                None => vec![Difference {
+                    kind: DifferenceKind::Missing,
+                    interface: expected.name.clone(),
+                    member_kind: None,
+                    member: None,
+                    expected: None,
+                    actual: None,
+                }],
+            },
+        )
+        .collect()
+}
+
+/// What is compared of a method, property or signal.
+struct Member {
+    name: String,
+    signature: String,
+    /// Only for properties.
+    access: Option<String>,
+}
+
+fn compare_interface(expected: &InterfaceInfo, actual: &InterfaceInfo) -> Vec<Difference> {
+    let mut differences = Vec::new();
+
+    let methods = |interface: &InterfaceInfo| {
+        interface
+            .methods
+            .iter()
+            .map(|m| Member {
+                name: m.name.clone(),
+                signature: format!(
+                    "({}) → ({})",
+                    signature(&m.arguments),
+                    signature(&m.return_values)
+                ),
+                access: None,
+            })
+            .collect::<Vec<_>>()
+    };
+    compare_members(
+        &mut differences,
+        &expected.name,
+        "method",
+        methods(expected),
+        methods(actual),
+    );
+
+    let properties = |interface: &InterfaceInfo| {
+        interface
+            .properties
+            .iter()
+            .map(|p| Member {
+                name: p.name.clone(),
+                signature: p.type_name.clone(),
+                access: Some(p.access.clone()),
+            })
+            .collect::<Vec<_>>()
+    };
+    compare_members(
+        &mut differences,
+        &expected.name,
+        "property",
+        properties(expected),
+        properties(actual),
+    );
+
+    let signals = |interface: &InterfaceInfo| {
+        interface
+            .signals
+            .iter()
+            .map(|s| Member {
+                name: s.name.clone(),
+                signature: format!("({})", signature(&s.arguments)),
+                access: None,
+            })
+            .collect::<Vec<_>>()
+    };
+    compare_members(
+        &mut differences,
+        &expected.name,
+        "signal",
+        signals(expected),
+        signals(actual),
+    );
+
+    differences
+}
+
+fn compare_members(
+    differences: &mut Vec<Difference>,
+    interface: &str,
+    member_kind: &'static str,
+    expected: Vec<Member>,
+    actual: Vec<Member>,
+) {
+    let difference =
+        |kind, member: &str, expected: Option<&String>, actual: Option<&String>| Difference {
+            kind,
+            interface: interface.to_string(),
+            member_kind: Some(member_kind),
+            member: Some(member.to_string()),
+            expected: expected.cloned(),
+            actual: actual.cloned(),
+        };
+
+    for member in &expected {
+        let Some(actual_member) = actual.iter().find(|m| m.name == member.name) else {
+            differences.push(difference(
+                DifferenceKind::Missing,
+                &member.name,
+                None,
+                None,
+            ));
+            continue;
+        };
+        if member.signature != actual_member.signature {
+            differences.push(difference(
+                DifferenceKind::SignatureMismatch,
+                &member.name,
+                Some(&member.signature),
+                Some(&actual_member.signature),
+            ));
+        }
+        if member.access != actual_member.access {
+            differences.push(difference(
+                DifferenceKind::AccessMismatch,
+                &member.name,
+                member.access.as_ref(),
+                actual_member.access.as_ref(),
+            ));
+        }
+    }
+
+    for member in &actual {
+        if !expected.iter().any(|m| m.name == member.name) {
+            differences.push(difference(DifferenceKind::Extra, &member.name, None, None));
+        }
+    }
+}
+
+fn signature(arguments: &[ArgumentInfo]) -> String {
+    arguments.iter().map(|a| a.type_name.as_str()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interfaces(body: &str) -> Vec<InterfaceInfo> {
+        let xml = format!("<node>{body}</node>");
+        parse_introspection_xml(&xml).unwrap().0
+    }
+
+    const CONTRACT: &str = r#"
+        <interface name="com.example.Thing">
+          <method name="Get">
+            <arg name="key" type="s" direction="in"/>
+            <arg name="value" type="v" direction="out"/>
+          </method>
+          <property name="Name" type="s" access="readwrite"/>
+          <signal name="Changed"><arg name="key" type="s"/></signal>
+        </interface>"#;
+
+    fn report(expected: &str, actual: &str) -> Vec<String> {
+        compare_interfaces(&interfaces(expected), &interfaces(actual))
+            .iter()
+            .map(Difference::to_string)
+            .collect::<Vec<_>>()
+    }
+
+    #[test]
+    fn identical_interfaces() {
+        assert!(report(CONTRACT, CONTRACT).is_empty());
+    }
+
+    #[test]
+    fn extra_interfaces_are_ignored() {
+        let actual = format!(
+            r#"{CONTRACT}<interface name="org.freedesktop.DBus.Peer"><method name="Ping"/></interface>"#
+        );
+        assert!(report(CONTRACT, &actual).is_empty());
+    }
+
+    #[test]
+    fn missing_interface() {
+        let differences = compare_interfaces(&interfaces(CONTRACT), &[]);
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].kind, DifferenceKind::Missing);
+        assert_eq!(differences[0].member, None);
+        assert_eq!(
+            differences[0].to_string(),
+            "missing interface com.example.Thing"
+        );
+    }
+
+    #[test]
+    fn missing_and_extra_members() {
+        let actual = r#"
+            <interface name="com.example.Thing">
+              <method name="Get">
+                <arg type="s" direction="in"/>
+                <arg type="v" direction="out"/>
+              </method>
+              <method name="Reset"/>
+              <property name="Size" type="u" access="read"/>
+            </interface>"#;
+        assert_eq!(
+            report(CONTRACT, actual),
+            [
+                "extra method com.example.Thing.Reset",
+                "missing property com.example.Thing.Name",
+                "extra property com.example.Thing.Size",
+                "missing signal com.example.Thing.Changed",
+            ]
+        );
+    }
+
+    #[test]
+    fn signature_mismatches() {
+        let actual = r#"
+            <interface name="com.example.Thing">
+              <method name="Get">
+                <arg type="s" direction="in"/>
+                <arg type="s" direction="out"/>
+              </method>
+              <property name="Name" type="as" access="readwrite"/>
+              <signal name="Changed"><arg type="s"/><arg type="v"/></signal>
+            </interface>"#;
+        let differences = compare_interfaces(&interfaces(CONTRACT), &interfaces(actual));
+        assert!(differences
+            .iter()
+            .all(|d| d.kind == DifferenceKind::SignatureMismatch));
+        assert_eq!(
+            differences.iter().map(Difference::to_string).collect::<Vec<_>>(),
+            [
+                "signature mismatch in method com.example.Thing.Get: expected (s) → (v), found (s) → (s)",
+                "signature mismatch in property com.example.Thing.Name: expected s, found as",
+                "signature mismatch in signal com.example.Thing.Changed: expected (s), found (sv)",
+            ]
+        );
+    }
+
+    #[test]
+    fn access_mismatches() {
+        let actual = CONTRACT.replace("readwrite", "read");
+        let differences = compare_interfaces(&interfaces(CONTRACT), &interfaces(&actual));
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].kind, DifferenceKind::AccessMismatch);
+        assert_eq!(
+            differences[0].to_string(),
+            "access mismatch in property com.example.Thing.Name: expected readwrite, found read"
+        );
+
+        let actual = CONTRACT.replace(
+            r#"type="s" access="readwrite""#,
+            r#"type="u" access="write""#,
+        );
+        assert_eq!(
+            report(CONTRACT, &actual),
+            [
+                "signature mismatch in property com.example.Thing.Name: expected s, found u",
+                "access mismatch in property com.example.Thing.Name: expected readwrite, found write",
+            ]
+        );
+    }
+
+    #[test]
+    fn contracts_must_be_valid() {
+        for body in [
+            r#"<interface name="thing"/>"#,
+            r#"<interface name="com.example.Thing"><method name="Get-It"/></interface>"#,
+            r#"<interface name="com.example.Thing"><property name="Name" type="a" access="read"/></interface>"#,
+        ] {
+            let interface = &interfaces(body)[0];
+            assert!(validate_interface(interface).is_err(), "{body:?}");
+        }
+        assert!(validate_interface(&interfaces(CONTRACT)[0]).is_ok());
+    }
+}
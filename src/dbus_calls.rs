@@ -0,0 +1,366 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use dbus::{arg::messageitem::MessageItem, blocking::Connection, Message};
+
+use crate::{
+    dbus_introspection::{ArgumentInfo, MethodInfo, ObjectInfo, PropertyInfo, SignalInfo},
+    error::{AppError, DbusError},
+    request_log::timed_call,
+    values::parse_argument,
+};
+
+/// Something done to an object through a form on its page.
+#[derive(Debug, Clone)]
+pub enum ObjectAction {
+    Call(MethodCall),
+    SetProperty(PropertyWrite),
+    EmitSignal(SignalEmission),
+}
+
+/// A method call made through the explorer, as entered and as answered.
+#[derive(Debug, Clone)]
+pub struct MethodCall {
+    pub interface: String,
+    pub method: String,
+    pub inputs: Vec<String>,
+    /// The converted inputs, `None` if any of them could not be converted.
+    pub arguments: Option<Vec<MessageItem>>,
+    pub reply: std::result::Result<Vec<MessageItem>, String>,
+    /// The name of the D-Bus error the call failed with, if it got that far.
+    pub error_name: Option<String>,
+    pub duration: Duration,
+}
+
+impl MethodCall {
+    /// Convert `inputs` according to the signature of `method` and, if that succeeds, call it.
+    pub fn execute(
+        conn: &Connection,
+        service_name: &str,
+        object_path: &str,
+        interface: &str,
+        method: &MethodInfo,
+        inputs: Vec<String>,
+    ) -> Self {
+        let arguments = convert_inputs(method, &inputs);
+        let started = Instant::now();
+        let reply = match &arguments {
+            Ok(arguments) => call_method(
+                conn,
+                service_name,
+                object_path,
+                interface,
+                &method.name,
+                arguments,
+            ),
+            Err(e) => Err(anyhow!("{e:#}")),
+        };
+
+        Self {
+            interface: interface.to_string(),
+            method: method.name.clone(),
+            inputs,
+            arguments: arguments.ok(),
+            error_name: reply.as_ref().err().and_then(dbus_error_name),
+            reply: reply.map_err(|e| format!("{e:#}")),
+            duration: started.elapsed(),
+        }
+    }
+}
+
+/// A property write made through the explorer, as entered and as answered.
+#[derive(Debug, Clone)]
+pub struct PropertyWrite {
+    pub interface: String,
+    pub property: String,
+    pub input: String,
+    /// The converted input, `None` if it could not be converted.
+    pub value: Option<MessageItem>,
+    pub result: std::result::Result<(), String>,
+    /// The name of the D-Bus error the write failed with, if it got that far.
+    pub error_name: Option<String>,
+    pub duration: Duration,
+}
+
+impl PropertyWrite {
+    /// Convert `input` according to the type of `property` and, if that succeeds, write it.
+    pub fn execute(
+        conn: &Connection,
+        service_name: &str,
+        object_path: &str,
+        interface: &str,
+        property: &PropertyInfo,
+        input: String,
+    ) -> Self {
+        let value = parse_argument(&input, &property.type_name, &property.name);
+        let started = Instant::now();
+        let result = match &value {
+            Ok(value) => set_property(
+                conn,
+                service_name,
+                object_path,
+                interface,
+                &property.name,
+                value.clone(),
+            ),
+            Err(e) => Err(anyhow!("{e:#}")),
+        };
+
+        Self {
+            interface: interface.to_string(),
+            property: property.name.clone(),
+            input,
+            value: value.ok(),
+            error_name: result.as_ref().err().and_then(dbus_error_name),
+            result: result.map_err(|e| format!("{e:#}")),
+            duration: started.elapsed(),
+        }
+    }
+}
+
+/// A signal emitted through the explorer, from its own connection rather than the service's.
+#[derive(Debug, Clone)]
+pub struct SignalEmission {
+    pub interface: String,
+    pub signal: String,
+    pub inputs: Vec<String>,
+    /// The converted inputs, `None` if any of them could not be converted.
+    pub arguments: Option<Vec<MessageItem>>,
+    pub result: std::result::Result<(), String>,
+}
+
+impl SignalEmission {
+    /// Convert `inputs` according to the signature of `signal` and, if that succeeds, emit it.
+    pub fn execute(
+        conn: &Connection,
+        object_path: &str,
+        interface: &str,
+        signal: &SignalInfo,
+        inputs: Vec<String>,
+    ) -> Self {
+        let arguments = convert_arguments(&signal.name, &signal.arguments, &inputs);
+        let result = match &arguments {
+            Ok(arguments) => emit_signal(conn, object_path, interface, &signal.name, arguments),
+            Err(e) => Err(anyhow!("{e:#}")),
+        };
+
+        Self {
+            interface: interface.to_string(),
+            signal: signal.name.clone(),
+            inputs,
+            arguments: arguments.ok(),
+            result: result.map_err(|e| format!("{e:#}")),
+        }
+    }
+}
+
+/// The name of the D-Bus error behind a failed call, if there is one.
+fn dbus_error_name(error: &anyhow::Error) -> Option<String> {
+    error
+        .chain()
+        .find_map(|e| e.downcast_ref::<AppError>())
+        .and_then(AppError::dbus_error)
+        .map(|e| e.name)
+}
+
+/// Convert text inputs to message items according to the signature of `method`.
+pub fn convert_inputs(method: &MethodInfo, inputs: &[String]) -> Result<Vec<MessageItem>> {
+    convert_arguments(&method.name, &method.arguments, inputs)
+}
+
+fn convert_arguments(
+    member: &str,
+    arguments: &[ArgumentInfo],
+    inputs: &[String],
+) -> Result<Vec<MessageItem>> {
+    if arguments.len() != inputs.len() {
+        return Err(anyhow!(
+            "{member} takes {} argument(s) but {} were given",
+            arguments.len(),
+            inputs.len()
+        ));
+    }
+
+    arguments
+        .iter()
+        .zip(inputs)
+        .enumerate()
+        .map(|(i, (arg, text))| {
+            let name = arg.name.clone().unwrap_or_else(|| format!("arg{i}"));
+            parse_argument(text, &arg.type_name, &name)
+        })
+        .collect()
+}
+
+pub fn call_method(
+    conn: &Connection,
+    service_name: &str,
+    object_path: &str,
+    interface: &str,
+    method: &str,
+    arguments: &[MessageItem],
+) -> Result<Vec<MessageItem>> {
+    call_method_with_timeout(
+        conn,
+        service_name,
+        object_path,
+        interface,
+        method,
+        arguments,
+        Duration::from_millis(5000),
+    )
+}
+
+/// [`call_method`], waiting at most `timeout` for the reply.
+pub fn call_method_with_timeout(
+    conn: &Connection,
+    service_name: &str,
+    object_path: &str,
+    interface: &str,
+    method: &str,
+    arguments: &[MessageItem],
+    timeout: Duration,
+) -> Result<Vec<MessageItem>> {
+    let mut message = Message::new_method_call(service_name, object_path, interface, method)
+        .map_err(|e| anyhow!(e))?;
+    message.append_items(arguments);
+
+    let reply = timed_call(
+        service_name,
+        object_path,
+        &format!("{interface}.{method}"),
+        || conn.channel().send_with_reply_and_block(message, timeout),
+    )
+    .map_err(|e| AppError::from(DbusError::from(&e)))
+    .with_context(|| format!("Failed to call {interface}.{method}"))?;
+
+    Ok(reply.get_items())
+}
+
+pub fn get_property(
+    conn: &Connection,
+    service_name: &str,
+    object_path: &str,
+    interface: &str,
+    property: &str,
+) -> Result<MessageItem> {
+    let reply = call_method(
+        conn,
+        service_name,
+        object_path,
+        "org.freedesktop.DBus.Properties",
+        "Get",
+        &[
+            MessageItem::Str(interface.to_string()),
+            MessageItem::Str(property.to_string()),
+        ],
+    )?;
+
+    match reply.into_iter().next() {
+        Some(MessageItem::Variant(value)) => Ok(*value),
+        other => Err(anyhow!("Unexpected reply to Get: {other:?}")),
+    }
+}
+
+/// Property values by interface and property name, or why an interface could not be read.
+pub type PropertyValues =
+    BTreeMap<String, std::result::Result<BTreeMap<String, MessageItem>, String>>;
+
+pub fn get_all_properties(
+    conn: &Connection,
+    service_name: &str,
+    object_path: &str,
+    interface: &str,
+) -> Result<BTreeMap<String, MessageItem>> {
+    let reply = call_method(
+        conn,
+        service_name,
+        object_path,
+        "org.freedesktop.DBus.Properties",
+        "GetAll",
+        &[MessageItem::Str(interface.to_string())],
+    )?;
+
+    vardict_reply(reply, "GetAll")
+}
+
+/// Unpack a reply consisting of an `a{sv}`, as returned by `GetAll` and many other methods.
+pub fn vardict_reply(
+    reply: Vec<MessageItem>,
+    method: &str,
+) -> Result<BTreeMap<String, MessageItem>> {
+    match reply.into_iter().next() {
+        Some(MessageItem::Dict(dict)) => dict
+            .into_vec()
+            .into_iter()
+            .map(|(name, value)| match (name, value) {
+                (MessageItem::Str(name), MessageItem::Variant(value)) => Ok((name, *value)),
+                other => Err(anyhow!("Unexpected entry in reply to {method}: {other:?}")),
+            })
+            .collect(),
+        other => Err(anyhow!("Unexpected reply to {method}: {other:?}")),
+    }
+}
+
+/// Read the properties of every interface of `object` that has readable ones.
+pub fn read_properties(
+    conn: &Connection,
+    service_name: &str,
+    object: &ObjectInfo,
+) -> PropertyValues {
+    object
+        .interfaces
+        .iter()
+        .filter(|i| i.properties.iter().any(|p| p.access.contains("read")))
+        .map(|i| {
+            let values = get_all_properties(conn, service_name, &object.path, &i.name)
+                .map_err(|e| format!("{e:#}"));
+            (i.name.clone(), values)
+        })
+        .collect()
+}
+
+pub fn set_property(
+    conn: &Connection,
+    service_name: &str,
+    object_path: &str,
+    interface: &str,
+    property: &str,
+    value: MessageItem,
+) -> Result<()> {
+    call_method(
+        conn,
+        service_name,
+        object_path,
+        "org.freedesktop.DBus.Properties",
+        "Set",
+        &[
+            MessageItem::Str(interface.to_string()),
+            MessageItem::Str(property.to_string()),
+            MessageItem::Variant(Box::new(value)),
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn emit_signal(
+    conn: &Connection,
+    object_path: &str,
+    interface: &str,
+    signal: &str,
+    arguments: &[MessageItem],
+) -> Result<()> {
+    let mut message =
+        Message::new_signal(object_path, interface, signal).map_err(|e| anyhow!(e))?;
+    message.append_items(arguments);
+
+    conn.channel()
+        .send(message)
+        .map_err(|()| anyhow!("Failed to emit {interface}.{signal}"))?;
+    // The connection is usually dropped right after, so do not leave the signal queued
+    conn.channel().flush();
+    Ok(())
+}
@@ -1,27 +1,76 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
 use dbus::blocking::Connection;
 use log::debug;
-use serde::Deserialize;
-
-#[derive(Debug, Clone)]
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    error::{DbusError, DbusErrorKind},
+    metrics::METRICS,
+    request_log::timed_call,
+    standard_interfaces::add_standard_docs,
+    systemd::SystemdUnit,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServiceInfo {
     pub name: String,
     pub owner: Option<String>,
+    /// The process owning the name.
+    pub pid: Option<u32>,
+    /// The systemd unit of that process, only looked up where it is shown, see
+    /// [`resolve_unit`](crate::systemd::resolve_unit).
+    pub unit: Option<SystemdUnit>,
     pub objects: Vec<ObjectInfo>,
     pub error: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ObjectInfo {
     pub path: String,
     pub interfaces: Vec<InterfaceInfo>,
     pub error: Option<String>,
     pub child_nodes: Vec<String>,
+    /// The raw reply to `Introspect`, kept so that it can be shown verbatim.
+    pub xml: Option<String>,
+    pub xml_error: Option<XmlParseError>,
+    /// The error reply to `Introspect`, if there was one.
+    pub dbus_error: Option<DbusError>,
+}
+
+/// Failure to parse an introspection document, with the position where parsing stopped.
+#[derive(Error, Debug, Clone, Serialize, Deserialize)]
+#[error("{message} at line {line}, column {column}")]
+pub struct XmlParseError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl XmlParseError {
+    fn at_offset(xml: &str, offset: usize, message: String) -> Self {
+        let offset = offset.min(xml.len());
+        let before = &xml.as_bytes()[..offset];
+        let line = before.iter().filter(|b| **b == b'\n').count() + 1;
+        let line_start = before
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .map_or(0, |i| i + 1);
+        let column = String::from_utf8_lossy(&before[line_start..])
+            .chars()
+            .count()
+            + 1;
+        Self {
+            message,
+            line,
+            column,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InterfaceInfo {
     pub name: String,
     pub methods: Vec<MethodInfo>,
@@ -30,7 +79,7 @@ pub struct InterfaceInfo {
     pub description: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MethodInfo {
     pub name: String,
     pub arguments: Vec<ArgumentInfo>,
@@ -38,7 +87,7 @@ pub struct MethodInfo {
     pub description: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PropertyInfo {
     pub name: String,
     pub type_name: String,
@@ -46,14 +95,14 @@ pub struct PropertyInfo {
     pub description: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SignalInfo {
     pub name: String,
     pub arguments: Vec<ArgumentInfo>,
     pub description: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ArgumentInfo {
     pub name: Option<String>,
     pub type_name: String,
@@ -105,7 +154,7 @@ struct DbusProperty {
     #[serde(rename = "@access")]
     access: String,
     #[serde(rename = "annotation", default)]
-    _annotations: Vec<DbusAnnotation>,
+    annotations: Vec<DbusAnnotation>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -142,16 +191,46 @@ struct DbusChildNode {
     name: String,
 }
 
-pub fn get_service_names_only(conn: &Connection) -> Result<Vec<String>> {
+/// All names on the bus, unique connection names included.
+pub fn list_names(conn: &Connection) -> Result<Vec<String>> {
     let proxy = conn.with_proxy(
         "org.freedesktop.DBus",
         "/org/freedesktop/DBus",
         Duration::from_millis(1000), // Reduced timeout
     );
 
-    let (names,): (Vec<String>,) = proxy
-        .method_call("org.freedesktop.DBus", "ListNames", ())
-        .context("Failed to list D-Bus names")?;
+    let (names,): (Vec<String>,) = timed_call(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus.ListNames",
+        || proxy.method_call("org.freedesktop.DBus", "ListNames", ()),
+    )
+    .context("Failed to list D-Bus names")?;
+
+    Ok(names)
+}
+
+/// The names the bus can start a service for.
+pub fn list_activatable_names(conn: &Connection) -> Result<Vec<String>> {
+    let proxy = conn.with_proxy(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        Duration::from_millis(1000),
+    );
+
+    let (names,): (Vec<String>,) = timed_call(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus.ListActivatableNames",
+        || proxy.method_call("org.freedesktop.DBus", "ListActivatableNames", ()),
+    )
+    .context("Failed to list activatable D-Bus names")?;
+
+    Ok(names)
+}
+
+pub fn get_service_names_only(conn: &Connection) -> Result<Vec<String>> {
+    let names = list_names(conn)?;
 
     let mut service_names = Vec::new();
     for name in names {
@@ -171,9 +250,13 @@ pub fn discover_services(conn: &Connection, filter: Option<&str>) -> Result<Vec<
         Duration::from_millis(2000), // Reduced timeout
     );
 
-    let (names,): (Vec<String>,) = proxy
-        .method_call("org.freedesktop.DBus", "ListNames", ())
-        .context("Failed to list D-Bus names")?;
+    let (names,): (Vec<String>,) = timed_call(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus.ListNames",
+        || proxy.method_call("org.freedesktop.DBus", "ListNames", ()),
+    )
+    .context("Failed to list D-Bus names")?;
 
     let mut services = Vec::new();
 
@@ -197,9 +280,12 @@ pub fn discover_services(conn: &Connection, filter: Option<&str>) -> Result<Vec<
 }
 
 pub fn analyze_service(conn: &Connection, service_name: &str) -> ServiceInfo {
+    let started = Instant::now();
     let mut service_info = ServiceInfo {
         name: service_name.to_string(),
         owner: None,
+        pid: None,
+        unit: None,
         objects: Vec::new(),
         error: None,
     };
@@ -210,14 +296,36 @@ pub fn analyze_service(conn: &Connection, service_name: &str) -> ServiceInfo {
         Duration::from_millis(500),
     );
 
-    if let Ok((owner,)) = dbus_proxy.method_call::<(String,), _, _, _>(
+    if let Ok((owner,)) = timed_call(
         "org.freedesktop.DBus",
-        "GetNameOwner",
-        (service_name,),
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus.GetNameOwner",
+        || {
+            dbus_proxy.method_call::<(String,), _, _, _>(
+                "org.freedesktop.DBus",
+                "GetNameOwner",
+                (service_name,),
+            )
+        },
     ) {
         service_info.owner = Some(owner);
     }
 
+    if let Ok((pid,)) = timed_call(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus.GetConnectionUnixProcessID",
+        || {
+            dbus_proxy.method_call::<(u32,), _, _, _>(
+                "org.freedesktop.DBus",
+                "GetConnectionUnixProcessID",
+                (service_name,),
+            )
+        },
+    ) {
+        service_info.pid = Some(pid);
+    }
+
     // Only start from root path - no guessing, always record results
     let root_object = introspect_object(conn, service_name, "/");
 
@@ -258,6 +366,7 @@ pub fn analyze_service(conn: &Connection, service_name: &str) -> ServiceInfo {
             Some("No accessible objects found or service not authorized".to_string());
     }
 
+    METRICS.record_crawl(service_name, started.elapsed());
     service_info
 }
 
@@ -268,38 +377,62 @@ pub fn introspect_object(
 ) -> Option<ObjectInfo> {
     let proxy = conn.with_proxy(service_name, object_path, Duration::from_millis(1000)); // Reduced timeout
 
-    match proxy.method_call::<(String,), _, _, _>(
-        "org.freedesktop.DBus.Introspectable",
-        "Introspect",
-        (),
-    ) {
+    let reply = timed_call(
+        service_name,
+        object_path,
+        "org.freedesktop.DBus.Introspectable.Introspect",
+        || {
+            proxy.method_call::<(String,), _, _, _>(
+                "org.freedesktop.DBus.Introspectable",
+                "Introspect",
+                (),
+            )
+        },
+    );
+    match reply {
         Ok((xml,)) => match parse_introspection_xml_serde(&xml, service_name, object_path) {
-            Ok((interfaces, child_nodes)) => Some(ObjectInfo {
-                path: object_path.to_string(),
-                interfaces,
-                error: None,
-                child_nodes,
-            }),
+            Ok((mut interfaces, child_nodes)) => {
+                interfaces.iter_mut().for_each(add_standard_docs);
+                Some(ObjectInfo {
+                    path: object_path.to_string(),
+                    interfaces,
+                    error: None,
+                    child_nodes,
+                    xml: Some(xml),
+                    xml_error: None,
+                    dbus_error: None,
+                })
+            }
             Err(e) => Some(ObjectInfo {
                 path: object_path.to_string(),
                 interfaces: Vec::new(),
                 error: Some(format!("XML parsing failed: {e}")),
                 child_nodes: Vec::new(),
+                xml: Some(xml),
+                xml_error: Some(e),
+                dbus_error: None,
             }),
         },
         Err(e) => {
-            let error_msg = if e
-                .to_string()
-                .contains("org.freedesktop.DBus.Error.AccessDenied")
-            {
-                "Access denied - not authorized to introspect this object".to_string()
-            } else if e
-                .to_string()
-                .contains("org.freedesktop.DBus.Error.UnknownMethod")
-            {
-                "Object does not support introspection".to_string()
-            } else {
-                format!("Introspection failed: {e}")
+            let dbus_error = DbusError::from(&e);
+            let error_msg = match dbus_error.kind() {
+                DbusErrorKind::AccessDenied => {
+                    format!(
+                        "Access denied - not authorized to introspect this object: {dbus_error}"
+                    )
+                }
+                // The kind also covers unknown properties, so tell the names apart
+                DbusErrorKind::UnknownObject => match dbus_error.name.as_str() {
+                    "org.freedesktop.DBus.Error.UnknownMethod"
+                    | "org.freedesktop.DBus.Error.UnknownInterface" => {
+                        format!("Object does not support introspection: {dbus_error}")
+                    }
+                    "org.freedesktop.DBus.Error.UnknownObject" => {
+                        format!("No such object: {dbus_error}")
+                    }
+                    _ => format!("Introspection failed: {dbus_error}"),
+                },
+                _ => format!("Introspection failed: {dbus_error}"),
             };
 
             Some(ObjectInfo {
@@ -307,6 +440,9 @@ pub fn introspect_object(
                 interfaces: Vec::new(),
                 error: Some(error_msg),
                 child_nodes: Vec::new(),
+                xml: None,
+                xml_error: None,
+                dbus_error: Some(dbus_error),
             })
         }
     }
@@ -324,26 +460,40 @@ fn parse_introspection_xml_serde(
     xml: &str,
     service_name: &str,
     object_path: &str,
-) -> Result<(Vec<InterfaceInfo>, Vec<String>)> {
-    // Parse the XML document
-    let parse_result = quick_xml::de::from_str::<DbusNode>(xml);
-
-    let dbus_node = match parse_result {
-        Ok(node) => {
-            // Log successful parses at debug level
-            log_xml_document(service_name, object_path, xml, true);
-            node
+) -> std::result::Result<(Vec<InterfaceInfo>, Vec<String>), XmlParseError> {
+    let result = parse_introspection_xml(xml);
+    match &result {
+        // Log successful parses at debug level
+        Ok(_) => log_xml_document(service_name, object_path, xml, true),
+        // Log failed parses for non-freedesktop services to reduce noise
+        Err(_) if !service_name.starts_with("org.freedesktop.") => {
+            log_xml_document(service_name, object_path, xml, false)
         }
+        Err(_) => {}
+    }
+    result
+}
+
+/// Parse an introspection document into its interfaces and the names of its child nodes.
+pub fn parse_introspection_xml(
+    xml: &str,
+) -> std::result::Result<(Vec<InterfaceInfo>, Vec<String>), XmlParseError> {
+    // Parse the XML document
+    let mut deserializer = quick_xml::de::Deserializer::from_str(xml);
+    let dbus_node = match DbusNode::deserialize(&mut deserializer) {
+        Ok(node) => node,
         Err(e) => {
-            // Log failed parses for non-freedesktop services to reduce noise
-            if !service_name.starts_with("org.freedesktop.") {
-                log_xml_document(service_name, object_path, xml, false);
-            }
-            return Err(anyhow::anyhow!(
-                "Failed to parse D-Bus introspection XML for {}:{}: {}",
-                service_name,
-                object_path,
-                e
+            // Syntax errors know where they happened, other errors are reported where the
+            // deserializer stopped reading.
+            let reader = deserializer.get_ref().get_ref();
+            let offset = match e {
+                quick_xml::DeError::InvalidXml(_) => reader.error_position(),
+                _ => reader.buffer_position(),
+            };
+            return Err(XmlParseError::at_offset(
+                xml,
+                offset as usize,
+                e.to_string(),
             ));
         }
     };
@@ -412,12 +562,21 @@ fn parse_introspection_xml_serde(
 
         // Convert properties
         for dbus_property in dbus_interface.properties {
-            let property = PropertyInfo {
+            let mut property = PropertyInfo {
                 name: dbus_property.name,
                 type_name: dbus_property.type_name,
                 access: dbus_property.access,
                 description: None,
             };
+
+            // Find property description from annotations
+            for annotation in &dbus_property.annotations {
+                if annotation.name == "org.freedesktop.DBus.Description" {
+                    property.description = Some(annotation.value.clone());
+                    break;
+                }
+            }
+
             interface.properties.push(property);
         }
 
@@ -1,14 +1,95 @@
 use axum::{
-    http::StatusCode,
+    extract::Request,
+    http::{header::ACCEPT, StatusCode},
+    middleware::Next,
     response::{Html, IntoResponse, Response},
+    Json,
 };
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use thiserror::Error;
 
+use crate::utils::html_escape;
+
+/// The kinds of error replies that the explorer reacts to differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DbusErrorKind {
+    AccessDenied,
+    UnknownObject,
+    Timeout,
+    NoReply,
+    ServiceUnknown,
+    Other,
+}
+
+/// An error reply from the bus or a service, by name and message.
+#[derive(Error, Debug, Clone, Serialize, Deserialize)]
+#[error("{message} ({name})")]
+pub struct DbusError {
+    pub name: String,
+    pub message: String,
+}
+
+impl DbusError {
+    pub fn kind(&self) -> DbusErrorKind {
+        match self.name.as_str() {
+            "org.freedesktop.DBus.Error.AccessDenied"
+            | "org.freedesktop.DBus.Error.AuthFailed"
+            | "org.freedesktop.DBus.Error.InteractiveAuthorizationRequired" => {
+                DbusErrorKind::AccessDenied
+            }
+            "org.freedesktop.DBus.Error.UnknownObject"
+            | "org.freedesktop.DBus.Error.UnknownInterface"
+            | "org.freedesktop.DBus.Error.UnknownMethod"
+            | "org.freedesktop.DBus.Error.UnknownProperty" => DbusErrorKind::UnknownObject,
+            "org.freedesktop.DBus.Error.Timeout" | "org.freedesktop.DBus.Error.TimedOut" => {
+                DbusErrorKind::Timeout
+            }
+            "org.freedesktop.DBus.Error.NoReply" => DbusErrorKind::NoReply,
+            "org.freedesktop.DBus.Error.ServiceUnknown"
+            | "org.freedesktop.DBus.Error.NameHasNoOwner" => DbusErrorKind::ServiceUnknown,
+            _ => DbusErrorKind::Other,
+        }
+    }
+}
+
+impl From<&dbus::Error> for DbusError {
+    fn from(error: &dbus::Error) -> Self {
+        Self {
+            // Errors raised by libdbus itself may lack a name
+            name: error
+                .name()
+                .unwrap_or("org.freedesktop.DBus.Error.Failed")
+                .to_string(),
+            message: error.message().unwrap_or_default().to_string(),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("D-Bus connection failed: {0}")]
     DbusConnection(#[from] dbus::Error),
 
+    #[error("Access denied: {0}")]
+    AccessDenied(DbusError),
+
+    #[error("Unknown object: {0}")]
+    UnknownObject(DbusError),
+
+    #[error("Timed out: {0}")]
+    Timeout(DbusError),
+
+    #[error("No reply: {0}")]
+    NoReply(DbusError),
+
+    #[error("Service unknown: {0}")]
+    ServiceUnknown(DbusError),
+
+    #[error("D-Bus call failed: {0}")]
+    DbusCall(DbusError),
+
     #[error("Service introspection failed: {0}")]
     ServiceIntrospection(String),
 
@@ -25,16 +106,34 @@ pub enum AppError {
     UrlDecode(String),
 
     #[error("Internal server error: {0}")]
-    #[allow(dead_code)]
     Internal(String),
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let (status, message) = match self {
+impl From<DbusError> for AppError {
+    fn from(error: DbusError) -> Self {
+        match error.kind() {
+            DbusErrorKind::AccessDenied => AppError::AccessDenied(error),
+            DbusErrorKind::UnknownObject => AppError::UnknownObject(error),
+            DbusErrorKind::Timeout => AppError::Timeout(error),
+            DbusErrorKind::NoReply => AppError::NoReply(error),
+            DbusErrorKind::ServiceUnknown => AppError::ServiceUnknown(error),
+            DbusErrorKind::Other => AppError::DbusCall(error),
+        }
+    }
+}
+
+impl AppError {
+    fn status(&self) -> (StatusCode, &'static str) {
+        match self {
             AppError::DbusConnection(_) => {
                 (StatusCode::SERVICE_UNAVAILABLE, "D-Bus service unavailable")
             }
+            AppError::AccessDenied(_) => (StatusCode::FORBIDDEN, "Access denied"),
+            AppError::UnknownObject(_) => (StatusCode::NOT_FOUND, "Unknown object"),
+            AppError::Timeout(_) => (StatusCode::GATEWAY_TIMEOUT, "Service timed out"),
+            AppError::NoReply(_) => (StatusCode::GATEWAY_TIMEOUT, "Service did not reply"),
+            AppError::ServiceUnknown(_) => (StatusCode::NOT_FOUND, "Service unknown"),
+            AppError::DbusCall(_) => (StatusCode::BAD_GATEWAY, "D-Bus call failed"),
             AppError::ServiceIntrospection(_) => {
                 (StatusCode::BAD_GATEWAY, "Failed to introspect service")
             }
@@ -43,6 +142,56 @@ impl IntoResponse for AppError {
             AppError::ObjectNotFound(_) => (StatusCode::NOT_FOUND, "Object not found"),
             AppError::UrlDecode(_) => (StatusCode::BAD_REQUEST, "Invalid URL encoding"),
             AppError::Internal(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"),
+        }
+    }
+
+    /// The error reply behind this error, if it came from the bus or a service.
+    pub fn dbus_error(&self) -> Option<DbusError> {
+        match self {
+            AppError::DbusConnection(e) => Some(DbusError::from(e)),
+            AppError::AccessDenied(e)
+            | AppError::UnknownObject(e)
+            | AppError::Timeout(e)
+            | AppError::NoReply(e)
+            | AppError::ServiceUnknown(e)
+            | AppError::DbusCall(e) => Some(e.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let (status, title) = self.status();
+        let dbus_error = self.dbus_error().map(|e| {
+            json!({
+                "name": e.name,
+                "message": e.message,
+                "kind": e.kind(),
+            })
+        });
+        json!({
+            "status": status.as_u16(),
+            "error": title,
+            "message": self.to_string(),
+            "dbus_error": dbus_error,
+        })
+    }
+}
+
+/// The status and JSON form of an error response, for [`json_errors`].
+#[derive(Debug, Clone)]
+pub struct ErrorJson(pub StatusCode, pub Value);
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let (status, title) = self.status();
+
+        let dbus_details = match self.dbus_error() {
+            Some(e) => format!(
+                "<table><tr><th>Error name</th><td><code>{}</code></td></tr><tr><th>Message</th><td>{}</td></tr></table>",
+                html_escape(&e.name),
+                html_escape(&e.message)
+            ),
+            None => String::new(),
         };
 
         let html = format!(
@@ -53,6 +202,7 @@ impl IntoResponse for AppError {
     <style>
         body {{ font-family: 'Courier New', 'Monaco', 'Menlo', monospace; margin: 40px; }}
         .error {{ color: #d32f2f; background: #ffebee; padding: 20px; border-radius: 4px; }}
+        th, td {{ text-align: left; padding: 4px 12px 4px 0; }}
     </style>
 </head>
 <body>
@@ -60,15 +210,51 @@ impl IntoResponse for AppError {
     <div class="error">
         <h2>{}</h2>
         <p>{}</p>
+        <p>{}</p>
+        {}
         <p><a href="/local/dbus_explorer/app">← Back to Home</a></p>
     </div>
 </body>
 </html>"#,
             status.as_u16(),
-            message
+            title,
+            html_escape(&self.to_string()),
+            dbus_details
         );
 
-        (status, Html(html)).into_response()
+        let mut response = (status, Html(html)).into_response();
+        response
+            .extensions_mut()
+            .insert(ErrorJson(status, self.to_json()));
+        response
+    }
+}
+
+impl AppError {
+    /// Keep a page that shows this error, but answer JSON requests with the error itself.
+    pub fn attach_json(&self, mut response: Response) -> Response {
+        let (status, _) = self.status();
+        response
+            .extensions_mut()
+            .insert(ErrorJson(status, self.to_json()));
+        response
+    }
+}
+
+/// Answer requests that prefer JSON over HTML with the JSON form of error pages.
+pub async fn json_errors(request: Request, next: Next) -> Response {
+    let wants_json = request
+        .headers()
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json") && !accept.contains("text/html"));
+
+    let response = next.run(request).await;
+    match response.extensions().get::<ErrorJson>() {
+        Some(ErrorJson(status, json)) if wants_json => {
+            (*status, Json(json.clone())).into_response()
+        }
+        _ => response,
     }
 }
 
@@ -0,0 +1,832 @@
+use dbus::arg::messageitem::MessageItem;
+
+use crate::{
+    signature::{parse_single_type, DbusType},
+    values::{array_item, dict_item, parse_basic, ValueError},
+};
+
+/// Parse a value in the GVariant text format, as printed by `gdbus call`, as the given type.
+///
+/// Type annotations like `uint32 5` or `@as []` must agree with the expected type. Inside
+/// variants the type is inferred the way GLib does it: plain integers are `int32`, numbers
+/// with a fraction or exponent `double`, and empty containers need an annotation. Byte
+/// strings like `b'abc'` include the terminating nul byte, as in GLib.
+pub fn parse_gvariant(
+    text: &str,
+    dbus_type: &DbusType,
+    location: &str,
+) -> Result<MessageItem, ValueError> {
+    let mut parser = Parser {
+        text,
+        position: 0,
+        location,
+    };
+    let node = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.position < text.len() {
+        return Err(parser.error("unexpected text after the value"));
+    }
+    to_item(&node, dbus_type, location)
+}
+
+/// A parsed but not yet typed value.
+#[derive(Debug)]
+enum Node {
+    Annotated(DbusType, Box<Node>),
+    Variant(Box<Node>),
+    Array(Vec<Node>),
+    Dict(Vec<(Node, Node)>),
+    /// A single `{key, value}` entry, only valid as an array element.
+    Entry(Box<Node>, Box<Node>),
+    Tuple(Vec<Node>),
+    Str(String),
+    Bytes(Vec<u8>),
+    Number(String),
+    Bool(bool),
+}
+
+impl Node {
+    fn describe(&self) -> &'static str {
+        match self {
+            Node::Annotated(_, _) => "an annotated value",
+            Node::Variant(_) => "a variant",
+            Node::Array(_) => "an array",
+            Node::Dict(_) => "a dict",
+            Node::Entry(_, _) => "a dict entry",
+            Node::Tuple(_) => "a tuple",
+            Node::Str(_) => "a string",
+            Node::Bytes(_) => "a byte string",
+            Node::Number(_) => "a number",
+            Node::Bool(_) => "a boolean",
+        }
+    }
+
+    /// The key as written, for error locations.
+    fn key_text(&self) -> String {
+        match self {
+            Node::Annotated(_, inner) => inner.key_text(),
+            Node::Str(text) | Node::Number(text) => text.clone(),
+            Node::Bool(b) => b.to_string(),
+            other => other.describe().to_string(),
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+    location: &'a str,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: impl Into<String>) -> ValueError {
+        ValueError {
+            location: self.location.to_string(),
+            message: format!("at position {}: {}", self.position, message.into()),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek().filter(|c| c.is_whitespace()) {
+            self.position += c.len_utf8();
+        }
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.position += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ValueError> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected {expected:?}")))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Node, ValueError> {
+        self.skip_whitespace();
+        let Some(c) = self.peek() else {
+            return Err(self.error("unexpected end of text"));
+        };
+
+        match c {
+            '@' => {
+                self.position += 1;
+                let annotation = self.parse_type_annotation()?;
+                let value = self.parse_value()?;
+                Ok(Node::Annotated(annotation, Box::new(value)))
+            }
+            '<' => {
+                self.position += 1;
+                let value = self.parse_value()?;
+                self.expect('>')?;
+                Ok(Node::Variant(Box::new(value)))
+            }
+            '[' => {
+                self.position += 1;
+                Ok(Node::Array(self.parse_list(']')?))
+            }
+            '(' => {
+                self.position += 1;
+                Ok(Node::Tuple(self.parse_list(')')?))
+            }
+            '{' => {
+                self.position += 1;
+                self.parse_dict()
+            }
+            '\'' | '"' => {
+                let bytes = self.parse_quoted()?;
+                String::from_utf8(bytes)
+                    .map(Node::Str)
+                    .map_err(|_| self.error("string is not valid UTF-8"))
+            }
+            'b' if matches!(
+                self.text[1 + self.position..].chars().next(),
+                Some('\'' | '"')
+            ) =>
+            {
+                self.position += 1;
+                let mut bytes = self.parse_quoted()?;
+                bytes.push(0);
+                Ok(Node::Bytes(bytes))
+            }
+            c if c.is_ascii_digit() || "+-.".contains(c) => Ok(Node::Number(self.parse_number())),
+            c if c.is_ascii_alphabetic() => self.parse_word(),
+            other => Err(self.error(format!("unexpected {other:?}"))),
+        }
+    }
+
+    /// Comma-separated values up to `close`, allowing a trailing comma as in `(1,)`.
+    fn parse_list(&mut self, close: char) -> Result<Vec<Node>, ValueError> {
+        let mut values = Vec::new();
+        if self.eat(close) {
+            return Ok(values);
+        }
+        loop {
+            values.push(self.parse_value()?);
+            if self.eat(',') {
+                if self.eat(close) {
+                    return Ok(values);
+                }
+            } else {
+                self.expect(close)?;
+                return Ok(values);
+            }
+        }
+    }
+
+    /// Either a dict `{k: v, ...}` or a single dict entry `{k, v}`, after the `{`.
+    fn parse_dict(&mut self) -> Result<Node, ValueError> {
+        if self.eat('}') {
+            return Ok(Node::Dict(Vec::new()));
+        }
+
+        let key = self.parse_value()?;
+        if self.eat(',') {
+            let value = self.parse_value()?;
+            self.expect('}')?;
+            return Ok(Node::Entry(Box::new(key), Box::new(value)));
+        }
+
+        self.expect(':')?;
+        let mut entries = vec![(key, self.parse_value()?)];
+        while self.eat(',') {
+            if self.eat('}') {
+                return Ok(Node::Dict(entries));
+            }
+            let key = self.parse_value()?;
+            self.expect(':')?;
+            entries.push((key, self.parse_value()?));
+        }
+        self.expect('}')?;
+        Ok(Node::Dict(entries))
+    }
+
+    /// A single complete type after `@`, e.g. `a{sv}`.
+    fn parse_type_annotation(&mut self) -> Result<DbusType, ValueError> {
+        let text = self.text;
+        let start = self.position;
+        let mut depth = 0usize;
+        for c in text[start..].chars() {
+            self.position += c.len_utf8();
+            match c {
+                'a' => continue,
+                '(' | '{' => depth += 1,
+                ')' | '}' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            if depth == 0 {
+                break;
+            }
+        }
+
+        parse_single_type(&text[start..self.position]).map_err(|e| {
+            self.position = start;
+            self.error(e.to_string())
+        })
+    }
+
+    /// The contents of a quoted string with escapes resolved. Octal escapes give raw bytes.
+    fn parse_quoted(&mut self) -> Result<Vec<u8>, ValueError> {
+        let text = self.text;
+        let Some(quote) = self.peek() else {
+            return Err(self.error("unexpected end of text"));
+        };
+        self.position += 1;
+
+        let mut bytes = Vec::new();
+        let mut chars = text[self.position..].chars().peekable();
+        while let Some(c) = chars.next() {
+            self.position += c.len_utf8();
+            if c == quote {
+                return Ok(bytes);
+            }
+            if c != '\\' {
+                let mut buffer = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                continue;
+            }
+
+            let Some(escape) = chars.next() else {
+                break;
+            };
+            self.position += escape.len_utf8();
+            let unescaped = match escape {
+                'a' => '\x07',
+                'b' => '\x08',
+                'f' => '\x0c',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'v' => '\x0b',
+                'u' | 'U' => {
+                    let length = if escape == 'u' { 4 } else { 8 };
+                    let digits: String = (0..length).filter_map(|_| chars.next()).collect();
+                    self.position += digits.len();
+                    u32::from_str_radix(&digits, 16)
+                        .ok()
+                        .filter(|_| digits.len() == length)
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| self.error(format!("invalid escape \\{escape}{digits}")))?
+                }
+                '0'..='7' => {
+                    let mut value = escape.to_digit(8).unwrap_or_default();
+                    for _ in 0..2 {
+                        let Some(digit) = chars.peek().and_then(|c| c.to_digit(8)) else {
+                            break;
+                        };
+                        chars.next();
+                        self.position += 1;
+                        value = value * 8 + digit;
+                    }
+                    let byte =
+                        u8::try_from(value).map_err(|_| self.error("octal escape out of range"))?;
+                    bytes.push(byte);
+                    continue;
+                }
+                other => other,
+            };
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(unescaped.encode_utf8(&mut buffer).as_bytes());
+        }
+
+        Err(self.error("unterminated string"))
+    }
+
+    fn parse_number(&mut self) -> String {
+        let start = self.position;
+        let mut previous = None;
+        while let Some(c) = self.peek() {
+            let exponent_sign = "+-".contains(c)
+                && (self.position == start || matches!(previous, Some('e' | 'E')))
+                && !self.text[start..self.position].contains(['x', 'X']);
+            if !(c.is_ascii_alphanumeric() || c == '.' || exponent_sign) {
+                break;
+            }
+            self.position += 1;
+            previous = Some(c);
+        }
+        self.text[start..self.position].to_string()
+    }
+
+    fn parse_word(&mut self) -> Result<Node, ValueError> {
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            self.position += 1;
+        }
+
+        let annotation = match &self.text[start..self.position] {
+            "true" => return Ok(Node::Bool(true)),
+            "false" => return Ok(Node::Bool(false)),
+            word @ ("inf" | "nan") => return Ok(Node::Number(word.to_string())),
+            "boolean" => DbusType::Boolean,
+            "byte" => DbusType::Byte,
+            "int16" => DbusType::Int16,
+            "uint16" => DbusType::UInt16,
+            "int32" => DbusType::Int32,
+            "uint32" => DbusType::UInt32,
+            "int64" => DbusType::Int64,
+            "uint64" => DbusType::UInt64,
+            "double" => DbusType::Double,
+            "string" => DbusType::String,
+            "objectpath" => DbusType::ObjectPath,
+            "signature" => DbusType::Signature,
+            "handle" => DbusType::UnixFd,
+            "just" | "nothing" => {
+                self.position = start;
+                return Err(self.error("maybe values cannot be sent over D-Bus"));
+            }
+            word => {
+                let word = word.to_string();
+                self.position = start;
+                return Err(self.error(format!("unknown word {word:?}")));
+            }
+        };
+
+        let value = self.parse_value()?;
+        Ok(Node::Annotated(annotation, Box::new(value)))
+    }
+}
+
+fn to_item(node: &Node, dbus_type: &DbusType, location: &str) -> Result<MessageItem, ValueError> {
+    let error = |message: String| ValueError {
+        location: location.to_string(),
+        message,
+    };
+
+    match (node, dbus_type) {
+        (Node::Annotated(annotation, value), _) => {
+            if annotation != dbus_type {
+                return Err(error(format!(
+                    "annotated as {} but expected {}",
+                    annotation.describe(),
+                    dbus_type.describe()
+                )));
+            }
+            to_item(value, dbus_type, location)
+        }
+        (Node::Variant(value), DbusType::Variant) => {
+            let value_type = infer_type(value).map_err(error)?;
+            let value = to_item(value, &value_type, &format!("{location}.value"))?;
+            Ok(MessageItem::Variant(Box::new(value)))
+        }
+        (Node::Array(elements), DbusType::Array(element_type)) => {
+            let items = elements
+                .iter()
+                .enumerate()
+                .map(|(i, element)| to_item(element, element_type, &format!("{location}[{i}]")))
+                .collect::<Result<Vec<_>, _>>()?;
+            array_item(items, dbus_type).map_err(error)
+        }
+        (Node::Bytes(bytes), DbusType::Array(element_type)) if **element_type == DbusType::Byte => {
+            let items = bytes.iter().map(|b| MessageItem::Byte(*b)).collect();
+            array_item(items, dbus_type).map_err(error)
+        }
+        (Node::Array(elements), DbusType::Dict(key_type, value_type)) => {
+            let items = elements
+                .iter()
+                .enumerate()
+                .map(|(i, element)| match element {
+                    Node::Entry(key, value) => {
+                        let entry_location = format!("{location}[{:?}]", key.key_text());
+                        Ok((
+                            to_item(key, key_type, &entry_location)?,
+                            to_item(value, value_type, &entry_location)?,
+                        ))
+                    }
+                    other => Err(ValueError {
+                        location: format!("{location}[{i}]"),
+                        message: format!(
+                            "expected a dict entry like {{key, value}} but got {}",
+                            other.describe()
+                        ),
+                    }),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            dict_item(items, key_type, value_type).map_err(error)
+        }
+        (Node::Dict(entries), DbusType::Dict(key_type, value_type)) => {
+            let items = entries
+                .iter()
+                .map(|(key, value)| {
+                    let entry_location = format!("{location}[{:?}]", key.key_text());
+                    Ok((
+                        to_item(key, key_type, &entry_location)?,
+                        to_item(value, value_type, &entry_location)?,
+                    ))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            dict_item(items, key_type, value_type).map_err(error)
+        }
+        (Node::Tuple(fields), DbusType::Struct(field_types)) => {
+            if fields.len() != field_types.len() {
+                return Err(error(format!(
+                    "expected {} fields but got {}",
+                    field_types.len(),
+                    fields.len()
+                )));
+            }
+            let items = fields
+                .iter()
+                .zip(field_types)
+                .enumerate()
+                .map(|(i, (field, field_type))| {
+                    to_item(field, field_type, &format!("{location}.{i}"))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(MessageItem::Struct(items))
+        }
+        (Node::Str(text), DbusType::String | DbusType::ObjectPath | DbusType::Signature) => {
+            parse_basic(text, dbus_type).map_err(error)
+        }
+        (Node::Bool(b), DbusType::Boolean) => Ok(MessageItem::Bool(*b)),
+        (Node::Number(text), DbusType::Double) => text
+            .parse()
+            .map(MessageItem::Double)
+            .map_err(|_| error(format!("expected double but got {text:?}"))),
+        (Node::Number(text), _) if dbus_type.is_basic() => {
+            integer_item(text, dbus_type).map_err(error)
+        }
+        (node, _) => Err(error(format!(
+            "expected {} but got {}",
+            dbus_type.describe(),
+            node.describe()
+        ))),
+    }
+}
+
+fn integer_item(text: &str, dbus_type: &DbusType) -> Result<MessageItem, String> {
+    let value = parse_integer(text)
+        .ok_or_else(|| format!("expected {} but got {text:?}", dbus_type.describe()))?;
+    let out_of_range = |_| format!("{value} is out of range for {}", dbus_type.describe());
+
+    let item = match dbus_type {
+        DbusType::Byte => MessageItem::Byte(value.try_into().map_err(out_of_range)?),
+        DbusType::Int16 => MessageItem::Int16(value.try_into().map_err(out_of_range)?),
+        DbusType::UInt16 => MessageItem::UInt16(value.try_into().map_err(out_of_range)?),
+        DbusType::Int32 => MessageItem::Int32(value.try_into().map_err(out_of_range)?),
+        DbusType::UInt32 => MessageItem::UInt32(value.try_into().map_err(out_of_range)?),
+        DbusType::Int64 => MessageItem::Int64(value.try_into().map_err(out_of_range)?),
+        DbusType::UInt64 => MessageItem::UInt64(value.try_into().map_err(out_of_range)?),
+        other => return Err(format!("expected {} but got a number", other.describe())),
+    };
+    Ok(item)
+}
+
+/// Decimal, hexadecimal with `0x` or octal with a leading `0`, as in GLib.
+fn parse_integer(text: &str) -> Option<i128> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    if !digits.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let magnitude = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        i128::from_str_radix(hex, 16).ok()?
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i128::from_str_radix(&digits[1..], 8).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+/// The type GLib would give a value without an expected type, i.e. inside a variant.
+fn infer_type(node: &Node) -> Result<DbusType, String> {
+    let inferred = match node {
+        Node::Annotated(annotation, _) => annotation.clone(),
+        Node::Variant(_) => DbusType::Variant,
+        Node::Str(_) => DbusType::String,
+        Node::Bytes(_) => DbusType::Array(Box::new(DbusType::Byte)),
+        Node::Bool(_) => DbusType::Boolean,
+        Node::Number(text) => {
+            let is_hex = text.contains(['x', 'X']);
+            if !is_hex
+                && (text.contains(['.', 'e', 'E']) || text.contains("inf") || text.contains("nan"))
+            {
+                DbusType::Double
+            } else {
+                DbusType::Int32
+            }
+        }
+        Node::Array(elements) => {
+            // Annotated elements decide the type, e.g. [1, int64 2]
+            let Some(element) = elements
+                .iter()
+                .find(|e| matches!(e, Node::Annotated(_, _)))
+                .or_else(|| elements.first())
+            else {
+                return Err(
+                    "cannot infer the type of an empty array, annotate it like @as []".to_string(),
+                );
+            };
+            match element {
+                Node::Entry(key, value) => {
+                    DbusType::Dict(Box::new(infer_type(key)?), Box::new(infer_type(value)?))
+                }
+                element => DbusType::Array(Box::new(infer_type(element)?)),
+            }
+        }
+        Node::Dict(entries) => {
+            let Some((key, value)) = entries.first() else {
+                return Err(
+                    "cannot infer the type of an empty dict, annotate it like @a{sv} {}"
+                        .to_string(),
+                );
+            };
+            DbusType::Dict(Box::new(infer_type(key)?), Box::new(infer_type(value)?))
+        }
+        Node::Tuple(fields) => {
+            DbusType::Struct(fields.iter().map(infer_type).collect::<Result<_, _>>()?)
+        }
+        Node::Entry(_, _) => {
+            return Err("dict entries are only allowed directly in arrays".to_string())
+        }
+    };
+
+    // Rejects what D-Bus cannot express, such as empty structs or dicts with container keys
+    parse_single_type(&inferred.to_string()).map_err(|e| e.to_string())
+}
+
+/// Format a value in the GVariant text format understood by `gdbus`.
+///
+/// Values inside variants carry no expected type, so numbers and empty containers are
+/// annotated with their type there.
+pub fn gvariant_text(item: &MessageItem, annotate: bool) -> String {
+    let annotation = |keyword: &str, text: String| {
+        if annotate {
+            format!("{keyword} {text}")
+        } else {
+            text
+        }
+    };
+
+    match item {
+        MessageItem::Array(array) if array.is_empty() && annotate => {
+            format!("@{} []", item.signature())
+        }
+        MessageItem::Array(array) => format!(
+            "[{}]",
+            array
+                .iter()
+                .map(|e| gvariant_text(e, annotate))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        MessageItem::Dict(dict) if dict.is_empty() && annotate => {
+            format!("@{} {{}}", item.signature())
+        }
+        MessageItem::Dict(dict) => format!(
+            "{{{}}}",
+            dict.iter()
+                .map(|(k, v)| format!(
+                    "{}: {}",
+                    gvariant_text(k, annotate),
+                    gvariant_text(v, annotate)
+                ))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        MessageItem::Struct(fields) if fields.len() == 1 => {
+            format!("({},)", gvariant_text(&fields[0], annotate))
+        }
+        MessageItem::Struct(fields) => format!(
+            "({})",
+            fields
+                .iter()
+                .map(|f| gvariant_text(f, annotate))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        MessageItem::Variant(inner) => format!("<{}>", gvariant_text(inner, true)),
+        MessageItem::Str(s) => gvariant_string(s),
+        MessageItem::ObjectPath(path) => annotation("objectpath", gvariant_string(path)),
+        MessageItem::Signature(signature) => annotation("signature", gvariant_string(signature)),
+        MessageItem::Bool(b) => b.to_string(),
+        MessageItem::Byte(n) => annotation("byte", format!("0x{n:02x}")),
+        MessageItem::Int16(n) => annotation("int16", n.to_string()),
+        MessageItem::UInt16(n) => annotation("uint16", n.to_string()),
+        MessageItem::Int32(n) => n.to_string(),
+        MessageItem::UInt32(n) => annotation("uint32", n.to_string()),
+        MessageItem::Int64(n) => annotation("int64", n.to_string()),
+        MessageItem::UInt64(n) => annotation("uint64", n.to_string()),
+        MessageItem::Double(n) => format!("{n:?}"),
+        MessageItem::UnixFd(_) => annotation("handle", "0".to_string()),
+    }
+}
+
+/// A GVariant string literal.
+pub fn gvariant_string(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str, signature: &str) -> Result<MessageItem, ValueError> {
+        parse_gvariant(text, &parse_single_type(signature).unwrap(), "arg")
+    }
+
+    fn error(text: &str, signature: &str) -> String {
+        parse(text, signature).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn type_annotations() {
+        assert_eq!(parse("uint32 5", "u").unwrap(), MessageItem::UInt32(5));
+        assert_eq!(parse("int64 -5", "x").unwrap(), MessageItem::Int64(-5));
+        assert_eq!(parse("byte 0xff", "y").unwrap(), MessageItem::Byte(255));
+        assert_eq!(
+            parse("objectpath '/a'", "o").unwrap(),
+            MessageItem::ObjectPath("/a".into())
+        );
+        assert_eq!(
+            error("int64 5", "u"),
+            "arg: annotated as int64 but expected uint32"
+        );
+        assert_eq!(error("256", "y"), "arg: 256 is out of range for byte");
+    }
+
+    #[test]
+    fn type_prefixes() {
+        assert_eq!(parse("@as []", "as").unwrap(), parse("[]", "as").unwrap());
+        assert_eq!(
+            parse("@a{sv} {}", "a{sv}").unwrap().signature().to_string(),
+            "a{sv}"
+        );
+        assert_eq!(parse("@u 7", "u").unwrap(), MessageItem::UInt32(7));
+        assert!(error("@ai []", "as").contains("annotated as"));
+        assert!(error("@z 1", "i").starts_with("arg: at position 1:"));
+    }
+
+    #[test]
+    fn variants() {
+        let variant = |item| MessageItem::Variant(Box::new(item));
+        assert_eq!(parse("<5>", "v").unwrap(), variant(MessageItem::Int32(5)));
+        assert_eq!(
+            parse("<1.5>", "v").unwrap(),
+            variant(MessageItem::Double(1.5))
+        );
+        assert_eq!(
+            parse("<'a'>", "v").unwrap(),
+            variant(MessageItem::Str("a".into()))
+        );
+        assert_eq!(
+            parse("<int64 5>", "v").unwrap(),
+            variant(MessageItem::Int64(5))
+        );
+        assert_eq!(
+            parse("<[1, int64 2]>", "v")
+                .unwrap()
+                .signature()
+                .to_string(),
+            "v"
+        );
+        match parse("<[1, int64 2]>", "v").unwrap() {
+            MessageItem::Variant(inner) => assert_eq!(inner.signature().to_string(), "ax"),
+            other => panic!("expected a variant, got {other:?}"),
+        }
+        assert_eq!(
+            error("<[]>", "v"),
+            "arg: cannot infer the type of an empty array, annotate it like @as []"
+        );
+        assert_eq!(error("5", "v"), "arg: expected variant but got a number");
+    }
+
+    #[test]
+    fn dicts_and_dict_entries() {
+        let dict = parse("{'a': 1, 'b': 2}", "a{si}").unwrap();
+        assert_eq!(parse("[{'a', 1}, {'b', 2}]", "a{si}").unwrap(), dict);
+        assert_eq!(parse("{'a': 1, 'b': 2,}", "a{si}").unwrap(), dict);
+        match parse("<{'a': <true>}>", "v").unwrap() {
+            MessageItem::Variant(inner) => assert_eq!(inner.signature().to_string(), "a{sv}"),
+            other => panic!("expected a variant, got {other:?}"),
+        }
+        assert_eq!(
+            error("[1]", "a{si}"),
+            "arg[0]: expected a dict entry like {key, value} but got a number"
+        );
+        assert_eq!(
+            error("<{'a', 1}>", "v"),
+            "arg: dict entries are only allowed directly in arrays"
+        );
+    }
+
+    #[test]
+    fn string_escapes() {
+        let string = |text| match parse(text, "s").unwrap() {
+            MessageItem::Str(s) => s,
+            other => panic!("expected a string, got {other:?}"),
+        };
+        assert_eq!(string(r"'it\'s'"), "it's");
+        assert_eq!(string(r#""say \"hi\"""#), "say \"hi\"");
+        assert_eq!(string(r"'a\tb\nc\\'"), "a\tb\nc\\");
+        assert_eq!(string(r"'é\U0001F600'"), "é😀");
+        assert_eq!(string("'é'"), "é");
+        assert_eq!(
+            parse(r"b'a\001'", "ay").unwrap(),
+            parse("[0x61, 1, 0]", "ay").unwrap()
+        );
+        assert_eq!(
+            error(r"'\u00g0'", "s"),
+            r"arg: at position 7: invalid escape \u00g0"
+        );
+        assert_eq!(
+            error("'abc", "s"),
+            "arg: at position 4: unterminated string"
+        );
+        assert_eq!(
+            error(r"'\377'", "s"),
+            "arg: at position 6: string is not valid UTF-8"
+        );
+    }
+
+    #[test]
+    fn error_locations() {
+        assert_eq!(
+            error("[1, 'x']", "ai"),
+            "arg[1]: expected int32 but got a string"
+        );
+        assert_eq!(
+            error("{'a': 'x'}", "a{si}"),
+            r#"arg["a"]: expected int32 but got a string"#
+        );
+        assert_eq!(
+            error("(1, 'x')", "(ii)"),
+            "arg.1: expected int32 but got a string"
+        );
+        assert_eq!(
+            error("<(1, [true, 2])>", "v"),
+            "arg.value.1[1]: expected boolean but got a number"
+        );
+        assert_eq!(error("[1, 2", "ai"), "arg: at position 5: expected ']'");
+        assert_eq!(
+            error("1 2", "i"),
+            "arg: at position 2: unexpected text after the value"
+        );
+        assert_eq!(
+            error("just 1", "i"),
+            "arg: at position 0: maybe values cannot be sent over D-Bus"
+        );
+    }
+
+    #[test]
+    fn text_round_trips() {
+        let cases = [
+            ("s", r"'it\'s a \\ test'"),
+            ("y", "0x2a"),
+            ("ay", "[0x00, 0xff]"),
+            ("n", "-3"),
+            ("q", "3"),
+            ("i", "-2147483648"),
+            ("u", "4294967295"),
+            ("x", "-9223372036854775808"),
+            ("t", "18446744073709551615"),
+            ("d", "1.5"),
+            ("b", "true"),
+            ("o", "'/org/example'"),
+            ("g", "'a{sv}'"),
+            ("as", "[]"),
+            (
+                "a{sv}",
+                "{'a': <uint32 1>, 'b': <@as []>, 'c': <<'nested'>>}",
+            ),
+            ("(i)", "(1,)"),
+            ("(sa{ss}v)", "('x', {}, <(int64 1, 2.0)>)"),
+        ];
+        for (signature, text) in cases {
+            let item = parse(text, signature).unwrap();
+            for annotate in [false, true] {
+                let printed = gvariant_text(&item, annotate);
+                let reparsed = parse(&printed, signature)
+                    .unwrap_or_else(|e| panic!("{text:?} printed as {printed:?}: {e}"));
+                assert_eq!(reparsed, item, "{text:?} printed as {printed:?}");
+            }
+        }
+        assert_eq!(
+            gvariant_text(&parse("{'a': <uint32 1>}", "a{sv}").unwrap(), false),
+            "{'a': <uint32 1>}"
+        );
+        assert_eq!(gvariant_text(&parse("[]", "as").unwrap(), true), "@as []");
+    }
+}
@@ -1,19 +1,46 @@
-use axum::{extract::Path, response::Html};
+use std::{collections::VecDeque, path::Path as FilePath, time::Duration};
+
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::header,
+    response::{Html, IntoResponse, Response},
+};
 use dbus::blocking::Connection;
 use log::info;
+use serde::Deserialize;
 
 use crate::{
+    bus_policy::{BusPolicy, Identity, MethodCallTarget, PolicyRule, PolicySubject, RuleKind},
+    bus_stats::{self, read_bus_stats, BusStats},
+    command_lines::monitor_command_lines,
+    dbus_calls::{
+        read_properties, MethodCall, ObjectAction, PropertyValues, PropertyWrite, SignalEmission,
+    },
     dbus_introspection::{
-        analyze_service, discover_services, get_service_names_only, introspect_object, ServiceInfo,
+        analyze_service, discover_services, get_service_names_only, introspect_object, ObjectInfo,
+        ServiceInfo,
     },
-    error::{AppError, Result},
+    error::{AppError, DbusErrorKind, Result},
+    health::{probe_services, HealthHistory, HealthStatus, SLOW_THRESHOLD},
+    interface_catalog::{build_interface_catalog, InterfaceCatalogEntry},
+    interface_views::RendererRegistry,
+    lint::{lint_service, Lint, LintRule, Severity},
+    match_rules::{capture, rule_text, Capture, MatchRuleSpec},
+    metrics::METRICS,
+    recorder::{RecordedStep, StepKind},
+    request_log,
+    state::AppState,
+    systemd::{active_state_class, resolve_unit, SystemdUnit, SYSTEMD_SERVICE},
     templates::{
-        render_dbus_types_reference, render_object_details, render_service_list, PageTemplate,
+        render_argument_editor_script, render_dbus_types_reference, render_object_details,
+        render_object_xml, render_service_list, PageTemplate,
     },
     utils::{
-        build_breadcrumb_navigation, build_object_flat_list, find_child_objects,
-        validate_object_path, validate_service_name,
+        build_breadcrumb_navigation, build_object_flat_list, find_child_objects, html_escape,
+        object_page_url, validate_interface_name, validate_member_name, validate_object_path,
+        validate_service_name,
     },
+    value_view::{BytesFormat, ValueFormat},
 };
 
 pub async fn landing_page() -> Result<Html<String>> {
@@ -33,7 +60,10 @@ pub async fn landing_page() -> Result<Html<String>> {
     Ok(Html(page.render()))
 }
 
-pub async fn service_page(Path(service_name): Path<String>) -> Result<Html<String>> {
+pub async fn service_page(
+    State(state): State<AppState>,
+    Path(service_name): Path<String>,
+) -> Result<Html<String>> {
     let service_name =
         urlencoding::decode(&service_name).map_err(|e| AppError::UrlDecode(e.to_string()))?;
 
@@ -42,77 +72,977 @@ pub async fn service_page(Path(service_name): Path<String>) -> Result<Html<Strin
 
     let conn = Connection::new_system().map_err(AppError::DbusConnection)?;
 
-    let service_info = analyze_service(&conn, &service_name);
+    let mut service_info = analyze_service(&conn, &service_name);
 
     if service_info.objects.is_empty() && service_info.error.is_some() {
         return Err(AppError::ServiceNotFound(service_name.to_string()));
     }
+    resolve_unit(&conn, &mut service_info);
 
     let navigation = format!(
         r#"<div class="navigation"><a href="/local/dbus_explorer/app">Home</a> / {}</div>"#,
         html_escape(&service_name)
     );
 
-    let content = render_service_content(&service_info, &service_name);
+    let content = render_service_content(&service_info, &service_name, &state.bus_config);
     let body = format!("{navigation}{content}");
 
     let page = PageTemplate::new(&service_name, body);
     Ok(Html(page.render()))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ObjectPageQuery {
+    /// How byte arrays are shown, see [`BytesFormat::from_name`].
+    bytes: Option<String>,
+    /// "collapsed" (default) or "expanded".
+    standard: Option<String>,
+    /// Whose access to evaluate against the bus policy: "self", "user" or "group".
+    policy_as: Option<String>,
+    /// The user or group name or ID, unless `policy_as` is "self".
+    policy_name: Option<String>,
+}
+
+/// Display options of an object page.
+#[derive(Debug, Clone)]
+struct ObjectPageOptions {
+    format: ValueFormat,
+    collapse_standard: bool,
+    policy_subject: Option<PolicySubject>,
+}
+
+impl ObjectPageOptions {
+    fn new(service_name: &str) -> Self {
+        Self {
+            format: ValueFormat::for_service(service_name),
+            collapse_standard: true,
+            policy_subject: None,
+        }
+    }
+
+    fn from_query(service_name: &str, query: &ObjectPageQuery) -> Result<Self> {
+        let mut options = Self::new(service_name);
+        if let Some(bytes) = &query.bytes {
+            options.format.bytes = BytesFormat::from_name(bytes).ok_or_else(|| {
+                AppError::InvalidInput(format!("Unknown byte array format {bytes}"))
+            })?;
+        }
+        match query.standard.as_deref() {
+            None | Some("collapsed") => {}
+            Some("expanded") => options.collapse_standard = false,
+            Some(other) => {
+                return Err(AppError::InvalidInput(format!(
+                    "Unknown option for standard interfaces {other}"
+                )))
+            }
+        }
+        if let Some(kind) = query.policy_as.as_deref().filter(|kind| !kind.is_empty()) {
+            options.policy_subject = Some(
+                PolicySubject::from_parts(kind, query.policy_name.as_deref()).ok_or_else(|| {
+                    AppError::InvalidInput(format!("Cannot evaluate the policy as {kind}"))
+                })?,
+            );
+        }
+        Ok(options)
+    }
+
+    fn query(&self, bytes: BytesFormat, collapse_standard: bool) -> String {
+        let mut query = format!(
+            "?bytes={}&standard={}",
+            bytes.name(),
+            if collapse_standard {
+                "collapsed"
+            } else {
+                "expanded"
+            }
+        );
+        if let Some(subject) = &self.policy_subject {
+            query.push_str(&format!(
+                "&policy_as={}&policy_name={}",
+                subject.kind(),
+                urlencoding::encode(subject.name())
+            ));
+        }
+        query
+    }
+}
+
 pub async fn object_page(
+    State(state): State<AppState>,
     Path((service_name, object_path)): Path<(String, String)>,
-) -> Result<Html<String>> {
+    Query(query): Query<ObjectPageQuery>,
+) -> Result<Response> {
+    let (service_name, object_path) = decode_object_location(&service_name, &object_path)?;
+    let options = ObjectPageOptions::from_query(&service_name, &query)?;
+
+    info!("Serving object page for: {service_name} {object_path}");
+
+    let conn = Connection::new_system().map_err(AppError::DbusConnection)?;
+
+    render_object_page(&conn, &service_name, &object_path, None, &options, &state)
+}
+
+pub async fn object_form(
+    State(state): State<AppState>,
+    Path((service_name, object_path)): Path<(String, String)>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<Response> {
+    let (service_name, object_path) = decode_object_location(&service_name, &object_path)?;
+
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+    };
+    let interface = field("interface")
+        .ok_or_else(|| AppError::InvalidInput("Missing form field interface".to_string()))?;
+    validate_interface_name(&interface)?;
+    let mut inputs: Vec<String> = fields
+        .iter()
+        .filter(|(key, _)| key == "arg")
+        .map(|(_, value)| value.clone())
+        .collect();
+
+    let conn = Connection::new_system().map_err(AppError::DbusConnection)?;
+
+    let object_info = introspect_object(&conn, &service_name, &object_path)
+        .ok_or_else(|| AppError::ObjectNotFound(format!("{service_name}:{object_path}")))?;
+    if object_info.dbus_error.is_some() {
+        // Nothing can be done on the object, so show why
+        return render_object_page(
+            &conn,
+            &service_name,
+            &object_path,
+            None,
+            &ObjectPageOptions::new(&service_name),
+            &state,
+        );
+    }
+    let interface_info = object_info
+        .interfaces
+        .iter()
+        .find(|i| i.name == interface)
+        .ok_or_else(|| AppError::InvalidInput(format!("Unknown interface {interface}")))?;
+
+    let action = if let Some(method) = field("method") {
+        validate_member_name(&method)?;
+        info!("Calling {interface}.{method} on {service_name} {object_path}");
+
+        let method_info = interface_info
+            .methods
+            .iter()
+            .find(|m| m.name == method)
+            .ok_or_else(|| {
+                AppError::InvalidInput(format!("Unknown method {interface}.{method}"))
+            })?;
+
+        ObjectAction::Call(MethodCall::execute(
+            &conn,
+            &service_name,
+            &object_path,
+            &interface,
+            method_info,
+            inputs,
+        ))
+    } else if let Some(property) = field("property") {
+        validate_member_name(&property)?;
+        info!("Setting {interface}.{property} on {service_name} {object_path}");
+
+        let property_info = interface_info
+            .properties
+            .iter()
+            .find(|p| p.name == property)
+            .ok_or_else(|| {
+                AppError::InvalidInput(format!("Unknown property {interface}.{property}"))
+            })?;
+        if inputs.len() != 1 {
+            return Err(AppError::InvalidInput(format!(
+                "Expected one value for {interface}.{property}"
+            )));
+        }
+
+        ObjectAction::SetProperty(PropertyWrite::execute(
+            &conn,
+            &service_name,
+            &object_path,
+            &interface,
+            property_info,
+            inputs.remove(0),
+        ))
+    } else if let Some(signal) = field("signal") {
+        validate_member_name(&signal)?;
+        info!("Emitting {interface}.{signal} at {object_path}");
+
+        let signal_info = interface_info
+            .signals
+            .iter()
+            .find(|s| s.name == signal)
+            .ok_or_else(|| {
+                AppError::InvalidInput(format!("Unknown signal {interface}.{signal}"))
+            })?;
+
+        ObjectAction::EmitSignal(SignalEmission::execute(
+            &conn,
+            &object_path,
+            &interface,
+            signal_info,
+            inputs,
+        ))
+    } else {
+        return Err(AppError::InvalidInput(
+            "Missing form field method, property or signal".to_string(),
+        ));
+    };
+
+    state
+        .recorder
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .record(&service_name, &object_path, &action);
+
+    render_object_page(
+        &conn,
+        &service_name,
+        &object_path,
+        Some(&action),
+        &ObjectPageOptions::new(&service_name),
+        &state,
+    )
+}
+
+fn decode_object_location(service_name: &str, object_path: &str) -> Result<(String, String)> {
     let service_name =
-        urlencoding::decode(&service_name).map_err(|e| AppError::UrlDecode(e.to_string()))?;
+        urlencoding::decode(service_name).map_err(|e| AppError::UrlDecode(e.to_string()))?;
     let object_path =
-        urlencoding::decode(&object_path).map_err(|e| AppError::UrlDecode(e.to_string()))?;
+        urlencoding::decode(object_path).map_err(|e| AppError::UrlDecode(e.to_string()))?;
     let object_path = format!("/{object_path}");
 
     validate_service_name(&service_name)?;
     validate_object_path(&object_path)?;
 
-    info!("Serving object page for: {service_name} {object_path}");
-
-    let conn = Connection::new_system().map_err(AppError::DbusConnection)?;
+    Ok((service_name.into_owned(), object_path))
+}
 
-    let object_info = introspect_object(&conn, &service_name, &object_path)
+fn render_object_page(
+    conn: &Connection,
+    service_name: &str,
+    object_path: &str,
+    action: Option<&ObjectAction>,
+    options: &ObjectPageOptions,
+    state: &AppState,
+) -> Result<Response> {
+    let object_info = introspect_object(conn, service_name, object_path)
         .ok_or_else(|| AppError::ObjectNotFound(format!("{service_name}:{object_path}")))?;
+    // Read after any action so that the page shows its effect
+    let property_values = read_properties(conn, service_name, &object_info);
 
     // Get all service objects to find children
-    let service_info = analyze_service(&conn, &service_name);
-    let child_objects = find_child_objects(&service_info.objects, &object_path);
+    let service_info = analyze_service(conn, service_name);
+    let child_objects = find_child_objects(&service_info.objects, object_path);
 
-    let navigation = build_breadcrumb_navigation(&service_name, &object_path);
-    let object_details = render_object_details(&object_info);
-    let child_links = render_child_object_links(&child_objects, &service_name);
+    let navigation = build_breadcrumb_navigation(service_name, object_path);
+    let page_options = render_page_options(service_name, object_path, options);
+    let object_details = render_object_details(
+        service_name,
+        &object_info,
+        action,
+        &property_values,
+        &options.format,
+        &state.renderers,
+        options.collapse_standard,
+    );
+    let policy_evaluation = render_policy_evaluation(
+        service_name,
+        object_path,
+        &object_info,
+        options,
+        &state.bus_config,
+    );
+    let object_xml = render_object_xml(&object_info);
+    let child_links = render_child_object_links(&child_objects, service_name);
     let type_reference = render_dbus_types_reference();
+    let script = render_argument_editor_script();
 
-    let body = format!("{navigation}{object_details}{child_links}{type_reference}");
+    let body =
+        format!("{navigation}{page_options}{object_details}{policy_evaluation}{object_xml}{child_links}{type_reference}{script}");
     let title = format!("{service_name} {object_path}");
 
     let page = PageTemplate::new(&title, body);
-    Ok(Html(page.render()))
+    let response = Html(page.render()).into_response();
+    Ok(match &object_info.dbus_error {
+        Some(dbus_error) => AppError::from(dbus_error.clone()).attach_json(response),
+        None => response,
+    })
+}
+
+fn render_page_options(
+    service_name: &str,
+    object_path: &str,
+    options: &ObjectPageOptions,
+) -> String {
+    let url = object_page_url(service_name, object_path);
+    let link = |label: &str, selected: bool, query: String| {
+        if selected {
+            format!("<strong>{label}</strong>")
+        } else {
+            format!(r#"<a href="{url}{query}">{label}</a>"#)
+        }
+    };
+
+    let bytes: Vec<String> = BytesFormat::ALL
+        .iter()
+        .map(|bytes| {
+            link(
+                bytes.name(),
+                *bytes == options.format.bytes,
+                options.query(*bytes, options.collapse_standard),
+            )
+        })
+        .collect();
+    let standard: Vec<String> = [("collapsed", true), ("expanded", false)]
+        .iter()
+        .map(|(label, collapse)| {
+            link(
+                label,
+                *collapse == options.collapse_standard,
+                options.query(options.format.bytes, *collapse),
+            )
+        })
+        .collect();
+
+    format!(
+        r#"<div class="page-options">Byte arrays: {} &middot; Standard interfaces: {}</div>"#,
+        bytes.join(" | "),
+        standard.join(" | ")
+    )
 }
 
-pub async fn all_services_page() -> Result<Html<String>> {
+/// A form to pick whose access to evaluate and, once picked, the verdict for every member.
+fn render_policy_evaluation(
+    service_name: &str,
+    object_path: &str,
+    object_info: &ObjectInfo,
+    options: &ObjectPageOptions,
+    bus_config: &FilePath,
+) -> String {
+    let subject = options.policy_subject.as_ref();
+    let kind_option = |kind: &str, label: &str| {
+        format!(
+            r#"<option value="{kind}"{}>{label}</option>"#,
+            if subject.map(PolicySubject::kind) == Some(kind) {
+                " selected"
+            } else {
+                ""
+            }
+        )
+    };
+    let mut html = format!(
+        r#"<h2>Bus policy</h2><form method="get" action="{}" class="call-form"><input type="hidden" name="bytes" value="{}"><input type="hidden" name="standard" value="{}">Evaluate access for <select name="policy_as">{}{}{}</select> <input type="text" name="policy_name" placeholder="name or ID" value="{}"><button type="submit">Evaluate</button></form>"#,
+        object_page_url(service_name, object_path),
+        options.format.bytes.name(),
+        if options.collapse_standard {
+            "collapsed"
+        } else {
+            "expanded"
+        },
+        kind_option("self", "this application"),
+        kind_option("user", "user"),
+        kind_option("group", "group"),
+        html_escape(subject.map(PolicySubject::name).unwrap_or_default())
+    );
+
+    let Some(subject) = subject else {
+        return html;
+    };
+    let Some(identity) = subject.identity() else {
+        html.push_str(&format!(
+            r#"<div class="error">No {} named {}</div>"#,
+            subject.kind(),
+            html_escape(subject.name())
+        ));
+        return html;
+    };
+    let policy = BusPolicy::load(bus_config);
+    for error in &policy.errors {
+        html.push_str(&format!(
+            r#"<div class="error">{}</div>"#,
+            html_escape(error)
+        ));
+    }
+
+    html.push_str(&format!(
+        "<p>Evaluated for {} against {} rule(s). Properties are read with <code>org.freedesktop.DBus.Properties.Get</code> and written with <code>Set</code>, so the policy decides for all properties alike.</p>",
+        html_escape(&identity.to_string()),
+        policy.rules.len()
+    ));
+    html.push_str("<table><tr><th>Interface</th><th>Member</th><th>Access</th><th>Verdict</th><th>Rule</th></tr>");
+    for interface in &object_info.interfaces {
+        let mut checks: Vec<(&str, &str, &str, &str)> = interface
+            .methods
+            .iter()
+            .map(|method| {
+                (
+                    method.name.as_str(),
+                    "call",
+                    interface.name.as_str(),
+                    method.name.as_str(),
+                )
+            })
+            .collect();
+        for property in &interface.properties {
+            if property.access.contains("read") {
+                checks.push((
+                    &property.name,
+                    "read",
+                    "org.freedesktop.DBus.Properties",
+                    "Get",
+                ));
+            }
+            if property.access.contains("write") {
+                checks.push((
+                    &property.name,
+                    "write",
+                    "org.freedesktop.DBus.Properties",
+                    "Set",
+                ));
+            }
+        }
+
+        for (member, access, call_interface, call_member) in checks {
+            let call = MethodCallTarget {
+                destination: service_name.to_string(),
+                path: object_path.to_string(),
+                interface: call_interface.to_string(),
+                member: call_member.to_string(),
+            };
+            let verdict = policy.check_send(&identity, &call);
+            let rule = match verdict.rule {
+                Some(rule) => format!(
+                    r#"<a href="/local/dbus_explorer/app/policy/{}#rule-{}">{}</a> <code>{}</code>"#,
+                    urlencoding::encode(service_name),
+                    rule.id,
+                    html_escape(&rule_source(rule)),
+                    html_escape(&rule.context.to_string())
+                ),
+                None => "no rule matches".to_string(),
+            };
+            html.push_str(&format!(
+                r#"<tr><td>{}</td><td>{}</td><td>{access}</td><td><span class="status {}">{}</span></td><td>{rule}</td></tr>"#,
+                html_escape(&interface.name),
+                html_escape(member),
+                if verdict.allowed {
+                    "status-responsive"
+                } else {
+                    "status-erroring"
+                },
+                if verdict.allowed { "allow" } else { "deny" }
+            ));
+        }
+    }
+    html.push_str("</table>");
+
+    html
+}
+
+pub async fn all_services_page(State(state): State<AppState>) -> Result<Html<String>> {
     info!("Serving all services page");
 
     let conn = Connection::new_system().map_err(AppError::DbusConnection)?;
 
-    let services = discover_services(&conn, None)
+    let mut services = discover_services(&conn, None)
         .map_err(|e| AppError::ServiceIntrospection(e.to_string()))?;
+    for service in &mut services {
+        resolve_unit(&conn, service);
+    }
 
     let navigation = r#"<div class="navigation"><a href="/local/dbus_explorer/app">Home</a> / All Services</div>"#;
 
-    let content = render_all_services_content(&services);
-    let body = format!("{navigation}{content}");
+    let content = render_all_services_content(&services, &state.renderers);
+    let script = render_argument_editor_script();
+    let body = format!("{navigation}{content}{script}");
 
     let page = PageTemplate::new("All Services and Objects", body);
     Ok(Html(page.render()))
 }
 
-fn render_service_content(service_info: &ServiceInfo, service_name: &str) -> String {
+pub async fn interfaces_page() -> Result<Html<String>> {
+    info!("Serving interfaces page");
+
+    let conn = Connection::new_system().map_err(AppError::DbusConnection)?;
+
+    let services = discover_services(&conn, None)
+        .map_err(|e| AppError::ServiceIntrospection(e.to_string()))?;
+    let catalog = build_interface_catalog(&services);
+
+    let navigation =
+        r#"<div class="navigation"><a href="/local/dbus_explorer/app">Home</a> / Interfaces</div>"#;
+
+    let content = render_interface_catalog(&catalog);
+    let body = format!("{navigation}{content}");
+
+    let page = PageTemplate::new("Interfaces", body);
+    Ok(Html(page.render()))
+}
+
+pub async fn lint_page() -> Result<Html<String>> {
+    info!("Serving lint page");
+
+    let conn = Connection::new_system().map_err(AppError::DbusConnection)?;
+
+    let services = discover_services(&conn, None)
+        .map_err(|e| AppError::ServiceIntrospection(e.to_string()))?;
+    let reports: Vec<(String, Vec<Lint>)> = services
+        .iter()
+        .map(|service| (service.name.clone(), lint_service(service)))
+        .collect();
+
+    let navigation =
+        r#"<div class="navigation"><a href="/local/dbus_explorer/app">Home</a> / Lint</div>"#;
+
+    let content = render_lint_summary(&reports);
+    let body = format!("{navigation}{content}");
+
+    let page = PageTemplate::new("Lint", body);
+    Ok(Html(page.render()))
+}
+
+pub async fn service_lint_page(Path(service_name): Path<String>) -> Result<Html<String>> {
+    let service_name =
+        urlencoding::decode(&service_name).map_err(|e| AppError::UrlDecode(e.to_string()))?;
+
+    validate_service_name(&service_name)?;
+    info!("Serving lint page for: {service_name}");
+
+    let conn = Connection::new_system().map_err(AppError::DbusConnection)?;
+
+    let service_info = analyze_service(&conn, &service_name);
+    if service_info.objects.is_empty() && service_info.error.is_some() {
+        return Err(AppError::ServiceNotFound(service_name.to_string()));
+    }
+    let lints = lint_service(&service_info);
+
+    let navigation = format!(
+        r#"<div class="navigation"><a href="/local/dbus_explorer/app">Home</a> / <a href="/local/dbus_explorer/app/lint">Lint</a> / <a href="/local/dbus_explorer/app/service/{}">{}</a></div>"#,
+        urlencoding::encode(&service_name),
+        html_escape(&service_name)
+    );
+
+    let content = render_service_lints(&lints);
+    let body = format!("{navigation}{content}");
+
+    let page = PageTemplate::new(&format!("Lint {service_name}"), body);
+    Ok(Html(page.render()))
+}
+
+pub async fn policy_page(
+    State(state): State<AppState>,
+    Path(service_name): Path<String>,
+) -> Result<Html<String>> {
+    let service_name =
+        urlencoding::decode(&service_name).map_err(|e| AppError::UrlDecode(e.to_string()))?;
+
+    validate_service_name(&service_name)?;
+    info!("Serving bus policy page for: {service_name}");
+
+    let policy = BusPolicy::load(&state.bus_config);
+
+    let navigation = format!(
+        r#"<div class="navigation"><a href="/local/dbus_explorer/app">Home</a> / <a href="/local/dbus_explorer/app/service/{}">{}</a> / Bus policy</div>"#,
+        urlencoding::encode(&service_name),
+        html_escape(&service_name)
+    );
+
+    let content = render_service_policy(&policy, &service_name);
+    let body = format!("{navigation}{content}");
+
+    let page = PageTemplate::new(&format!("Bus policy {service_name}"), body);
+    Ok(Html(page.render()))
+}
+
+pub async fn stats_page() -> Result<Html<String>> {
+    info!("Serving bus statistics page");
+
+    let conn = Connection::new_system().map_err(AppError::DbusConnection)?;
+
+    let navigation = r#"<div class="navigation"><a href="/local/dbus_explorer/app">Home</a> / Bus statistics</div>"#;
+    let content = match read_bus_stats(&conn) {
+        Ok(stats) => render_bus_stats(&stats),
+        Err(e) => format!(
+            r#"<div class="error">Statistics are not available: {}</div><p>The bus daemon must be built with statistics and its policy must allow calls to <code>org.freedesktop.DBus.Debug.Stats</code>.</p>"#,
+            html_escape(&format!("{e:#}"))
+        ),
+    };
+    let body = format!("{navigation}{content}");
+
+    let page = PageTemplate::new("Bus statistics", body);
+    Ok(Html(page.render()))
+}
+
+pub async fn metrics_page(State(state): State<AppState>) -> Result<impl IntoResponse> {
+    info!("Serving metrics");
+
+    // Gather the bus metrics first so that the calls they make are counted in this scrape
+    let bus_metrics = state
+        .bus_metrics
+        .get()
+        .await
+        .map_err(|e| AppError::ServiceIntrospection(format!("{e:#}")))?;
+    let body = format!("{}{bus_metrics}", METRICS.render());
+
+    Ok((
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        body,
+    ))
+}
+
+pub async fn recording_page(State(state): State<AppState>) -> Result<Html<String>> {
+    info!("Serving recording page");
+
+    Ok(render_recording_page(&state))
+}
+
+pub async fn recording_form(
+    State(state): State<AppState>,
+    Form(form): Form<RecordingForm>,
+) -> Result<Html<String>> {
+    {
+        let mut recorder = state.recorder.lock().unwrap_or_else(|e| e.into_inner());
+        match form.action.as_str() {
+            "pause" => recorder.recording = false,
+            "resume" => recorder.recording = true,
+            "clear" => recorder.clear(),
+            other => {
+                return Err(AppError::InvalidInput(format!(
+                    "Unknown recording action {other}"
+                )))
+            }
+        }
+        info!("Recording {}", form.action);
+    }
+
+    Ok(render_recording_page(&state))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecordingForm {
+    /// "pause", "resume" or "clear".
+    action: String,
+}
+
+pub async fn recording_export(State(state): State<AppState>) -> Result<impl IntoResponse> {
+    info!("Exporting recording");
+
+    let recording = state
+        .recorder
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .recording();
+    let body = serde_json::to_string_pretty(&recording)
+        .map_err(|e| AppError::InvalidInput(e.to_string()))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"recording.json\"",
+            ),
+        ],
+        body,
+    ))
+}
+
+fn render_recording_page(state: &AppState) -> Html<String> {
+    let recorder = state.recorder.lock().unwrap_or_else(|e| e.into_inner());
+
+    let navigation =
+        r#"<div class="navigation"><a href="/local/dbus_explorer/app">Home</a> / Recording</div>"#;
+    let button = |action: &str, label: &str| {
+        format!(
+            r#"<form method="post" style="display: inline"><input type="hidden" name="action" value="{action}"><button type="submit">{label}</button></form> "#
+        )
+    };
+    let mut content = format!(
+        r#"<p>{} {}{}<a href="/local/dbus_explorer/app/recording/export">Export for replay</a></p><p>Replay an export with <code>dbus_explorer replay recording.json</code>. Only the last {} steps are kept.</p>"#,
+        if recorder.recording {
+            "Recording method calls and property writes."
+        } else {
+            "Recording is paused."
+        },
+        if recorder.recording {
+            button("pause", "Pause")
+        } else {
+            button("resume", "Resume")
+        },
+        button("clear", "Clear"),
+        recorder.capacity()
+    );
+    content.push_str(&render_recorded_steps(recorder.steps()));
+    let body = format!("{navigation}{content}");
+
+    let page = PageTemplate::new("Recording", body);
+    Html(page.render())
+}
+
+fn render_recorded_steps(steps: &VecDeque<RecordedStep>) -> String {
+    if steps.is_empty() {
+        return "<p>Nothing recorded yet.</p>".to_string();
+    }
+
+    let mut html = String::from("<table><tr><th>#</th><th>Object</th><th>Step</th><th>Inputs</th><th>Outcome</th><th>Time</th></tr>");
+    for (i, step) in steps.iter().enumerate() {
+        let outcome = match &step.error {
+            Some(error) => format!(
+                r#"<span class="status status-erroring">{}</span> {}"#,
+                html_escape(error.name.as_deref().unwrap_or("error")),
+                html_escape(&error.message)
+            ),
+            None => format!(
+                "<code>{}</code>",
+                html_escape(&serde_json::Value::Array(step.reply.clone()).to_string())
+            ),
+        };
+        html.push_str(&format!(
+            r#"<tr><td>{}</td><td><a href="{}">{} {}</a></td><td>{} <code>{}.{}</code></td><td><code>{}</code></td><td>{outcome}</td><td>{:.1} ms</td></tr>"#,
+            i + 1,
+            object_page_url(&step.service, &step.path),
+            html_escape(&step.service),
+            html_escape(&step.path),
+            match step.kind {
+                StepKind::Call => "call",
+                StepKind::SetProperty => "set",
+            },
+            html_escape(&step.interface),
+            html_escape(&step.member),
+            html_escape(&step.inputs.join(" ")),
+            step.duration_ms
+        ));
+    }
+    html.push_str("</table>");
+
+    html
+}
+
+/// Seconds to listen for with a match rule when none are given, and at most.
+const DEFAULT_LISTEN_SECONDS: u64 = 5;
+const MAX_LISTEN_SECONDS: u64 = 30;
+/// Listening stops early once this many messages have matched.
+const MAX_CAPTURED_MESSAGES: usize = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct ListenQuery {
+    /// Present when the rule should be run rather than only checked.
+    listen: Option<String>,
+    seconds: Option<String>,
+}
+
+pub async fn match_rule_page(
+    Query(spec): Query<MatchRuleSpec>,
+    Query(listen): Query<ListenQuery>,
+) -> Result<Html<String>> {
+    info!("Serving match rule page");
+
+    let seconds = match listen.seconds.as_deref().map(str::trim) {
+        None | Some("") => DEFAULT_LISTEN_SECONDS,
+        Some(text) => text
+            .parse::<u64>()
+            .ok()
+            .filter(|s| (1..=MAX_LISTEN_SECONDS).contains(s))
+            .ok_or_else(|| {
+                AppError::InvalidInput(format!(
+                    "Seconds must be a whole number from 1 to {MAX_LISTEN_SECONDS}"
+                ))
+            })?,
+    };
+
+    let navigation = r#"<div class="navigation"><a href="/local/dbus_explorer/app">Home</a> / Match rules</div>"#;
+    let mut content = render_match_rule_form(&spec, seconds);
+
+    let submitted = listen.listen.is_some() || listen.seconds.is_some();
+    if submitted {
+        match spec.conditions() {
+            Err(errors) => content.push_str(&format!(
+                r#"<div class="error"><strong>Invalid match rule:</strong><ul>{}</ul></div>"#,
+                errors
+                    .iter()
+                    .map(|e| format!("<li>{}</li>", html_escape(e)))
+                    .collect::<String>()
+            )),
+            Ok(conditions) => {
+                let rule = rule_text(&conditions);
+                content.push_str(&format!(
+                    r#"<h2>Rule</h2><p><code>{}</code></p><pre class="command-lines">{}</pre>"#,
+                    if rule.is_empty() {
+                        "<em>empty, matches every message</em>".to_string()
+                    } else {
+                        html_escape(&rule)
+                    },
+                    html_escape(&monitor_command_lines(&rule).join("\n"))
+                ));
+                if listen.listen.is_some() {
+                    // Listening blocks for up to the whole duration, keep it off the async workers
+                    let listened = {
+                        let rule = rule.clone();
+                        request_log::spawn_blocking(move || {
+                            capture(&rule, Duration::from_secs(seconds), MAX_CAPTURED_MESSAGES)
+                        })
+                        .await
+                        .map_err(|e| AppError::Internal(format!("Listening failed: {e}")))?
+                    };
+                    content.push_str(&render_capture(listened, seconds));
+                }
+            }
+        }
+    }
+    let body = format!("{navigation}{content}");
+
+    let page = PageTemplate::new("Match rules", body);
+    Ok(Html(page.render()))
+}
+
+fn render_match_rule_form(spec: &MatchRuleSpec, seconds: u64) -> String {
+    let types: String = ["", "signal", "method_call", "method_return", "error"]
+        .iter()
+        .map(|t| {
+            format!(
+                r#"<option value="{t}"{}>{}</option>"#,
+                if spec.message_type == *t {
+                    " selected"
+                } else {
+                    ""
+                },
+                if t.is_empty() { "any" } else { t }
+            )
+        })
+        .collect();
+    let field = |name: &str, value: &str, placeholder: &str| {
+        format!(
+            r#"<label>{name} <input type="text" name="{name}" value="{}" placeholder="{placeholder}"></label><br>"#,
+            html_escape(value)
+        )
+    };
+
+    format!(
+        r#"<p>Compose a match rule, check it, and listen to the messages it matches. Empty fields are left out of the rule.</p><form method="get" class="call-form"><label>type <select name="type">{types}</select></label><br>{}{}{}{}{}{}{}<label>arguments <textarea name="args" rows="3" cols="40" placeholder="arg0=com.example.Name&#10;arg1path=/com/example/">{}</textarea></label><br><label>seconds <input type="number" name="seconds" min="1" max="{MAX_LISTEN_SECONDS}" value="{seconds}"></label><button type="submit">Check</button><button type="submit" name="listen" value="1">Listen</button></form>"#,
+        field("sender", &spec.sender, "org.freedesktop.DBus"),
+        field("destination", &spec.destination, ":1.42"),
+        field(
+            "interface",
+            &spec.interface,
+            "org.freedesktop.DBus.Properties"
+        ),
+        field("member", &spec.member, "PropertiesChanged"),
+        field("path", &spec.path, "/org/freedesktop/DBus"),
+        field("path_namespace", &spec.path_namespace, "/org/freedesktop"),
+        field("arg0namespace", &spec.arg0namespace, "com.example"),
+        html_escape(&spec.args)
+    )
+}
+
+fn render_capture(capture: anyhow::Result<Capture>, seconds: u64) -> String {
+    let capture = match capture {
+        Ok(capture) => capture,
+        Err(e) => {
+            return format!(
+                r#"<div class="error"><strong>Could not listen:</strong> {}</div>"#,
+                html_escape(&format!("{e:#}"))
+            )
+        }
+    };
+
+    let mut html = format!(
+        "<h2>Matching messages</h2><p>{} {}</p>",
+        if capture.truncated {
+            format!(
+                "Stopped after the first {} matching messages.",
+                capture.messages.len()
+            )
+        } else {
+            format!(
+                "{} matching message(s) in {seconds} s.",
+                capture.messages.len()
+            )
+        },
+        if capture.monitor {
+            "Listened as a monitor, which sees all traffic."
+        } else {
+            "The bus did not allow the explorer to become a monitor, so only signals were seen; method calls and replies between other connections are not delivered to ordinary matches."
+        }
+    );
+    if capture.messages.is_empty() {
+        return html;
+    }
+
+    html.push_str("<table><tr><th>Time</th><th>Type</th><th>Sender</th><th>Destination</th><th>Path</th><th>Member</th><th>Serial</th><th>Arguments</th></tr>");
+    for message in &capture.messages {
+        let member = match (&message.error_name, &message.interface, &message.member) {
+            (Some(error_name), _, _) => error_name.clone(),
+            (None, Some(interface), Some(member)) => format!("{interface}.{member}"),
+            (None, None, Some(member)) => member.clone(),
+            _ => String::new(),
+        };
+        let serial = match (message.serial, message.reply_serial) {
+            (Some(serial), Some(reply_serial)) => format!("{serial} (reply to {reply_serial})"),
+            (Some(serial), None) => serial.to_string(),
+            (None, Some(reply_serial)) => format!("reply to {reply_serial}"),
+            (None, None) => String::new(),
+        };
+        html.push_str(&format!(
+            r#"<tr><td>+{:.1} ms</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td><code>{}</code></td></tr>"#,
+            message.elapsed.as_secs_f64() * 1000.0,
+            message.message_type,
+            html_escape(message.sender.as_deref().unwrap_or_default()),
+            html_escape(message.destination.as_deref().unwrap_or_default()),
+            html_escape(message.path.as_deref().unwrap_or_default()),
+            html_escape(&member),
+            serial,
+            html_escape(&message.arguments.join(", "))
+        ));
+    }
+    html.push_str("</table>");
+    html
+}
+
+pub async fn health_page(State(state): State<AppState>) -> Result<Html<String>> {
+    info!("Serving health page");
+
+    let conn = Connection::new_system().map_err(AppError::DbusConnection)?;
+
+    let service_names =
+        get_service_names_only(&conn).map_err(|e| AppError::ServiceIntrospection(e.to_string()))?;
+    // Probes block for up to their timeout, keep them off the async workers
+    let probes = {
+        let service_names = service_names.clone();
+        request_log::spawn_blocking(move || probe_services(&service_names))
+            .await
+            .map_err(|e| AppError::Internal(format!("Probing failed: {e}")))?
+    };
+
+    let mut history = state.health.lock().unwrap_or_else(|e| e.into_inner());
+    history.retain(&service_names);
+    for (service_name, probe) in probes {
+        history.record(&service_name, probe);
+    }
+
+    let navigation =
+        r#"<div class="navigation"><a href="/local/dbus_explorer/app">Home</a> / Health</div>"#;
+
+    let content = render_health_content(&history);
+    let body = format!("{navigation}{content}");
+
+    let page = PageTemplate::new("Service Health", body);
+    Ok(Html(page.render()))
+}
+
+fn render_service_content(
+    service_info: &ServiceInfo,
+    service_name: &str,
+    bus_config: &FilePath,
+) -> String {
     let mut html = String::new();
 
     // Show service owner information if available
@@ -123,6 +1053,15 @@ fn render_service_content(service_info: &ServiceInfo, service_name: &str) -> Str
         ));
     }
 
+    if let Some(pid) = service_info.pid {
+        html.push_str(&format!(
+            r#"<div class="service-info"><strong>Process:</strong> {pid}</div>"#
+        ));
+    }
+    if let Some(unit) = &service_info.unit {
+        html.push_str(&render_unit(unit));
+    }
+
     if let Some(error) = &service_info.error {
         html.push_str(&format!(
             r#"<div class="error"><strong>Error:</strong> {}</div>"#,
@@ -131,6 +1070,11 @@ fn render_service_content(service_info: &ServiceInfo, service_name: &str) -> Str
         return html;
     }
 
+    html.push_str(&format!(
+        r#"<p><a href="/local/dbus_explorer/app/lint/{0}">Lint report</a> | <a href="/local/dbus_explorer/app/policy/{0}">Bus policy</a></p>"#,
+        urlencoding::encode(service_name)
+    ));
+
     html.push_str("<h2>Objects</h2>");
     html.push_str(&build_object_flat_list(&service_info.objects, service_name));
 
@@ -142,12 +1086,19 @@ fn render_service_content(service_info: &ServiceInfo, service_name: &str) -> Str
         .collect();
 
     if !error_objects.is_empty() {
+        // The policy only explains denied access, so do not read it for other errors
+        let policy = error_objects
+            .iter()
+            .any(|object| is_access_denied(object))
+            .then(|| BusPolicy::load(bus_config));
+        let identity = Identity::current();
         html.push_str("<h2>Objects with Errors</h2><ul>");
         for object in error_objects {
             html.push_str(&format!(
-                "<li><strong>{}</strong>: {}</li>",
+                "<li><strong>{}</strong>: {}{}</li>",
                 html_escape(&object.path),
-                html_escape(object.error.as_ref().unwrap())
+                html_escape(object.error.as_ref().unwrap()),
+                render_denying_rule(service_name, object, policy.as_ref(), identity.as_ref())
             ));
         }
         html.push_str("</ul>");
@@ -156,11 +1107,70 @@ fn render_service_content(service_info: &ServiceInfo, service_name: &str) -> Str
     html
 }
 
-fn render_all_services_content(services: &[ServiceInfo]) -> String {
+/// Point an access denied introspection at the policy rule that denied it.
+fn render_denying_rule(
+    service_name: &str,
+    object: &ObjectInfo,
+    policy: Option<&BusPolicy>,
+    identity: Option<&Identity>,
+) -> String {
+    let (Some(policy), Some(identity)) = (policy, identity) else {
+        return String::new();
+    };
+    if !is_access_denied(object) {
+        return String::new();
+    }
+
+    let call = MethodCallTarget {
+        destination: service_name.to_string(),
+        path: object.path.clone(),
+        interface: "org.freedesktop.DBus.Introspectable".to_string(),
+        member: "Introspect".to_string(),
+    };
+    let verdict = policy.check_send(identity, &call);
+    match verdict.rule {
+        _ if verdict.allowed => {
+            " (the bus policy allows the call, so the service itself refused it)".to_string()
+        }
+        Some(rule) => format!(
+            r#" (denied by <a href="/local/dbus_explorer/app/policy/{}#rule-{}">{}</a>)"#,
+            urlencoding::encode(service_name),
+            rule.id,
+            html_escape(&rule_source(rule))
+        ),
+        None => format!(
+            r#" (no rule of the <a href="/local/dbus_explorer/app/policy/{}">bus policy</a> allows it)"#,
+            urlencoding::encode(service_name)
+        ),
+    }
+}
+
+fn is_access_denied(object: &ObjectInfo) -> bool {
+    object
+        .dbus_error
+        .as_ref()
+        .is_some_and(|e| e.kind() == DbusErrorKind::AccessDenied)
+}
+
+fn render_unit(unit: &SystemdUnit) -> String {
+    format!(
+        r#"<div class="service-info"><strong>Systemd unit:</strong> <a href="{}">{}</a> <span class="status {}">{}</span> {}</div>"#,
+        object_page_url(SYSTEMD_SERVICE, &unit.object_path),
+        html_escape(&unit.name),
+        active_state_class(&unit.active_state),
+        html_escape(&unit.active_state),
+        html_escape(&unit.sub_state)
+    )
+}
+
+fn render_all_services_content(services: &[ServiceInfo], renderers: &RendererRegistry) -> String {
     let mut html = String::new();
 
     for service in services {
         html.push_str(&format!("<h2>Service: {}</h2>", html_escape(&service.name)));
+        if let Some(unit) = &service.unit {
+            html.push_str(&render_unit(unit));
+        }
 
         if let Some(error) = &service.error {
             html.push_str(&format!(
@@ -170,7 +1180,15 @@ fn render_all_services_content(services: &[ServiceInfo]) -> String {
         } else {
             for object in &service.objects {
                 html.push_str(&format!("<h3>Object: {}</h3>", html_escape(&object.path)));
-                html.push_str(&render_object_details(object));
+                html.push_str(&render_object_details(
+                    &service.name,
+                    object,
+                    None,
+                    &PropertyValues::new(),
+                    &ValueFormat::for_service(&service.name),
+                    renderers,
+                    true,
+                ));
             }
         }
     }
@@ -178,6 +1196,462 @@ fn render_all_services_content(services: &[ServiceInfo]) -> String {
     html
 }
 
+fn render_bus_stats(stats: &BusStats) -> String {
+    let service_link = |name: &str| {
+        format!(
+            r#"<a href="/local/dbus_explorer/app/service/{}">{}</a>"#,
+            urlencoding::encode(name),
+            html_escape(name)
+        )
+    };
+
+    let mut html = String::from("<h2>Bus</h2><table><tr><th>Statistic</th><th>Value</th></tr>");
+    for (key, value) in &stats.bus {
+        let value = bus_stats::number(value)
+            .map(|n| n.to_string())
+            .unwrap_or_else(|| html_escape(&format!("{value:?}")));
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{value}</td></tr>",
+            html_escape(key)
+        ));
+    }
+    html.push_str("</table>");
+
+    html.push_str(&format!(
+        "<h2>Connections</h2><p>{} connection(s), most match rules first.</p>",
+        stats.connections.len()
+    ));
+    html.push_str("<table><tr><th>Connection</th><th>Names</th><th>Queued messages in / out</th><th>Queued bytes in / out</th><th>Peak bytes in / out</th><th>Match rules (peak)</th><th>Names (peak)</th></tr>");
+    for connection in &stats.connections {
+        let names: Vec<String> = connection.names.iter().map(|n| service_link(n)).collect();
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td>",
+            service_link(&connection.unique_name),
+            names.join("<br>")
+        ));
+        match &connection.stats {
+            Ok(_) => {
+                let n = |key| connection.number(key);
+                html.push_str(&format!(
+                    "<td>{} / {}</td><td>{} / {}</td><td>{} / {}</td><td>{} ({})</td><td>{} ({})</td></tr>",
+                    n("IncomingMessages"),
+                    n("OutgoingMessages"),
+                    n("IncomingBytes"),
+                    n("OutgoingBytes"),
+                    n("PeakIncomingBytes"),
+                    n("PeakOutgoingBytes"),
+                    n("MatchRules"),
+                    n("PeakMatchRules"),
+                    n("BusNames"),
+                    n("PeakBusNames"),
+                ));
+            }
+            Err(e) => html.push_str(&format!(
+                r#"<td colspan="5" class="error">{}</td></tr>"#,
+                html_escape(e)
+            )),
+        }
+    }
+    html.push_str("</table>");
+
+    html.push_str("<h2>Match rules</h2>");
+    if stats.connections.iter().all(|c| c.match_rules.is_empty()) {
+        html.push_str("<p>No connection has match rules.</p>");
+    }
+    for connection in stats
+        .connections
+        .iter()
+        .filter(|c| !c.match_rules.is_empty())
+    {
+        let rules: String = connection
+            .match_rules
+            .iter()
+            .map(|rule| format!("<li><code>{}</code></li>", html_escape(rule)))
+            .collect();
+        let names = if connection.names.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", connection.names.join(", "))
+        };
+        html.push_str(&format!(
+            "<details><summary>{}{}: {} rule(s)</summary><ul>{rules}</ul></details>",
+            html_escape(&connection.unique_name),
+            html_escape(&names),
+            connection.match_rules.len()
+        ));
+    }
+
+    html
+}
+
+fn render_lint_summary(reports: &[(String, Vec<Lint>)]) -> String {
+    let count = |lints: &[Lint], rule: LintRule| lints.iter().filter(|l| l.rule == rule).count();
+    let all_lints: Vec<Lint> = reports.iter().flat_map(|(_, l)| l.clone()).collect();
+
+    let mut html = String::from("<h2>Guidelines</h2><table><tr><th>Rule</th><th>Severity</th><th>Guideline</th><th>Findings</th></tr>");
+    for rule in LintRule::ALL {
+        html.push_str(&format!(
+            "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            rule.name(),
+            severity_label(rule.severity()),
+            html_escape(rule.guideline()),
+            count(&all_lints, rule)
+        ));
+    }
+    html.push_str("</table>");
+
+    html.push_str("<h2>Services</h2><table><tr><th>Service</th>");
+    for rule in LintRule::ALL {
+        html.push_str(&format!("<th><code>{}</code></th>", rule.name()));
+    }
+    html.push_str("<th>Total</th></tr>");
+    for (service_name, lints) in reports {
+        html.push_str(&format!(
+            r#"<tr><td><a href="/local/dbus_explorer/app/lint/{}">{}</a></td>"#,
+            urlencoding::encode(service_name),
+            html_escape(service_name)
+        ));
+        for rule in LintRule::ALL {
+            html.push_str(&format!("<td>{}</td>", count(lints, rule)));
+        }
+        html.push_str(&format!("<td>{}</td></tr>", lints.len()));
+    }
+    html.push_str("</table>");
+
+    html
+}
+
+fn render_service_lints(lints: &[Lint]) -> String {
+    if lints.is_empty() {
+        return "<p>No findings, every interface follows the guidelines.</p>".to_string();
+    }
+
+    let mut html = format!("<p>{} finding(s)</p>", lints.len());
+    let mut interface = None;
+    for lint in lints {
+        if interface != Some(&lint.interface) {
+            if interface.is_some() {
+                html.push_str("</table>");
+            }
+            interface = Some(&lint.interface);
+            html.push_str(&format!(
+                "<h3>{}</h3><table><tr><th>Severity</th><th>Rule</th><th>Finding</th></tr>",
+                html_escape(&lint.interface)
+            ));
+        }
+        html.push_str(&format!(
+            r#"<tr><td>{}</td><td><code title="{}">{}</code></td><td>{}</td></tr>"#,
+            severity_label(lint.rule.severity()),
+            html_escape(lint.rule.guideline()),
+            lint.rule.name(),
+            html_escape(&lint.message)
+        ));
+    }
+    html.push_str("</table>");
+
+    html
+}
+
+fn render_service_policy(policy: &BusPolicy, service_name: &str) -> String {
+    let mut html = String::from(
+        "<p>Rules are applied by context: default, groups, users, console and mandatory. Within a context the last matching rule wins.</p>",
+    );
+    for error in &policy.errors {
+        html.push_str(&format!(
+            r#"<div class="error">{}</div>"#,
+            html_escape(error)
+        ));
+    }
+
+    let own: Vec<&PolicyRule> = policy
+        .rules
+        .iter()
+        .filter(|rule| rule.matches_own(service_name))
+        .collect();
+    html.push_str("<h2>Own</h2>");
+    html.push_str(&render_policy_rules(
+        &own,
+        "No rule lets anyone own this name.",
+    ));
+
+    let mut send: Vec<&PolicyRule> = policy
+        .rules
+        .iter()
+        .filter(|rule| rule.names_destination(service_name))
+        .collect();
+    send.sort_by_key(|rule| {
+        (
+            rule.attribute("send_interface").unwrap_or_default(),
+            rule.attribute("send_member").unwrap_or_default(),
+            rule.id,
+        )
+    });
+    html.push_str("<h2>Send</h2>");
+    let mut group = None;
+    for rule in &send {
+        let key = (
+            rule.attribute("send_interface").unwrap_or("any interface"),
+            rule.attribute("send_member").unwrap_or("any member"),
+        );
+        if group != Some(key) {
+            group = Some(key);
+            html.push_str(&format!(
+                "<h3>{} {}</h3>",
+                html_escape(key.0),
+                html_escape(key.1)
+            ));
+            let rules: Vec<&PolicyRule> = send
+                .iter()
+                .filter(|other| {
+                    other.attribute("send_interface").unwrap_or("any interface") == key.0
+                        && other.attribute("send_member").unwrap_or("any member") == key.1
+                })
+                .copied()
+                .collect();
+            html.push_str(&render_policy_rules(&rules, ""));
+        }
+    }
+    if send.is_empty() {
+        html.push_str("<p>No rule names this service as destination.</p>");
+    }
+
+    let receive: Vec<&PolicyRule> = policy
+        .rules
+        .iter()
+        .filter(|rule| rule.names_sender(service_name))
+        .collect();
+    html.push_str("<h2>Receive</h2>");
+    html.push_str(&render_policy_rules(
+        &receive,
+        "No rule names this service as sender.",
+    ));
+
+    let general: Vec<&PolicyRule> = policy
+        .rules
+        .iter()
+        .filter(|rule| {
+            rule.kind() == RuleKind::Send
+                && rule.attribute("send_destination").is_none()
+                && rule.attribute("send_destination_prefix").is_none()
+        })
+        .collect();
+    html.push_str("<h2>Send rules for every destination</h2>");
+    html.push_str(&render_policy_rules(&general, "None."));
+
+    html.push_str("<h2>Files</h2><ul>");
+    for file in &policy.files {
+        html.push_str(&format!(
+            "<li><code>{}</code></li>",
+            html_escape(&file.display().to_string())
+        ));
+    }
+    html.push_str("</ul>");
+
+    html
+}
+
+/// A table of rules, each row anchored so that other pages can link to it.
+fn render_policy_rules(rules: &[&PolicyRule], empty: &str) -> String {
+    if rules.is_empty() {
+        return format!("<p>{}</p>", html_escape(empty));
+    }
+
+    let mut html = String::from(
+        "<table><tr><th>Verdict</th><th>Applies to</th><th>Rule</th><th>Source</th></tr>",
+    );
+    for rule in rules {
+        let attributes: Vec<String> = rule
+            .attributes
+            .iter()
+            .map(|(key, value)| format!("{key}=\"{value}\""))
+            .collect();
+        html.push_str(&format!(
+            r#"<tr id="rule-{}"><td><span class="status {}">{}</span></td><td>{}</td><td><code>{}</code></td><td>{}</td></tr>"#,
+            rule.id,
+            if rule.allow {
+                "status-responsive"
+            } else {
+                "status-erroring"
+            },
+            if rule.allow { "allow" } else { "deny" },
+            html_escape(&rule.context.to_string()),
+            html_escape(&attributes.join(" ")),
+            html_escape(&rule_source(rule))
+        ));
+    }
+    html.push_str("</table>");
+
+    html
+}
+
+/// The file and line a rule is written on.
+fn rule_source(rule: &PolicyRule) -> String {
+    format!("{}:{}", rule.file.display(), rule.line)
+}
+
+fn severity_label(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Warning => "warning",
+        Severity::Info => "info",
+    }
+}
+
+fn render_interface_catalog(catalog: &[InterfaceCatalogEntry]) -> String {
+    let inconsistent: Vec<_> = catalog.iter().filter(|e| !e.is_consistent()).collect();
+
+    let mut html = String::from("<h2>Summary</h2><ul>");
+    html.push_str(&format!(
+        "<li>{} distinct interface names</li><li>{} with inconsistent implementations</li></ul>",
+        catalog.len(),
+        inconsistent.len()
+    ));
+
+    if !inconsistent.is_empty() {
+        html.push_str("<h2>Inconsistent Interfaces</h2><ul>");
+        for entry in &inconsistent {
+            html.push_str(&format!(
+                r##"<li><a href="#{}">{}</a> ({} variants)</li>"##,
+                html_escape(entry.name),
+                html_escape(entry.name),
+                entry.variants.len()
+            ));
+        }
+        html.push_str("</ul>");
+    }
+
+    html.push_str("<h2>All Interfaces</h2>");
+    for entry in catalog {
+        html.push_str(&format!(
+            r#"<div class="interface" id="{}"><h4>{}</h4><p>{} implementation{}</p>"#,
+            html_escape(entry.name),
+            html_escape(entry.name),
+            entry.implementation_count(),
+            if entry.implementation_count() == 1 {
+                ""
+            } else {
+                "s"
+            }
+        ));
+
+        if !entry.is_consistent() {
+            html.push_str(
+                r#"<div class="warning"><strong>Implementations differ in:</strong><ul>"#,
+            );
+            for member in entry.differing_members() {
+                html.push_str(&format!("<li><code>{}</code></li>", html_escape(member)));
+            }
+            html.push_str("</ul></div>");
+        }
+
+        for (i, variant) in entry.variants.iter().enumerate() {
+            if !entry.is_consistent() {
+                html.push_str(&format!("<h5>Variant {}:</h5>", i + 1));
+            }
+            html.push_str("<ul>");
+            for implementation in &variant.implementations {
+                html.push_str(&format!(
+                    r#"<li><a href="{}">{} {}</a></li>"#,
+                    object_page_url(implementation.service, implementation.path),
+                    html_escape(implementation.service),
+                    html_escape(implementation.path)
+                ));
+            }
+            html.push_str("</ul>");
+        }
+
+        html.push_str("</div>");
+    }
+
+    html
+}
+
+fn render_health_content(history: &HealthHistory) -> String {
+    let mut services: Vec<_> = history
+        .services()
+        .filter_map(|(name, probes)| Some((name, probes.back()?, probes)))
+        .collect();
+    // Problems first, they are what this page is for
+    services.sort_by_key(|(name, latest, _)| {
+        let rank = match latest.status {
+            HealthStatus::TimingOut => 0,
+            HealthStatus::Erroring => 1,
+            HealthStatus::Slow => 2,
+            HealthStatus::Responsive => 3,
+        };
+        (rank, name.to_string())
+    });
+
+    let mut html = String::from(
+        r#"<p><a href="/local/dbus_explorer/app/health">Probe again</a></p><h2>Summary</h2><ul>"#,
+    );
+    for status in [
+        HealthStatus::Responsive,
+        HealthStatus::Slow,
+        HealthStatus::TimingOut,
+        HealthStatus::Erroring,
+    ] {
+        let count = services
+            .iter()
+            .filter(|(_, latest, _)| latest.status == status)
+            .count();
+        html.push_str(&format!(
+            "<li>{} {}</li>",
+            render_health_status(status),
+            count
+        ));
+    }
+    html.push_str(&format!(
+        "</ul><p><em>Services replying to <code>org.freedesktop.DBus.Peer.Ping</code> within {} ms are responsive, history shows the last {} probes, newest last.</em></p>",
+        SLOW_THRESHOLD.as_millis(),
+        history.capacity()
+    ));
+
+    html.push_str(
+        "<table><tr><th>Service</th><th>Status</th><th>Latency</th><th>Machine ID</th><th>History</th><th>Error</th></tr>",
+    );
+    for (name, latest, probes) in services {
+        let responsive = probes
+            .iter()
+            .filter(|p| p.status == HealthStatus::Responsive)
+            .count();
+        let timeline: String = probes
+            .iter()
+            .map(|probe| {
+                format!(
+                    r#"<span class="status status-{}" title="{} ms, {} s ago">&nbsp;</span>"#,
+                    probe.status.label().replace(' ', "-"),
+                    probe.latency.as_millis(),
+                    probe.time.elapsed().unwrap_or_default().as_secs()
+                )
+            })
+            .collect();
+
+        html.push_str(&format!(
+            r#"<tr><td><a href="/local/dbus_explorer/app/service/{}">{}</a></td><td>{}</td><td>{:.1} ms</td><td>{}</td><td class="history">{} {}/{}</td><td>{}</td></tr>"#,
+            urlencoding::encode(name),
+            html_escape(name),
+            render_health_status(latest.status),
+            latest.latency.as_secs_f64() * 1000.0,
+            html_escape(latest.machine_id.as_deref().unwrap_or("")),
+            timeline,
+            responsive,
+            probes.len(),
+            html_escape(latest.error.as_deref().unwrap_or(""))
+        ));
+    }
+    html.push_str("</table>");
+
+    html
+}
+
+fn render_health_status(status: HealthStatus) -> String {
+    format!(
+        r#"<span class="status status-{}">{}</span>"#,
+        status.label().replace(' ', "-"),
+        status.label()
+    )
+}
+
 fn render_child_object_links(
     child_objects: &[&crate::dbus_introspection::ObjectInfo],
     service_name: &str,
@@ -267,11 +1741,3 @@ fn render_child_object_links(
     html.push_str("</ul>");
     html
 }
-
-fn html_escape(text: &str) -> String {
-    text.replace('&', "&amp;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;")
-        .replace('"', "&quot;")
-        .replace('\'', "&#x27;")
-}
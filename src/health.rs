@@ -0,0 +1,186 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant, SystemTime},
+};
+
+use dbus::{blocking::Connection, Error};
+
+use crate::{
+    error::{DbusError, DbusErrorKind},
+    request_log::{current_request, in_request, timed_call},
+};
+
+/// Replies slower than this are reported as slow.
+pub const SLOW_THRESHOLD: Duration = Duration::from_millis(100);
+/// Replies slower than this are not waited for.
+pub const PROBE_TIMEOUT: Duration = Duration::from_millis(2000);
+/// Number of connections probing at once.
+const PROBE_WORKERS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    Responsive,
+    Slow,
+    TimingOut,
+    Erroring,
+}
+
+impl HealthStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            HealthStatus::Responsive => "responsive",
+            HealthStatus::Slow => "slow",
+            HealthStatus::TimingOut => "timing out",
+            HealthStatus::Erroring => "erroring",
+        }
+    }
+}
+
+/// The outcome of pinging one service once.
+#[derive(Debug, Clone)]
+pub struct Probe {
+    pub time: SystemTime,
+    pub status: HealthStatus,
+    pub latency: Duration,
+    pub machine_id: Option<String>,
+    pub error: Option<String>,
+}
+
+/// The most recent probes of every service, oldest first.
+#[derive(Debug)]
+pub struct HealthHistory {
+    capacity: usize,
+    services: BTreeMap<String, VecDeque<Probe>>,
+}
+
+impl HealthHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            services: BTreeMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, service_name: &str, probe: Probe) {
+        let probes = self.services.entry(service_name.to_string()).or_default();
+        if probes.len() == self.capacity {
+            probes.pop_front();
+        }
+        probes.push_back(probe);
+    }
+
+    /// Forget services that are no longer on the bus.
+    pub fn retain(&mut self, service_names: &[String]) {
+        self.services.retain(|name, _| service_names.contains(name));
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn services(&self) -> impl Iterator<Item = (&String, &VecDeque<Probe>)> {
+        self.services.iter()
+    }
+}
+
+/// Call `Peer.Ping` and `Peer.GetMachineId` on the root object of a service.
+pub fn probe_service(conn: &Connection, service_name: &str) -> Probe {
+    let proxy = conn.with_proxy(service_name, "/", PROBE_TIMEOUT);
+    let time = SystemTime::now();
+
+    let start = Instant::now();
+    let ping = timed_call(service_name, "/", "org.freedesktop.DBus.Peer.Ping", || {
+        proxy.method_call::<(), _, _, _>("org.freedesktop.DBus.Peer", "Ping", ())
+    });
+    let latency = start.elapsed();
+
+    match ping {
+        Ok(()) => {
+            let machine_id = timed_call(
+                service_name,
+                "/",
+                "org.freedesktop.DBus.Peer.GetMachineId",
+                || {
+                    proxy.method_call::<(String,), _, _, _>(
+                        "org.freedesktop.DBus.Peer",
+                        "GetMachineId",
+                        (),
+                    )
+                },
+            )
+            .ok()
+            .map(|(id,)| id);
+            Probe {
+                time,
+                status: if latency < SLOW_THRESHOLD {
+                    HealthStatus::Responsive
+                } else {
+                    HealthStatus::Slow
+                },
+                latency,
+                machine_id,
+                error: None,
+            }
+        }
+        Err(e) => Probe {
+            time,
+            status: if is_timeout(&e) {
+                HealthStatus::TimingOut
+            } else {
+                HealthStatus::Erroring
+            },
+            latency,
+            machine_id: None,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+/// Probe all services on a few connections at once, so that a wedged daemon only holds up one
+/// of them while staying well within the number of connections the bus allows per user.
+pub fn probe_services(service_names: &[String]) -> Vec<(String, Probe)> {
+    let next = AtomicUsize::new(0);
+    let request = current_request();
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..PROBE_WORKERS.min(service_names.len()))
+            .map(|_| {
+                let request = request.clone();
+                scope.spawn(|| in_request(request, || probe_next(service_names, &next)))
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .filter_map(|handle| handle.join().ok())
+            .flatten()
+            .collect()
+    })
+}
+
+/// Probe the services from `next` on until none are left, on one connection.
+fn probe_next(service_names: &[String], next: &AtomicUsize) -> Vec<(String, Probe)> {
+    let conn = Connection::new_system();
+    let mut probes = Vec::new();
+    while let Some(name) = service_names.get(next.fetch_add(1, Ordering::Relaxed)) {
+        let probe = match &conn {
+            Ok(conn) => probe_service(conn, name),
+            Err(e) => Probe {
+                time: SystemTime::now(),
+                status: HealthStatus::Erroring,
+                latency: Duration::ZERO,
+                machine_id: None,
+                error: Some(e.to_string()),
+            },
+        };
+        probes.push((name.clone(), probe));
+    }
+    probes
+}
+
+fn is_timeout(error: &Error) -> bool {
+    matches!(
+        DbusError::from(error).kind(),
+        DbusErrorKind::Timeout | DbusErrorKind::NoReply
+    )
+}
@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+
+use crate::dbus_introspection::{InterfaceInfo, ServiceInfo};
+
+/// One object on which an interface was found.
+#[derive(Debug, Clone)]
+pub struct InterfaceImplementation<'a> {
+    pub service: &'a str,
+    pub path: &'a str,
+}
+
+/// Implementations that agree on members and signatures.
+#[derive(Debug, Clone)]
+pub struct InterfaceVariant<'a> {
+    pub members: Vec<String>,
+    pub implementations: Vec<InterfaceImplementation<'a>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct InterfaceCatalogEntry<'a> {
+    pub name: &'a str,
+    pub variants: Vec<InterfaceVariant<'a>>,
+}
+
+impl InterfaceCatalogEntry<'_> {
+    pub fn is_consistent(&self) -> bool {
+        self.variants.len() <= 1
+    }
+
+    pub fn implementation_count(&self) -> usize {
+        self.variants.iter().map(|v| v.implementations.len()).sum()
+    }
+
+    /// Members that are missing from, or have a different signature in, at least one variant.
+    pub fn differing_members(&self) -> Vec<&str> {
+        let mut differing: Vec<&str> = self
+            .variants
+            .iter()
+            .flat_map(|variant| variant.members.iter())
+            .filter(|member| {
+                !self
+                    .variants
+                    .iter()
+                    .all(|variant| variant.members.contains(member))
+            })
+            .map(String::as_str)
+            .collect();
+        differing.sort();
+        differing.dedup();
+        differing
+    }
+}
+
+/// Group every interface found in `services` by name and split each group by member signatures.
+pub fn build_interface_catalog(services: &[ServiceInfo]) -> Vec<InterfaceCatalogEntry<'_>> {
+    let mut by_name: BTreeMap<&str, Vec<InterfaceVariant>> = BTreeMap::new();
+
+    for service in services {
+        for object in &service.objects {
+            for interface in &object.interfaces {
+                let implementation = InterfaceImplementation {
+                    service: &service.name,
+                    path: &object.path,
+                };
+                let members = member_signatures(interface);
+
+                let variants = by_name.entry(&interface.name).or_default();
+                match variants.iter_mut().find(|v| v.members == members) {
+                    Some(variant) => variant.implementations.push(implementation),
+                    None => variants.push(InterfaceVariant {
+                        members,
+                        implementations: vec![implementation],
+                    }),
+                }
+            }
+        }
+    }
+
+    by_name
+        .into_iter()
+        .map(|(name, mut variants)| {
+            // Most common shape first, it is most likely the intended one
+            variants.sort_by(|a, b| b.implementations.len().cmp(&a.implementations.len()));
+            InterfaceCatalogEntry { name, variants }
+        })
+        .collect()
+}
+
+/// Describe every member by name and types only, argument names do not affect compatibility.
+fn member_signatures(interface: &InterfaceInfo) -> Vec<String> {
+    let mut members = Vec::new();
+
+    for method in &interface.methods {
+        let arguments: String = method
+            .arguments
+            .iter()
+            .map(|a| a.type_name.as_str())
+            .collect();
+        let returns: String = method
+            .return_values
+            .iter()
+            .map(|a| a.type_name.as_str())
+            .collect();
+        members.push(format!("method {}({arguments}) → ({returns})", method.name));
+    }
+
+    for property in &interface.properties {
+        members.push(format!(
+            "property {}: {} [{}]",
+            property.name, property.type_name, property.access
+        ));
+    }
+
+    for signal in &interface.signals {
+        let arguments: String = signal
+            .arguments
+            .iter()
+            .map(|a| a.type_name.as_str())
+            .collect();
+        members.push(format!("signal {}({arguments})", signal.name));
+    }
+
+    members.sort();
+    members
+}
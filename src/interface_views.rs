@@ -0,0 +1,254 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use dbus::arg::messageitem::MessageItem;
+
+use crate::{
+    dbus_introspection::InterfaceInfo,
+    systemd::active_state_class,
+    utils::html_escape,
+    value_view::{render_value, ValueFormat},
+};
+
+/// What a renderer gets to work with: the introspected interface and its current property
+/// values, if they could be read.
+pub struct InterfaceContext<'a> {
+    pub interface: &'a InterfaceInfo,
+    pub properties: Option<&'a BTreeMap<String, MessageItem>>,
+    pub format: &'a ValueFormat,
+}
+
+impl InterfaceContext<'_> {
+    pub fn property(&self, name: &str) -> Option<&MessageItem> {
+        self.properties?.get(name)
+    }
+}
+
+/// A specialised view of an interface, shown above the generic listing of its members.
+pub trait InterfaceRenderer: Send + Sync {
+    /// Render the view, or `None` if there is nothing to show, e.g. because the properties
+    /// could not be read.
+    fn render(&self, context: &InterfaceContext) -> Option<String>;
+}
+
+/// Renderers by the name of the interface they are for.
+#[derive(Default)]
+pub struct RendererRegistry {
+    renderers: HashMap<String, Box<dyn InterfaceRenderer>>,
+}
+
+impl fmt::Debug for RendererRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.renderers.keys()).finish()
+    }
+}
+
+impl RendererRegistry {
+    /// A registry with the renderers for common `org.freedesktop.*` interfaces.
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::default();
+        registry.register("org.freedesktop.systemd1.Unit", SystemdUnitRenderer);
+        registry.register(
+            "org.freedesktop.NetworkManager.Device",
+            NetworkManagerDeviceRenderer,
+        );
+        registry.register(
+            "org.freedesktop.hostname1",
+            PropertyTableRenderer {
+                properties: &[
+                    ("Hostname", "Hostname"),
+                    ("StaticHostname", "Static hostname"),
+                    ("PrettyHostname", "Pretty hostname"),
+                    ("OperatingSystemPrettyName", "Operating system"),
+                    ("KernelName", "Kernel"),
+                    ("KernelRelease", "Kernel release"),
+                    ("Chassis", "Chassis"),
+                ],
+            },
+        );
+        registry
+    }
+
+    /// Use `renderer` for `interface`, replacing any renderer registered before.
+    pub fn register(&mut self, interface: &str, renderer: impl InterfaceRenderer + 'static) {
+        self.renderers
+            .insert(interface.to_string(), Box::new(renderer));
+    }
+
+    pub fn get(&self, interface: &str) -> Option<&dyn InterfaceRenderer> {
+        self.renderers.get(interface).map(Box::as_ref)
+    }
+}
+
+/// A table of selected properties with friendly labels, skipping those that are missing.
+pub struct PropertyTableRenderer {
+    /// Property names and their labels.
+    pub properties: &'static [(&'static str, &'static str)],
+}
+
+impl InterfaceRenderer for PropertyTableRenderer {
+    fn render(&self, context: &InterfaceContext) -> Option<String> {
+        let rows: Vec<String> = self
+            .properties
+            .iter()
+            .filter_map(|(name, label)| {
+                let value = context.property(name)?;
+                let description = context
+                    .interface
+                    .properties
+                    .iter()
+                    .find(|p| p.name == *name)
+                    .and_then(|p| p.description.as_deref())
+                    .unwrap_or(name);
+                Some(format!(
+                    r#"<tr><th title="{}">{}</th><td>{}</td></tr>"#,
+                    html_escape(description),
+                    html_escape(label),
+                    render_value(value, context.format)
+                ))
+            })
+            .collect();
+
+        if rows.is_empty() {
+            return None;
+        }
+        Some(format!(
+            r#"<table class="interface-view">{}</table>"#,
+            rows.join("")
+        ))
+    }
+}
+
+/// The load, activation and enablement state of a systemd unit at a glance.
+pub struct SystemdUnitRenderer;
+
+impl InterfaceRenderer for SystemdUnitRenderer {
+    fn render(&self, context: &InterfaceContext) -> Option<String> {
+        let text = |name: &str| match context.property(name) {
+            Some(MessageItem::Str(s)) => s.as_str(),
+            _ => "",
+        };
+        let active_state = text("ActiveState");
+        if active_state.is_empty() {
+            return None;
+        }
+
+        let status_class = active_state_class(active_state);
+
+        Some(format!(
+            r#"<table class="interface-view">
+<tr><th>Unit</th><th>Description</th><th>Load</th><th>Active</th><th>Sub</th><th>Enabled</th></tr>
+<tr><td>{}</td><td>{}</td><td>{}</td><td><span class="status {status_class}">{}</span></td><td>{}</td><td>{}</td></tr>
+</table>"#,
+            html_escape(text("Id")),
+            html_escape(text("Description")),
+            html_escape(text("LoadState")),
+            html_escape(active_state),
+            html_escape(text("SubState")),
+            html_escape(text("UnitFileState")),
+        ))
+    }
+}
+
+/// Decodes the numeric state and type of a NetworkManager device.
+pub struct NetworkManagerDeviceRenderer;
+
+impl InterfaceRenderer for NetworkManagerDeviceRenderer {
+    fn render(&self, context: &InterfaceContext) -> Option<String> {
+        let number = |name: &str| match context.property(name) {
+            Some(MessageItem::UInt32(n)) => Some(*n),
+            _ => None,
+        };
+        let state = number("State")?;
+
+        let interface = match context.property("Interface") {
+            Some(MessageItem::Str(s)) => s.clone(),
+            _ => String::new(),
+        };
+        let device_type = number("DeviceType")
+            .map(|n| nm_device_type_name(n).map_or_else(|| n.to_string(), str::to_string))
+            .unwrap_or_default();
+        let state_name = nm_device_state_name(state).unwrap_or("unknown");
+        let status_class = match state {
+            100 => "status-responsive",
+            40..=90 | 110 => "status-slow",
+            120 => "status-erroring",
+            _ => "status-inactive",
+        };
+
+        let mut rows = format!(
+            r#"<tr><th>Interface</th><td>{}</td></tr>
+<tr><th>Type</th><td>{}</td></tr>
+<tr><th>State</th><td><span class="status {status_class}">{}</span> ({state})</td></tr>"#,
+            html_escape(&interface),
+            html_escape(&device_type),
+            state_name,
+        );
+        for (name, label) in [
+            ("ActiveConnection", "Active connection"),
+            ("Ip4Config", "IPv4 configuration"),
+            ("Ip6Config", "IPv6 configuration"),
+        ] {
+            // NetworkManager uses "/" for "none"
+            if let Some(value) = context
+                .property(name)
+                .filter(|v| !matches!(v, MessageItem::ObjectPath(p) if &**p == "/"))
+            {
+                rows.push_str(&format!(
+                    "<tr><th>{label}</th><td>{}</td></tr>",
+                    render_value(value, context.format)
+                ));
+            }
+        }
+
+        Some(format!(r#"<table class="interface-view">{rows}</table>"#))
+    }
+}
+
+/// `NMDeviceState`
+fn nm_device_state_name(state: u32) -> Option<&'static str> {
+    let name = match state {
+        0 => "unknown",
+        10 => "unmanaged",
+        20 => "unavailable",
+        30 => "disconnected",
+        40 => "prepare",
+        50 => "config",
+        60 => "need auth",
+        70 => "ip config",
+        80 => "ip check",
+        90 => "secondaries",
+        100 => "activated",
+        110 => "deactivating",
+        120 => "failed",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// `NMDeviceType`
+fn nm_device_type_name(device_type: u32) -> Option<&'static str> {
+    let name = match device_type {
+        0 => "unknown",
+        1 => "ethernet",
+        2 => "wifi",
+        5 => "bluetooth",
+        8 => "modem",
+        10 => "bond",
+        11 => "vlan",
+        13 => "bridge",
+        14 => "generic",
+        15 => "team",
+        16 => "tun",
+        17 => "ip tunnel",
+        18 => "macvlan",
+        19 => "vxlan",
+        20 => "veth",
+        29 => "wireguard",
+        32 => "loopback",
+        _ => return None,
+    };
+    Some(name)
+}
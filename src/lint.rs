@@ -0,0 +1,401 @@
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+
+use crate::{
+    dbus_introspection::{ArgumentInfo, InterfaceInfo, ServiceInfo},
+    standard_interfaces::is_standard_interface,
+};
+
+/// A D-Bus API design guideline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintRule {
+    MemberNaming,
+    UnversionedInterface,
+    UnnamedArgument,
+    VariantOveruse,
+    WriteOnlyProperty,
+    MissingDescription,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Info,
+}
+
+impl LintRule {
+    pub const ALL: [LintRule; 6] = [
+        LintRule::MemberNaming,
+        LintRule::UnversionedInterface,
+        LintRule::UnnamedArgument,
+        LintRule::VariantOveruse,
+        LintRule::WriteOnlyProperty,
+        LintRule::MissingDescription,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LintRule::MemberNaming => "member-naming",
+            LintRule::UnversionedInterface => "unversioned-interface",
+            LintRule::UnnamedArgument => "unnamed-argument",
+            LintRule::VariantOveruse => "variant-overuse",
+            LintRule::WriteOnlyProperty => "write-only-property",
+            LintRule::MissingDescription => "missing-description",
+        }
+    }
+
+    /// What the guideline asks for.
+    pub fn guideline(&self) -> &'static str {
+        match self {
+            LintRule::MemberNaming => "Methods, properties and signals are named in CamelCase",
+            LintRule::UnversionedInterface => {
+                "Interface names end in a major version, e.g. com.example.Foo1"
+            }
+            LintRule::UnnamedArgument => "Arguments have names",
+            LintRule::VariantOveruse => {
+                "Variants and a{sv} are the exception, used only where the type cannot be known in advance"
+            }
+            LintRule::WriteOnlyProperty => "Properties are readable, write-only ones are methods",
+            LintRule::MissingDescription => "Interfaces and their members are documented",
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            LintRule::VariantOveruse | LintRule::MissingDescription => Severity::Info,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+/// An interface overuses variants when more than this share of its arguments and properties
+/// are `v` or `a{sv}`.
+const MAX_VARIANT_SHARE: f64 = 1.0 / 3.0;
+
+/// One violation of a guideline.
+#[derive(Debug, Clone, Serialize)]
+pub struct Lint {
+    pub rule: LintRule,
+    pub interface: String,
+    /// `None` for findings about the interface as a whole.
+    pub member: Option<String>,
+    pub message: String,
+}
+
+/// Lint every distinct interface of a service once, skipping the standard interfaces which
+/// the service does not design itself.
+pub fn lint_service(service: &ServiceInfo) -> Vec<Lint> {
+    let mut seen = BTreeSet::new();
+    service
+        .objects
+        .iter()
+        .flat_map(|object| &object.interfaces)
+        .filter(|interface| !is_standard_interface(&interface.name))
+        .filter(|interface| seen.insert(interface.name.clone()))
+        .flat_map(lint_interface)
+        .collect()
+}
+
+pub fn lint_interface(interface: &InterfaceInfo) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let mut lint = |rule, member: Option<&str>, message: String| {
+        lints.push(Lint {
+            rule,
+            interface: interface.name.clone(),
+            member: member.map(str::to_string),
+            message,
+        })
+    };
+
+    let last_element = interface.name.rsplit('.').next().unwrap_or_default();
+    if !last_element.ends_with(|c: char| c.is_ascii_digit()) {
+        lint(
+            LintRule::UnversionedInterface,
+            None,
+            format!("{} has no version number", interface.name),
+        );
+    }
+    if interface.description.is_none() {
+        lint(
+            LintRule::MissingDescription,
+            None,
+            "Interface has no description".to_string(),
+        );
+    }
+
+    // Methods and signals are checked alike
+    let members = interface
+        .methods
+        .iter()
+        .map(|m| {
+            let arguments: Vec<&ArgumentInfo> =
+                m.arguments.iter().chain(&m.return_values).collect();
+            ("method", &m.name, arguments, m.description.is_some())
+        })
+        .chain(interface.signals.iter().map(|s| {
+            let arguments: Vec<&ArgumentInfo> = s.arguments.iter().collect();
+            ("signal", &s.name, arguments, s.description.is_some())
+        }));
+
+    // Variants are counted across the whole interface, one of them is often justified
+    let mut typed = 0;
+    let mut variants = Vec::new();
+
+    for (kind, name, arguments, has_description) in members {
+        if !is_camel_case(name) {
+            lint(
+                LintRule::MemberNaming,
+                Some(name),
+                format!("{kind} {name} is not CamelCase"),
+            );
+        }
+        let unnamed = arguments.iter().filter(|a| a.name.is_none()).count();
+        if unnamed > 0 {
+            lint(
+                LintRule::UnnamedArgument,
+                Some(name),
+                format!("{kind} {name} has {unnamed} unnamed argument(s)"),
+            );
+        }
+        typed += arguments.len();
+        for argument in arguments {
+            if is_variant_type(&argument.type_name) {
+                variants.push(format!(
+                    "{name}({})",
+                    argument.name.as_deref().unwrap_or("_")
+                ));
+            }
+        }
+        if !has_description {
+            lint(
+                LintRule::MissingDescription,
+                Some(name),
+                format!("{kind} {name} has no description"),
+            );
+        }
+    }
+
+    for property in &interface.properties {
+        let name = &property.name;
+        if !is_camel_case(name) {
+            lint(
+                LintRule::MemberNaming,
+                Some(name),
+                format!("property {name} is not CamelCase"),
+            );
+        }
+        typed += 1;
+        if is_variant_type(&property.type_name) {
+            variants.push(name.clone());
+        }
+        if property.access == "write" {
+            lint(
+                LintRule::WriteOnlyProperty,
+                Some(name),
+                format!("property {name} is write-only"),
+            );
+        }
+        if property.description.is_none() {
+            lint(
+                LintRule::MissingDescription,
+                Some(name),
+                format!("property {name} has no description"),
+            );
+        }
+    }
+
+    if variants.len() > 1 && variants.len() as f64 > typed as f64 * MAX_VARIANT_SHARE {
+        lint(
+            LintRule::VariantOveruse,
+            None,
+            format!(
+                "{} of {typed} arguments and properties are v or a{{sv}}: {}",
+                variants.len(),
+                variants.join(", ")
+            ),
+        );
+    }
+
+    lints
+}
+
+/// `UpperCamelCase`: starts with an upper case letter and has no underscores.
+fn is_camel_case(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase()) && !name.contains('_')
+}
+
+fn is_variant_type(type_name: &str) -> bool {
+    type_name == "v" || type_name == "a{sv}"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbus_introspection::parse_introspection_xml;
+
+    const DOC: &str = r#"<annotation name="org.freedesktop.DBus.Description" value="Documented"/>"#;
+
+    fn lints(rule: LintRule, body: &str) -> Vec<(Option<String>, String)> {
+        let xml =
+            format!(r#"<node><interface name="com.example.Thing1">{DOC}{body}</interface></node>"#);
+        let (interfaces, _) = parse_introspection_xml(&xml).unwrap();
+        lint_interface(&interfaces[0])
+            .into_iter()
+            .filter(|lint| lint.rule == rule)
+            .map(|lint| (lint.member, lint.message))
+            .collect()
+    }
+
+    fn lint(member: Option<&str>, message: &str) -> (Option<String>, String) {
+        (member.map(str::to_string), message.to_string())
+    }
+
+    #[test]
+    fn clean_interface() {
+        let xml = format!(
+            r#"<node><interface name="com.example.Thing1">{DOC}
+                 <method name="GetName">{DOC}<arg name="name" type="s" direction="out"/></method>
+                 <property name="Size" type="u" access="readwrite">{DOC}</property>
+                 <signal name="Changed">{DOC}<arg name="value" type="v"/></signal>
+               </interface></node>"#
+        );
+        let (interfaces, _) = parse_introspection_xml(&xml).unwrap();
+        let lints = lint_interface(&interfaces[0]);
+        assert!(lints.is_empty(), "{lints:?}");
+    }
+
+    #[test]
+    fn member_naming() {
+        let body = format!(
+            r#"<method name="get_name">{DOC}</method>
+               <method name="getName">{DOC}</method>
+               <signal name="Name_Changed">{DOC}</signal>
+               <property name="size" type="u" access="read">{DOC}</property>"#
+        );
+        assert_eq!(
+            lints(LintRule::MemberNaming, &body),
+            [
+                lint(Some("get_name"), "method get_name is not CamelCase"),
+                lint(Some("getName"), "method getName is not CamelCase"),
+                lint(Some("Name_Changed"), "signal Name_Changed is not CamelCase"),
+                lint(Some("size"), "property size is not CamelCase"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unversioned_interface() {
+        for (name, unversioned) in [
+            ("com.example.Thing1", false),
+            ("com.example.Thing12", false),
+            ("com.example.Thing", true),
+            ("com.example1.Thing", true),
+        ] {
+            let xml = format!(r#"<node><interface name="{name}">{DOC}</interface></node>"#);
+            let (interfaces, _) = parse_introspection_xml(&xml).unwrap();
+            let found = lint_interface(&interfaces[0])
+                .iter()
+                .any(|lint| lint.rule == LintRule::UnversionedInterface && lint.member.is_none());
+            assert_eq!(found, unversioned, "{name:?}");
+        }
+    }
+
+    #[test]
+    fn unnamed_arguments() {
+        let body = format!(
+            r#"<method name="Get">{DOC}
+                 <arg name="key" type="s" direction="in"/>
+                 <arg type="s" direction="in"/>
+                 <arg type="v" direction="out"/>
+               </method>
+               <signal name="Changed">{DOC}<arg type="s"/></signal>"#
+        );
+        assert_eq!(
+            lints(LintRule::UnnamedArgument, &body),
+            [
+                lint(Some("Get"), "method Get has 2 unnamed argument(s)"),
+                lint(Some("Changed"), "signal Changed has 1 unnamed argument(s)"),
+            ]
+        );
+    }
+
+    #[test]
+    fn variant_overuse() {
+        // A few variants among many typed values are fine
+        let body = format!(
+            r#"<method name="Get">{DOC}
+                 <arg name="key" type="s" direction="in"/>
+                 <arg name="value" type="v" direction="out"/>
+               </method>
+               <property name="Options" type="a{{sv}}" access="read">{DOC}</property>
+               <property name="Size" type="u" access="read">{DOC}</property>
+               <property name="Name" type="s" access="read">{DOC}</property>
+               <property name="Path" type="o" access="read">{DOC}</property>
+               <property name="Enabled" type="b" access="read">{DOC}</property>"#
+        );
+        assert!(lints(LintRule::VariantOveruse, &body).is_empty());
+
+        let body = format!(
+            r#"<method name="Get">{DOC}
+                 <arg name="key" type="s" direction="in"/>
+                 <arg name="value" type="v" direction="out"/>
+               </method>
+               <signal name="Changed">{DOC}<arg name="changes" type="a{{sv}}"/></signal>
+               <property name="Options" type="a{{sv}}" access="read">{DOC}</property>
+               <property name="Labels" type="a{{ss}}" access="read">{DOC}</property>"#
+        );
+        assert_eq!(
+            lints(LintRule::VariantOveruse, &body),
+            [lint(
+                None,
+                "3 of 5 arguments and properties are v or a{sv}: Get(value), Changed(changes), Options"
+            )]
+        );
+
+        // A lone variant is never overuse
+        let body = format!(
+            r#"<method name="Get">{DOC}<arg name="value" type="v" direction="out"/></method>"#
+        );
+        assert!(lints(LintRule::VariantOveruse, &body).is_empty());
+    }
+
+    #[test]
+    fn write_only_property() {
+        let body = format!(
+            r#"<property name="Secret" type="s" access="write">{DOC}</property>
+               <property name="Name" type="s" access="readwrite">{DOC}</property>
+               <property name="Size" type="u" access="read">{DOC}</property>"#
+        );
+        assert_eq!(
+            lints(LintRule::WriteOnlyProperty, &body),
+            [lint(Some("Secret"), "property Secret is write-only")]
+        );
+    }
+
+    #[test]
+    fn missing_description() {
+        let xml = r#"<node><interface name="com.example.Thing1">
+                       <method name="Get"/>
+                       <signal name="Changed"/>
+                       <property name="Size" type="u" access="read"/>
+                     </interface></node>"#;
+        let (interfaces, _) = parse_introspection_xml(xml).unwrap();
+        let lints: Vec<_> = lint_interface(&interfaces[0])
+            .into_iter()
+            .filter(|lint| lint.rule == LintRule::MissingDescription)
+            .map(|lint| lint.message)
+            .collect();
+        assert_eq!(
+            lints,
+            [
+                "Interface has no description",
+                "method Get has no description",
+                "signal Changed has no description",
+                "property Size has no description",
+            ]
+        );
+    }
+}
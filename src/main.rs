@@ -1,28 +1,68 @@
 use anyhow::Result;
+use clap::Parser;
 use log::info;
 
+mod bus_policy;
+mod bus_stats;
+mod cli;
+mod command_lines;
 mod config;
+mod conformance;
+mod dbus_calls;
 mod dbus_introspection;
 mod error;
+mod gvariant;
 mod handlers;
+mod health;
+mod interface_catalog;
+mod interface_views;
+mod lint;
+mod match_rules;
+mod metrics;
+mod mock;
+mod recorder;
+mod request_log;
 mod routes;
+mod signature;
+mod standard_interfaces;
+mod state;
+mod systemd;
 mod templates;
+mod test_runner;
 mod utils;
+mod value_view;
+mod values;
 
+use cli::{Cli, Command};
 use config::Config;
 use routes::create_routes;
+use state::AppState;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging
     acap_logging::init_logger();
 
-    // Load configuration
+    // Load configuration, which applies to the subcommands too
     let config = Config::from_env();
+    log::set_max_level(config.log_level_filter());
+
+    let cli = Cli::parse();
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve(config).await,
+        command => cli::run(command, cli.json).inspect_err(|e| {
+            if cli.json {
+                cli::print_error_json(e);
+            }
+        }),
+    }
+}
+
+async fn serve(config: Config) -> Result<()> {
     info!("Starting D-Bus Explorer with config: {config:?}");
 
     // Create the web application
-    let app = create_routes();
+    let app = create_routes(AppState::new(&config));
 
     let listener = tokio::net::TcpListener::bind(config.server_addr).await?;
     info!(
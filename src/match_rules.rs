@@ -0,0 +1,461 @@
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use dbus::{
+    arg::messageitem::MessageItem,
+    blocking::Connection,
+    channel::MatchingReceiver,
+    message::{MatchRule, MessageType},
+    Message,
+};
+use serde::Deserialize;
+
+use crate::{
+    dbus_calls::call_method,
+    error::{self, AppError},
+    gvariant::gvariant_text,
+    signature::DbusType,
+    utils::{
+        validate_interface_name, validate_member_name, validate_namespace, validate_object_path,
+        validate_service_name,
+    },
+    values::array_item,
+};
+
+/// The highest argument index a match rule may test.
+const MAX_ARGUMENT_INDEX: u8 = 63;
+
+/// The fields of a match rule as entered on the match rule page; empty fields are left out.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MatchRuleSpec {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub sender: String,
+    pub destination: String,
+    pub interface: String,
+    pub member: String,
+    pub path: String,
+    pub path_namespace: String,
+    pub arg0namespace: String,
+    /// `argN=value` or `argNpath=value` conditions, one per line.
+    pub args: String,
+}
+
+/// One `key='value'` pair of a match rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchCondition {
+    pub key: String,
+    pub value: String,
+}
+
+impl MatchRuleSpec {
+    /// The conditions in the order the fields are listed, or every problem with them.
+    pub fn conditions(&self) -> std::result::Result<Vec<MatchCondition>, Vec<String>> {
+        let mut conditions = Vec::new();
+        let mut errors = Vec::new();
+        let mut add = |key: &str, value: &str, validate: &dyn Fn(&str) -> error::Result<()>| {
+            let value = value.trim();
+            if value.is_empty() {
+                return;
+            }
+            match validate(value) {
+                Ok(()) => conditions.push(MatchCondition {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                }),
+                Err(AppError::InvalidInput(message)) => errors.push(format!("{key}: {message}")),
+                Err(e) => errors.push(format!("{key}: {e}")),
+            }
+        };
+
+        add("type", &self.message_type, &validate_message_type);
+        add("sender", &self.sender, &validate_service_name);
+        add("destination", &self.destination, &validate_service_name);
+        add("interface", &self.interface, &validate_interface_name);
+        add("member", &self.member, &validate_member_name);
+        add("path", &self.path, &validate_object_path);
+        add(
+            "path_namespace",
+            &self.path_namespace,
+            &validate_object_path,
+        );
+        add("arg0namespace", &self.arg0namespace, &validate_namespace);
+
+        for line in self.args.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            match parse_argument_condition(line) {
+                Ok(condition) => conditions.push(condition),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        if conditions.iter().any(|c| c.key == "path")
+            && conditions.iter().any(|c| c.key == "path_namespace")
+        {
+            errors.push("path and path_namespace cannot be used together".to_string());
+        }
+        // The bus keeps one condition per argument, whatever its kind
+        let mut indices = BTreeSet::new();
+        for condition in &conditions {
+            if let Some(index) = argument_index(&condition.key) {
+                if !indices.insert(index) {
+                    errors.push(format!("Argument {index} is tested more than once"));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(conditions)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn validate_message_type(value: &str) -> error::Result<()> {
+    match value {
+        "signal" | "method_call" | "method_return" | "error" => Ok(()),
+        other => Err(AppError::InvalidInput(format!(
+            "Unknown message type {other:?}, expected signal, method_call, method_return or error"
+        ))),
+    }
+}
+
+/// Parse `argN=value` or `argNpath=value`, without quotes around the value.
+fn parse_argument_condition(line: &str) -> std::result::Result<MatchCondition, String> {
+    let (key, value) = line
+        .split_once('=')
+        .ok_or_else(|| format!("Expected argN=value or argNpath=value but got {line:?}"))?;
+    let key = key.trim();
+    let index = key
+        .strip_prefix("arg")
+        .map(|rest| rest.strip_suffix("path").unwrap_or(rest))
+        .and_then(|digits| digits.parse::<u8>().ok())
+        .ok_or_else(|| format!("Expected argN or argNpath but got {key:?}"))?;
+    if index > MAX_ARGUMENT_INDEX {
+        return Err(format!(
+            "{key}: arguments are numbered from 0 to {MAX_ARGUMENT_INDEX}"
+        ));
+    }
+    if key.ends_with("path") && !value.starts_with('/') {
+        return Err(format!("{key}: {value:?} must start with '/'"));
+    }
+    Ok(MatchCondition {
+        key: key.to_string(),
+        value: value.to_string(),
+    })
+}
+
+fn argument_index(key: &str) -> Option<u8> {
+    if key == "arg0namespace" {
+        return Some(0);
+    }
+    let digits = key.strip_prefix("arg")?;
+    digits.strip_suffix("path").unwrap_or(digits).parse().ok()
+}
+
+/// Write conditions as a match rule string, quoting every value.
+pub fn rule_text(conditions: &[MatchCondition]) -> String {
+    conditions
+        .iter()
+        .map(|c| {
+            // Quotes cannot be escaped inside quotes, so close them around an escaped one
+            format!("{}='{}'", c.key, c.value.replace('\'', r"'\''"))
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// A message seen while listening with a match rule.
+#[derive(Debug, Clone)]
+pub struct CapturedMessage {
+    /// Time since listening started.
+    pub elapsed: Duration,
+    pub message_type: &'static str,
+    pub sender: Option<String>,
+    pub destination: Option<String>,
+    pub path: Option<String>,
+    pub interface: Option<String>,
+    pub member: Option<String>,
+    pub error_name: Option<String>,
+    pub serial: Option<u32>,
+    pub reply_serial: Option<u32>,
+    /// The arguments in the GVariant text format.
+    pub arguments: Vec<String>,
+}
+
+impl CapturedMessage {
+    fn new(mut message: Message, elapsed: Duration) -> Self {
+        let error_name = match message.msg_type() {
+            MessageType::Error => message
+                .as_result()
+                .err()
+                .and_then(|e| e.name().map(str::to_string)),
+            _ => None,
+        };
+        Self {
+            elapsed,
+            message_type: match message.msg_type() {
+                MessageType::Signal => "signal",
+                MessageType::MethodCall => "method_call",
+                MessageType::MethodReturn => "method_return",
+                MessageType::Error => "error",
+            },
+            sender: message.sender().map(|s| s.to_string()),
+            destination: message.destination().map(|d| d.to_string()),
+            path: message.path().map(|p| p.to_string()),
+            interface: message.interface().map(|i| i.to_string()),
+            member: message.member().map(|m| m.to_string()),
+            error_name,
+            serial: message.get_serial(),
+            reply_serial: message.get_reply_serial(),
+            arguments: message
+                .get_items()
+                .iter()
+                .map(|item| gvariant_text(item, true))
+                .collect(),
+        }
+    }
+}
+
+/// What was seen while listening, and how.
+#[derive(Debug, Clone)]
+pub struct Capture {
+    /// Whether the bus let the explorer monitor all traffic; if not, only signals were seen.
+    pub monitor: bool,
+    pub messages: Vec<CapturedMessage>,
+    /// Whether listening stopped at the message limit rather than at the end of the time.
+    pub truncated: bool,
+}
+
+/// Listen on a new connection for messages matching `rule` for `duration` or until `limit`
+/// messages have been seen.
+///
+/// The connection becomes a monitor if the bus allows it, which sees method calls and replies
+/// between other connections too. Otherwise the rule is added as an ordinary match, which only
+/// delivers signals.
+pub fn capture(rule: &str, duration: Duration, limit: usize) -> Result<Capture> {
+    let conn = Connection::new_system().context("Failed to connect to the system bus")?;
+    let own_name = conn.unique_name().to_string();
+
+    let rules = array_item(
+        vec![MessageItem::Str(rule.to_string())],
+        &DbusType::Array(Box::new(DbusType::String)),
+    )
+    .map_err(anyhow::Error::msg)?;
+    let monitor = call_method(
+        &conn,
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus.Monitoring",
+        "BecomeMonitor",
+        &[rules, MessageItem::UInt32(0)],
+    )
+    .is_ok();
+    if !monitor {
+        conn.add_match_no_cb(rule)
+            .with_context(|| format!("The bus rejected the match rule {rule}"))?;
+    }
+
+    let started = Instant::now();
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    let received = Arc::clone(&messages);
+    conn.start_receive(
+        MatchRule::new(),
+        Box::new(move |message, _| {
+            // The bus tells the explorer about its own name, which is not what is being tested
+            if message.destination().as_deref() != Some(own_name.as_str()) {
+                received
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push(CapturedMessage::new(message, started.elapsed()));
+            }
+            true
+        }),
+    );
+
+    let deadline = started + duration;
+    loop {
+        let count = messages.lock().unwrap_or_else(|e| e.into_inner()).len();
+        let now = Instant::now();
+        if count >= limit || now >= deadline {
+            break;
+        }
+        conn.process((deadline - now).min(Duration::from_millis(100)))?;
+    }
+
+    let mut messages = std::mem::take(&mut *messages.lock().unwrap_or_else(|e| e.into_inner()));
+    let truncated = messages.len() >= limit;
+    messages.truncate(limit);
+    Ok(Capture {
+        monitor,
+        messages,
+        truncated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn condition(key: &str, value: &str) -> MatchCondition {
+        MatchCondition {
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn conditions_in_field_order() {
+        let spec = MatchRuleSpec {
+            message_type: "signal".to_string(),
+            interface: " com.example.Thing ".to_string(),
+            member: "Changed".to_string(),
+            path_namespace: "/com/example".to_string(),
+            arg0namespace: "com.example".to_string(),
+            args: "\narg1=on\n  arg2path=/com/example/  \n".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            spec.conditions(),
+            Ok(vec![
+                condition("type", "signal"),
+                condition("interface", "com.example.Thing"),
+                condition("member", "Changed"),
+                condition("path_namespace", "/com/example"),
+                condition("arg0namespace", "com.example"),
+                condition("arg1", "on"),
+                condition("arg2path", "/com/example/"),
+            ])
+        );
+        assert_eq!(MatchRuleSpec::default().conditions(), Ok(vec![]));
+    }
+
+    #[test]
+    fn invalid_conditions() {
+        let spec = MatchRuleSpec {
+            message_type: "bogus".to_string(),
+            path: "/a".to_string(),
+            path_namespace: "/a".to_string(),
+            arg0namespace: "1com".to_string(),
+            args: "arg0=x\narg0path=/x\narg70=x\narg1path=z".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            spec.conditions(),
+            Err(vec![
+                "type: Unknown message type \"bogus\", expected signal, method_call, method_return or error".to_string(),
+                "arg0namespace: Element \"1com\" of namespace \"1com\" must not start with a digit".to_string(),
+                "arg70: arguments are numbered from 0 to 63".to_string(),
+                "arg1path: \"z\" must start with '/'".to_string(),
+                "path and path_namespace cannot be used together".to_string(),
+                "Argument 0 is tested more than once".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn arguments_tested_once() {
+        let spec = |arg0namespace: &str, args: &str| MatchRuleSpec {
+            arg0namespace: arg0namespace.to_string(),
+            args: args.to_string(),
+            ..Default::default()
+        };
+        assert!(spec(
+            "com.example",
+            "arg1=x
+arg2path=/"
+        )
+        .conditions()
+        .is_ok());
+        assert_eq!(
+            spec(
+                "com.example",
+                "arg0=x
+arg0path=/
+arg1=a
+arg1path=/b"
+            )
+            .conditions(),
+            Err(vec![
+                "Argument 0 is tested more than once".to_string(),
+                "Argument 0 is tested more than once".to_string(),
+                "Argument 1 is tested more than once".to_string(),
+            ])
+        );
+        assert_eq!(
+            spec(
+                "",
+                "arg3=x
+arg3=y"
+            )
+            .conditions(),
+            Err(vec!["Argument 3 is tested more than once".to_string()])
+        );
+    }
+
+    #[test]
+    fn argument_conditions() {
+        for (line, parsed) in [
+            ("arg0=x", Ok(condition("arg0", "x"))),
+            ("arg63=", Ok(condition("arg63", ""))),
+            ("arg3 = a=b", Ok(condition("arg3", " a=b"))),
+            ("arg5path=/", Ok(condition("arg5path", "/"))),
+            ("arg64=x", Err("arg64: arguments are numbered from 0 to 63")),
+            ("arg1path=x", Err("arg1path: \"x\" must start with '/'")),
+            ("arg=x", Err("Expected argN or argNpath but got \"arg\"")),
+            (
+                "argpath=/",
+                Err("Expected argN or argNpath but got \"argpath\""),
+            ),
+            (
+                "arg0namespace=com",
+                Err("Expected argN or argNpath but got \"arg0namespace\""),
+            ),
+            (
+                "arg0",
+                Err("Expected argN=value or argNpath=value but got \"arg0\""),
+            ),
+        ] {
+            assert_eq!(
+                parse_argument_condition(line),
+                parsed.map_err(str::to_string),
+                "{line:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn path_and_namespace_conflict() {
+        let spec = |path: &str, path_namespace: &str| MatchRuleSpec {
+            path: path.to_string(),
+            path_namespace: path_namespace.to_string(),
+            ..Default::default()
+        };
+        assert!(spec("/a", "").conditions().is_ok());
+        assert!(spec("", "/a").conditions().is_ok());
+        assert_eq!(
+            spec("/a", "/b").conditions(),
+            Err(vec![
+                "path and path_namespace cannot be used together".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn rule_texts() {
+        assert_eq!(rule_text(&[]), "");
+        assert_eq!(
+            rule_text(&[
+                condition("interface", "com.example.Thing"),
+                condition("arg0", "it's"),
+                condition("arg1", "''"),
+            ]),
+            r"interface='com.example.Thing',arg0='it'\''s',arg1=''\'''\'''"
+        );
+    }
+}
@@ -0,0 +1,397 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use dbus::blocking::Connection;
+
+use crate::{
+    dbus_introspection::{analyze_service, list_activatable_names, list_names},
+    request_log,
+};
+
+/// Upper bounds of the histogram buckets, in seconds.
+const BUCKETS: [f64; 10] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0, 5.0];
+
+/// Counters of the explorer itself, shared by everything that makes requests or D-Bus calls.
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    inner: Mutex<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    /// By route and status code.
+    requests: BTreeMap<(String, u16), u64>,
+    request_durations: BTreeMap<String, Histogram>,
+    /// By service.
+    dbus_calls: BTreeMap<String, u64>,
+    dbus_errors: BTreeMap<String, u64>,
+    dbus_durations: BTreeMap<String, Histogram>,
+    crawl_durations: Histogram,
+    last_crawl_durations: BTreeMap<String, Duration>,
+    /// By cache and whether the lookup was a hit.
+    cache_lookups: BTreeMap<(String, bool), u64>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Histogram {
+    /// Not cumulative, the last count is for observations above the last bound.
+    buckets: [u64; BUCKETS.len() + 1],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let bucket = BUCKETS
+            .iter()
+            .position(|&bound| seconds <= bound)
+            .unwrap_or(BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    /// Write the `_bucket`, `_sum` and `_count` series, with `labels` like `route="/"`.
+    fn write(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(self.buckets) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{{labels}{separator}le=\"{bound}\"}} {cumulative}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{{labels}{separator}le=\"+Inf\"}} {}",
+            self.count
+        );
+        let braces = |labels: &str| {
+            if labels.is_empty() {
+                String::new()
+            } else {
+                format!("{{{labels}}}")
+            }
+        };
+        let _ = writeln!(out, "{name}_sum{} {}", braces(labels), self.sum);
+        let _ = writeln!(out, "{name}_count{} {}", braces(labels), self.count);
+    }
+}
+
+impl Metrics {
+    fn counters(&self) -> std::sync::MutexGuard<'_, Counters> {
+        // Counters stay usable even if a thread panicked while updating them
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn record_request(&self, route: &str, status: u16, duration: Duration) {
+        let mut counters = self.counters();
+        *counters
+            .requests
+            .entry((route.to_string(), status))
+            .or_default() += 1;
+        counters
+            .request_durations
+            .entry(route.to_string())
+            .or_default()
+            .observe(duration);
+    }
+
+    pub fn record_dbus_call(&self, service: &str, ok: bool, duration: Duration) {
+        let mut counters = self.counters();
+        *counters.dbus_calls.entry(service.to_string()).or_default() += 1;
+        if !ok {
+            *counters.dbus_errors.entry(service.to_string()).or_default() += 1;
+        }
+        counters
+            .dbus_durations
+            .entry(service.to_string())
+            .or_default()
+            .observe(duration);
+    }
+
+    /// Record how long it took to walk all objects of a service.
+    pub fn record_crawl(&self, service: &str, duration: Duration) {
+        let mut counters = self.counters();
+        counters.crawl_durations.observe(duration);
+        counters
+            .last_crawl_durations
+            .insert(service.to_string(), duration);
+    }
+
+    pub fn record_cache_lookup(&self, cache: &str, hit: bool) {
+        *self
+            .counters()
+            .cache_lookups
+            .entry((cache.to_string(), hit))
+            .or_default() += 1;
+    }
+
+    /// The counters in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let counters = self.counters();
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "dbus_explorer_http_requests_total",
+            "counter",
+            "HTTP requests handled, by route and status code.",
+        );
+        for ((route, status), count) in &counters.requests {
+            let _ = writeln!(
+                out,
+                "dbus_explorer_http_requests_total{{route=\"{}\",status=\"{status}\"}} {count}",
+                escape_label(route)
+            );
+        }
+
+        header(
+            &mut out,
+            "dbus_explorer_http_request_duration_seconds",
+            "histogram",
+            "Time taken to handle HTTP requests, by route.",
+        );
+        for (route, histogram) in &counters.request_durations {
+            histogram.write(
+                &mut out,
+                "dbus_explorer_http_request_duration_seconds",
+                &format!("route=\"{}\"", escape_label(route)),
+            );
+        }
+
+        header(
+            &mut out,
+            "dbus_explorer_dbus_calls_total",
+            "counter",
+            "D-Bus calls made, by destination service.",
+        );
+        for (service, count) in &counters.dbus_calls {
+            let _ = writeln!(
+                out,
+                "dbus_explorer_dbus_calls_total{{service=\"{}\"}} {count}",
+                escape_label(service)
+            );
+        }
+
+        header(
+            &mut out,
+            "dbus_explorer_dbus_call_errors_total",
+            "counter",
+            "D-Bus calls answered with an error or not at all, by destination service.",
+        );
+        for (service, count) in &counters.dbus_errors {
+            let _ = writeln!(
+                out,
+                "dbus_explorer_dbus_call_errors_total{{service=\"{}\"}} {count}",
+                escape_label(service)
+            );
+        }
+
+        header(
+            &mut out,
+            "dbus_explorer_dbus_call_duration_seconds",
+            "histogram",
+            "Time taken by D-Bus calls, by destination service.",
+        );
+        for (service, histogram) in &counters.dbus_durations {
+            histogram.write(
+                &mut out,
+                "dbus_explorer_dbus_call_duration_seconds",
+                &format!("service=\"{}\"", escape_label(service)),
+            );
+        }
+
+        header(
+            &mut out,
+            "dbus_explorer_crawl_duration_seconds",
+            "histogram",
+            "Time taken to introspect all objects of a service.",
+        );
+        counters
+            .crawl_durations
+            .write(&mut out, "dbus_explorer_crawl_duration_seconds", "");
+
+        header(
+            &mut out,
+            "dbus_explorer_last_crawl_duration_seconds",
+            "gauge",
+            "Time taken by the latest walk of all objects of a service.",
+        );
+        for (service, duration) in &counters.last_crawl_durations {
+            let _ = writeln!(
+                out,
+                "dbus_explorer_last_crawl_duration_seconds{{service=\"{}\"}} {}",
+                escape_label(service),
+                duration.as_secs_f64()
+            );
+        }
+
+        header(
+            &mut out,
+            "dbus_explorer_cache_lookups_total",
+            "counter",
+            "Lookups in the caches of the explorer, by cache and result.",
+        );
+        for ((cache, hit), count) in &counters.cache_lookups {
+            let _ = writeln!(
+                out,
+                "dbus_explorer_cache_lookups_total{{cache=\"{}\",result=\"{}\"}} {count}",
+                escape_label(cache),
+                if *hit { "hit" } else { "miss" }
+            );
+        }
+
+        out
+    }
+}
+
+/// The bus gauges, gathered again only once they are older than the refresh interval so that
+/// frequent scrapes do not walk every service each time.
+#[derive(Debug)]
+pub struct BusMetricsCache {
+    interval: Duration,
+    /// When the gauges were gathered, and their text.
+    latest: tokio::sync::Mutex<Option<(Instant, String)>>,
+}
+
+impl BusMetricsCache {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            latest: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// The cached gauges, or new ones gathered on a blocking thread if they are out of date.
+    ///
+    /// Scrapes arriving while the gauges are gathered wait for them rather than gathering them
+    /// too.
+    pub async fn get(&self) -> anyhow::Result<String> {
+        let mut latest = self.latest.lock().await;
+        if let Some((gathered, text)) = latest.as_ref() {
+            if gathered.elapsed() < self.interval {
+                METRICS.record_cache_lookup("bus_metrics", true);
+                return Ok(text.clone());
+            }
+        }
+        METRICS.record_cache_lookup("bus_metrics", false);
+
+        let text = request_log::spawn_blocking(|| {
+            let conn = Connection::new_system().context("Failed to connect to the system bus")?;
+            render_bus_metrics(&conn)
+        })
+        .await??;
+        *latest = Some((Instant::now(), text.clone()));
+        Ok(text)
+    }
+}
+
+/// Gauges describing the bus, gathered by listing names and walking every service.
+pub fn render_bus_metrics(conn: &Connection) -> anyhow::Result<String> {
+    let started = Instant::now();
+    let names = list_names(conn)?;
+    let activatable = list_activatable_names(conn)?;
+    let (unique, well_known): (Vec<&String>, Vec<&String>) =
+        names.iter().partition(|name| name.starts_with(':'));
+
+    let mut out = String::new();
+    header(
+        &mut out,
+        "dbus_bus_names",
+        "gauge",
+        "Names currently on the bus, by kind.",
+    );
+    let _ = writeln!(
+        out,
+        "dbus_bus_names{{kind=\"well_known\"}} {}",
+        well_known.len()
+    );
+    let _ = writeln!(out, "dbus_bus_names{{kind=\"unique\"}} {}", unique.len());
+
+    header(
+        &mut out,
+        "dbus_bus_connections",
+        "gauge",
+        "Connections to the bus, one per unique name.",
+    );
+    let _ = writeln!(out, "dbus_bus_connections {}", unique.len());
+
+    header(
+        &mut out,
+        "dbus_bus_activatable_names",
+        "gauge",
+        "Names the bus can start a service for.",
+    );
+    let _ = writeln!(out, "dbus_bus_activatable_names {}", activatable.len());
+
+    let services: Vec<_> = well_known
+        .iter()
+        .map(|name| analyze_service(conn, name))
+        .collect();
+
+    header(
+        &mut out,
+        "dbus_service_objects",
+        "gauge",
+        "Objects found by introspecting a service from its root.",
+    );
+    for service in &services {
+        let _ = writeln!(
+            out,
+            "dbus_service_objects{{service=\"{}\"}} {}",
+            escape_label(&service.name),
+            service.objects.len()
+        );
+    }
+
+    header(
+        &mut out,
+        "dbus_service_object_errors",
+        "gauge",
+        "Objects of a service that could not be introspected.",
+    );
+    for service in &services {
+        let _ = writeln!(
+            out,
+            "dbus_service_object_errors{{service=\"{}\"}} {}",
+            escape_label(&service.name),
+            service.objects.iter().filter(|o| o.error.is_some()).count()
+        );
+    }
+
+    header(
+        &mut out,
+        "dbus_explorer_bus_scrape_duration_seconds",
+        "gauge",
+        "Time taken by the latest gathering of the bus metrics.",
+    );
+    let _ = writeln!(
+        out,
+        "dbus_explorer_bus_scrape_duration_seconds {}",
+        started.elapsed().as_secs_f64()
+    );
+
+    Ok(out)
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,1007 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    ffi::CString,
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use dbus::{
+    arg::messageitem::MessageItem,
+    blocking::{stdintf::org_freedesktop_dbus::RequestNameReply, Connection},
+    channel::{MatchingReceiver, Sender},
+    message::{MatchRule, MessageType},
+    strings::{ErrorName, Interface, Member},
+    Message,
+};
+use log::{debug, info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    dbus_introspection::{parse_introspection_xml, InterfaceInfo, ObjectInfo, ServiceInfo},
+    signature::{parse_single_type, DbusType},
+    utils::{
+        read_yaml_or_json, split_member_name, validate_error_name, validate_object_path,
+        validate_service_name,
+    },
+    values::{array_item, dict_item, json_to_item},
+};
+
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const INTROSPECTABLE_INTERFACE: &str = "org.freedesktop.DBus.Introspectable";
+const PEER_INTERFACE: &str = "org.freedesktop.DBus.Peer";
+
+/// Canned replies and property values, for the members whose default reply is not enough.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MockResponses {
+    #[serde(default)]
+    pub methods: Vec<MethodResponse>,
+    #[serde(default)]
+    pub properties: Vec<PropertyResponse>,
+}
+
+/// How to answer a method, on any service and object unless narrowed down.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MethodResponse {
+    pub service: Option<String>,
+    pub path: Option<String>,
+    /// `Interface.Method`.
+    pub method: String,
+    /// Replies in the order of the calls, the last one is repeated. A reply with one value is
+    /// that value, a reply with several values is an array of them, as in the `--json` output.
+    #[serde(default)]
+    pub replies: Vec<Value>,
+    /// Answer with this D-Bus error instead.
+    pub error: Option<String>,
+    pub error_message: Option<String>,
+}
+
+/// The initial value of a property.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PropertyResponse {
+    pub service: Option<String>,
+    pub path: Option<String>,
+    /// `Interface.Property`.
+    pub property: String,
+    pub value: Value,
+}
+
+/// Read the services to stand in for from a snapshot, or one object from an introspection XML file.
+///
+/// Names of the bus itself and unique names cannot be taken over, so they are left out.
+pub fn load_mock_services(
+    file: &Path,
+    service: Option<&str>,
+    path: Option<&str>,
+) -> Result<Vec<ServiceInfo>> {
+    if file.extension().is_some_and(|e| e == "xml") {
+        let (Some(service), Some(path)) = (service, path) else {
+            return Err(anyhow!(
+                "An XML file needs --service and --path to know where to export it"
+            ));
+        };
+        validate_service_name(service)?;
+        validate_object_path(path)?;
+        let xml = fs::read_to_string(file)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        let (interfaces, child_nodes) = parse_introspection_xml(&xml)
+            .with_context(|| format!("Failed to parse {}", file.display()))?;
+        return Ok(vec![ServiceInfo {
+            name: service.to_string(),
+            owner: None,
+            pid: None,
+            unit: None,
+            objects: vec![ObjectInfo {
+                path: path.to_string(),
+                interfaces,
+                error: None,
+                child_nodes,
+                xml: Some(xml),
+                xml_error: None,
+                dbus_error: None,
+            }],
+            error: None,
+        }]);
+    }
+
+    let services: Vec<ServiceInfo> = read_yaml_or_json(file)?;
+    let services: Vec<ServiceInfo> = services
+        .into_iter()
+        .filter(|s| !s.name.starts_with(':') && s.name != "org.freedesktop.DBus")
+        .filter(|s| service.is_none_or(|service| s.name == service))
+        .collect();
+    if services.is_empty() {
+        return Err(anyhow!("No service to export in {}", file.display()));
+    }
+    Ok(services)
+}
+
+/// Read canned responses, checking the member and error names in them to catch typos early.
+pub fn load_responses(file: Option<&PathBuf>) -> Result<MockResponses> {
+    let Some(file) = file else {
+        return Ok(MockResponses::default());
+    };
+    let responses: MockResponses = read_yaml_or_json(file)?;
+    for name in responses
+        .methods
+        .iter()
+        .map(|r| &r.method)
+        .chain(responses.properties.iter().map(|r| &r.property))
+    {
+        split_member_name(name)?;
+    }
+    for error in responses.methods.iter().filter_map(|r| r.error.as_ref()) {
+        validate_error_name(error)?;
+    }
+    Ok(responses)
+}
+
+/// Export every service on its own connection and answer calls until interrupted.
+pub fn run_mock(services: Vec<ServiceInfo>, responses: MockResponses, session: bool) -> Result<()> {
+    let mut handles = Vec::new();
+    for service in services {
+        let conn = if session {
+            Connection::new_session()
+        } else {
+            Connection::new_system()
+        }
+        .context("Failed to connect to the bus")?;
+        let reply = conn
+            .request_name(service.name.as_str(), false, true, true)
+            .with_context(|| format!("Failed to own {}", service.name))?;
+        // Without queueing the bus refuses names that are taken, rather than failing the call
+        if !matches!(
+            reply,
+            RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner
+        ) {
+            return Err(anyhow!(
+                "Failed to own {}, it is already owned by another connection",
+                service.name
+            ));
+        }
+        info!(
+            "Exporting {} with {} object(s)",
+            service.name,
+            service.objects.len()
+        );
+
+        let mut mock = MockService::new(service, &responses);
+        conn.start_receive(
+            MatchRule::new_method_call(),
+            Box::new(move |message, conn| {
+                if let Some(reply) = mock.handle(&message, conn) {
+                    let _ = conn.send(reply);
+                }
+                true
+            }),
+        );
+        handles.push(thread::spawn(move || -> Result<()> {
+            loop {
+                conn.process(Duration::from_secs(1))?;
+            }
+        }));
+    }
+
+    for handle in handles {
+        handle
+            .join()
+            .map_err(|_| anyhow!("A mock service panicked"))??;
+    }
+    Ok(())
+}
+
+/// The objects of one service and the state of their properties.
+struct MockService {
+    name: String,
+    objects: BTreeMap<String, ObjectInfo>,
+    responses: Vec<MethodResponse>,
+    /// Calls answered so far, by path, interface and method, to pick the next reply.
+    calls: HashMap<(String, String, String), usize>,
+    /// By path, interface and property.
+    properties: BTreeMap<(String, String, String), MessageItem>,
+}
+
+impl MockService {
+    fn new(service: ServiceInfo, responses: &MockResponses) -> Self {
+        let name = service.name;
+        let applies = |service: &Option<String>, path: &Option<String>, object_path: &str| {
+            service.as_ref().is_none_or(|s| *s == name)
+                && path.as_ref().is_none_or(|p| p == object_path)
+        };
+
+        let mut properties = BTreeMap::new();
+        for object in service.objects.iter().filter(|o| o.error.is_none()) {
+            for interface in &object.interfaces {
+                for property in &interface.properties {
+                    let canned = responses.properties.iter().rev().find(|r| {
+                        applies(&r.service, &r.path, &object.path)
+                            && r.property == format!("{}.{}", interface.name, property.name)
+                    });
+                    let value = match canned {
+                        Some(canned) => value_item(&canned.value, &property.type_name),
+                        None => default_item(&property.type_name),
+                    };
+                    match value {
+                        Ok(value) => {
+                            properties.insert(
+                                (
+                                    object.path.clone(),
+                                    interface.name.clone(),
+                                    property.name.clone(),
+                                ),
+                                value,
+                            );
+                        }
+                        Err(e) => warn!(
+                            "No value for {}.{} on {}: {e:#}",
+                            interface.name, property.name, object.path
+                        ),
+                    }
+                }
+            }
+        }
+
+        Self {
+            objects: service
+                .objects
+                .into_iter()
+                .filter(|o| o.error.is_none())
+                .map(|o| (o.path.clone(), o))
+                .collect(),
+            responses: responses
+                .methods
+                .iter()
+                .filter(|r| r.service.as_ref().is_none_or(|s| *s == name))
+                .cloned()
+                .collect(),
+            calls: HashMap::new(),
+            properties,
+            name,
+        }
+    }
+
+    /// The reply to a method call, `None` if the caller asked for no reply.
+    fn handle(&mut self, message: &Message, conn: &Connection) -> Option<Message> {
+        if message.msg_type() != MessageType::MethodCall {
+            return None;
+        }
+        let path = message.path()?.to_string();
+        let interface = message.interface().map(|i| i.to_string());
+        let member = message.member()?.to_string();
+        debug!(
+            "{} {path} {}.{member}",
+            self.name,
+            interface.as_deref().unwrap_or("*")
+        );
+
+        let mut signals = Vec::new();
+        let reply = self.reply(
+            &path,
+            interface.as_deref(),
+            &member,
+            &message.get_items(),
+            &mut signals,
+        );
+        for signal in signals {
+            let _ = conn.send(signal);
+        }
+        if message.get_no_reply() {
+            return None;
+        }
+        Some(match reply {
+            Ok(items) => {
+                let mut reply = message.method_return();
+                reply.append_items(&items);
+                reply
+            }
+            Err(fault) => fault.reply_to(message),
+        })
+    }
+
+    /// The reply values or error, with any signals the call causes added to `signals`.
+    fn reply(
+        &mut self,
+        path: &str,
+        interface: Option<&str>,
+        member: &str,
+        arguments: &[MessageItem],
+        signals: &mut Vec<Message>,
+    ) -> std::result::Result<Vec<MessageItem>, Fault> {
+        match (interface, member) {
+            (Some(INTROSPECTABLE_INTERFACE) | None, "Introspect") => {
+                return self
+                    .introspect(path)
+                    .map(|xml| vec![MessageItem::Str(xml)])
+                    .ok_or_else(|| unknown_object(path));
+            }
+            (Some(PEER_INTERFACE) | None, "Ping") => return Ok(Vec::new()),
+            (Some(PEER_INTERFACE) | None, "GetMachineId") => {
+                let id = fs::read_to_string("/etc/machine-id").unwrap_or_default();
+                return Ok(vec![MessageItem::Str(id.trim().to_string())]);
+            }
+            _ => {}
+        }
+
+        let object = self.objects.get(path).ok_or_else(|| unknown_object(path))?;
+
+        if interface == Some(PROPERTIES_INTERFACE) {
+            return self.properties_call(path, member, arguments, signals);
+        }
+
+        let (interface_info, method) = object
+            .interfaces
+            .iter()
+            .filter(|i| interface.is_none_or(|name| name == i.name))
+            .find_map(|i| i.methods.iter().find(|m| m.name == member).map(|m| (i, m)))
+            .ok_or_else(|| {
+                Fault::new(
+                    "org.freedesktop.DBus.Error.UnknownMethod",
+                    format!("No method {}.{member} on {path}", interface.unwrap_or("*")),
+                )
+            })?;
+
+        let expected: String = method
+            .arguments
+            .iter()
+            .map(|a| a.type_name.as_str())
+            .collect();
+        let actual: String = arguments
+            .iter()
+            .map(|a| a.signature().to_string())
+            .collect();
+        if expected != actual {
+            return Err(Fault::new(
+                "org.freedesktop.DBus.Error.InvalidArgs",
+                format!("Expected arguments of type {expected} but got {actual}"),
+            ));
+        }
+
+        let key = (
+            path.to_string(),
+            interface_info.name.clone(),
+            member.to_string(),
+        );
+        let count = self.calls.entry(key).or_default();
+        let call_index = *count;
+        *count += 1;
+
+        let method_name = format!("{}.{member}", interface_info.name);
+        let response = self
+            .responses
+            .iter()
+            .rev()
+            .find(|r| r.method == method_name && r.path.as_ref().is_none_or(|p| p == path));
+        let return_types: Vec<&str> = method
+            .return_values
+            .iter()
+            .map(|r| r.type_name.as_str())
+            .collect();
+        let failed =
+            |e: anyhow::Error| Fault::new("org.freedesktop.DBus.Error.Failed", format!("{e:#}"));
+
+        match response {
+            Some(MethodResponse {
+                error: Some(error),
+                error_message,
+                ..
+            }) => Err(Fault {
+                name: error.clone(),
+                message: error_message.clone().unwrap_or_default(),
+            }),
+            Some(response) if !response.replies.is_empty() => {
+                let reply = &response.replies[call_index.min(response.replies.len() - 1)];
+                reply_items(reply, &return_types).map_err(failed)
+            }
+            _ => return_types
+                .iter()
+                .map(|type_name| default_item(type_name))
+                .collect::<Result<Vec<_>>>()
+                .map_err(failed),
+        }
+    }
+
+    fn properties_call(
+        &mut self,
+        path: &str,
+        member: &str,
+        arguments: &[MessageItem],
+        signals: &mut Vec<Message>,
+    ) -> std::result::Result<Vec<MessageItem>, Fault> {
+        let object = &self.objects[path];
+        let find = |interface: &str, property: &str| {
+            object
+                .interfaces
+                .iter()
+                .filter(|i| i.name == interface)
+                .flat_map(|i| &i.properties)
+                .find(|p| p.name == property)
+                .ok_or_else(|| {
+                    Fault::new(
+                        "org.freedesktop.DBus.Error.UnknownProperty",
+                        format!("No property {interface}.{property} on {path}"),
+                    )
+                })
+        };
+        let key = |interface: &str, property: &str| {
+            (
+                path.to_string(),
+                interface.to_string(),
+                property.to_string(),
+            )
+        };
+        let unavailable = |interface: &str, property: &str| {
+            Fault::new(
+                "org.freedesktop.DBus.Error.Failed",
+                format!("No value for {interface}.{property}"),
+            )
+        };
+
+        match (member, arguments) {
+            ("Get", [MessageItem::Str(interface), MessageItem::Str(property)]) => {
+                let info = find(interface, property)?;
+                if !info.access.contains("read") {
+                    return Err(write_only(interface, property));
+                }
+                let value = self
+                    .properties
+                    .get(&key(interface, property))
+                    .ok_or_else(|| unavailable(interface, property))?;
+                Ok(vec![MessageItem::Variant(Box::new(value.clone()))])
+            }
+            ("GetAll", [MessageItem::Str(interface)]) => {
+                let interface_info = object
+                    .interfaces
+                    .iter()
+                    .find(|i| i.name == *interface)
+                    .ok_or_else(|| unknown_interface(interface, path))?;
+                let entries = readable_values(interface_info, path, &self.properties);
+                Ok(vec![vardict(entries)])
+            }
+            (
+                "Set",
+                [MessageItem::Str(interface), MessageItem::Str(property), MessageItem::Variant(value)],
+            ) => {
+                let info = find(interface, property)?;
+                if !info.access.contains("write") {
+                    return Err(read_only(interface, property));
+                }
+                if value.signature().to_string() != info.type_name {
+                    return Err(Fault::new(
+                        "org.freedesktop.DBus.Error.InvalidArgs",
+                        format!(
+                            "{interface}.{property} is of type {} but got {}",
+                            info.type_name,
+                            value.signature()
+                        ),
+                    ));
+                }
+                self.properties
+                    .insert(key(interface, property), (**value).clone());
+                signals.extend(properties_changed(path, interface, property, value));
+                Ok(Vec::new())
+            }
+            _ => Err(Fault::new(
+                "org.freedesktop.DBus.Error.InvalidArgs",
+                format!("Unexpected arguments for {PROPERTIES_INTERFACE}.{member}"),
+            )),
+        }
+    }
+
+    /// The recorded introspection XML, or for a path above the objects, a node listing children.
+    fn introspect(&self, path: &str) -> Option<String> {
+        if let Some(xml) = self.objects.get(path).and_then(|o| o.xml.clone()) {
+            return Some(xml);
+        }
+
+        let prefix = if path == "/" {
+            "/".to_string()
+        } else {
+            format!("{path}/")
+        };
+        let children: BTreeSet<&str> = self
+            .objects
+            .keys()
+            .filter_map(|p| p.strip_prefix(&prefix))
+            .filter_map(|rest| rest.split('/').next())
+            .filter(|child| !child.is_empty())
+            .collect();
+        if children.is_empty() && !self.objects.contains_key(path) {
+            return None;
+        }
+
+        let mut xml = String::from(
+            "<!DOCTYPE node PUBLIC \"-//freedesktop//DTD D-BUS Object Introspection 1.0//EN\"\n \"http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd\">\n<node>\n",
+        );
+        for child in children {
+            xml.push_str(&format!("  <node name=\"{child}\"/>\n"));
+        }
+        xml.push_str("</node>\n");
+        Some(xml)
+    }
+}
+
+fn readable_values(
+    interface: &InterfaceInfo,
+    path: &str,
+    properties: &BTreeMap<(String, String, String), MessageItem>,
+) -> Vec<(MessageItem, MessageItem)> {
+    interface
+        .properties
+        .iter()
+        .filter(|p| p.access.contains("read"))
+        .filter_map(|p| {
+            let value =
+                properties.get(&(path.to_string(), interface.name.clone(), p.name.clone()))?;
+            Some((
+                MessageItem::Str(p.name.clone()),
+                MessageItem::Variant(Box::new(value.clone())),
+            ))
+        })
+        .collect()
+}
+
+fn vardict(entries: Vec<(MessageItem, MessageItem)>) -> MessageItem {
+    dict_item(entries, &DbusType::String, &DbusType::Variant)
+        .expect("String keys and variant values form a valid dict")
+}
+
+fn properties_changed(
+    path: &str,
+    interface: &str,
+    property: &str,
+    value: &MessageItem,
+) -> Option<Message> {
+    let path = dbus::Path::new(path).ok()?;
+    let mut signal = Message::signal(
+        &path,
+        &Interface::from(PROPERTIES_INTERFACE),
+        &Member::from("PropertiesChanged"),
+    );
+    let invalidated = array_item(Vec::new(), &DbusType::Array(Box::new(DbusType::String)))
+        .expect("An empty array of strings is valid");
+    signal.append_items(&[
+        MessageItem::Str(interface.to_string()),
+        vardict(vec![(
+            MessageItem::Str(property.to_string()),
+            MessageItem::Variant(Box::new(value.clone())),
+        )]),
+        invalidated,
+    ]);
+    Some(signal)
+}
+
+/// Convert a reply written as in the `--json` output to items of the given types.
+fn reply_items(reply: &Value, types: &[&str]) -> Result<Vec<MessageItem>> {
+    match (types, reply) {
+        ([], Value::Null) => Ok(Vec::new()),
+        ([type_name], value) => Ok(vec![value_item(value, type_name)?]),
+        (types, Value::Array(values)) if values.len() == types.len() => types
+            .iter()
+            .zip(values)
+            .map(|(type_name, value)| value_item(value, type_name))
+            .collect(),
+        (types, _) => Err(anyhow!(
+            "Expected a reply of {} value(s) of type {}",
+            types.len(),
+            types.concat()
+        )),
+    }
+}
+
+fn value_item(value: &Value, type_name: &str) -> Result<MessageItem> {
+    let dbus_type = parse_single_type(type_name)?;
+    Ok(json_to_item(value, &dbus_type, "reply")?)
+}
+
+/// The zero value of a type: 0, false, an empty string or container, `/`, or the signature `s`.
+fn default_item(type_name: &str) -> Result<MessageItem> {
+    let dbus_type = parse_single_type(type_name)?;
+    Ok(json_to_item(
+        &default_json(&dbus_type),
+        &dbus_type,
+        "default",
+    )?)
+}
+
+fn default_json(dbus_type: &DbusType) -> Value {
+    match dbus_type {
+        DbusType::Boolean => json!(false),
+        DbusType::String => json!(""),
+        // dbus only holds signatures of one complete type, so the empty one cannot be sent
+        DbusType::Signature => json!("s"),
+        DbusType::ObjectPath => json!("/"),
+        DbusType::Variant => json!({"type": "s", "value": ""}),
+        DbusType::Array(_) => json!([]),
+        DbusType::Dict(_, _) => json!({}),
+        DbusType::Struct(fields) => Value::Array(fields.iter().map(default_json).collect()),
+        _ => json!(0),
+    }
+}
+
+/// An error reply: its D-Bus error name and message.
+struct Fault {
+    name: String,
+    message: String,
+}
+
+impl Fault {
+    fn new(name: &str, message: String) -> Self {
+        Self {
+            name: name.to_string(),
+            message,
+        }
+    }
+
+    fn reply_to(&self, message: &Message) -> Message {
+        let name = ErrorName::new(self.name.as_str())
+            .unwrap_or_else(|_| ErrorName::from("org.freedesktop.DBus.Error.Failed"));
+        let text = CString::new(self.message.replace('\0', "")).unwrap_or_default();
+        message.error(&name, &text)
+    }
+}
+
+fn unknown_object(path: &str) -> Fault {
+    Fault::new(
+        "org.freedesktop.DBus.Error.UnknownObject",
+        format!("No object at {path}"),
+    )
+}
+
+fn unknown_interface(interface: &str, path: &str) -> Fault {
+    Fault::new(
+        "org.freedesktop.DBus.Error.UnknownInterface",
+        format!("No interface {interface} on {path}"),
+    )
+}
+
+/// Reading a write-only property is denied, there is no error name of its own for it.
+fn write_only(interface: &str, property: &str) -> Fault {
+    Fault::new(
+        "org.freedesktop.DBus.Error.AccessDenied",
+        format!("{interface}.{property} cannot be read"),
+    )
+}
+
+fn read_only(interface: &str, property: &str) -> Fault {
+    Fault::new(
+        "org.freedesktop.DBus.Error.PropertyReadOnly",
+        format!("{interface}.{property} cannot be written"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIGHT: &str = r#"<node>
+  <interface name="com.example.Light">
+    <method name="GetState"><arg type="b" direction="out"/><arg type="i" direction="out"/></method>
+    <method name="SetLevel"><arg name="level" type="i" direction="in"/></method>
+    <method name="Describe"><arg type="(sao)" direction="out"/></method>
+    <property name="Level" type="i" access="readwrite"/>
+    <property name="Model" type="s" access="read"/>
+    <property name="Secret" type="s" access="write"/>
+  </interface>
+</node>"#;
+
+    fn object(path: &str, xml: &str) -> ObjectInfo {
+        let (interfaces, child_nodes) = parse_introspection_xml(xml).unwrap();
+        ObjectInfo {
+            path: path.to_string(),
+            interfaces,
+            error: None,
+            child_nodes,
+            xml: Some(xml.to_string()),
+            xml_error: None,
+            dbus_error: None,
+        }
+    }
+
+    fn mock(objects: Vec<ObjectInfo>, responses: &str) -> MockService {
+        let service = ServiceInfo {
+            name: "com.example.Light".to_string(),
+            owner: None,
+            pid: None,
+            unit: None,
+            objects,
+            error: None,
+        };
+        MockService::new(service, &serde_yaml::from_str(responses).unwrap())
+    }
+
+    fn light(responses: &str) -> MockService {
+        mock(vec![object("/light", LIGHT)], responses)
+    }
+
+    /// Call a member of `/light`, with the error name on failure.
+    fn call(
+        mock: &mut MockService,
+        interface: &str,
+        member: &str,
+        arguments: &[MessageItem],
+    ) -> std::result::Result<Vec<MessageItem>, String> {
+        mock.reply(
+            "/light",
+            Some(interface),
+            member,
+            arguments,
+            &mut Vec::new(),
+        )
+        .map_err(|fault| fault.name)
+    }
+
+    fn get(mock: &mut MockService, property: &str) -> std::result::Result<MessageItem, String> {
+        let arguments = [
+            MessageItem::Str("com.example.Light".to_string()),
+            MessageItem::Str(property.to_string()),
+        ];
+        call(mock, PROPERTIES_INTERFACE, "Get", &arguments).map(|mut items| items.remove(0))
+    }
+
+    fn set(
+        mock: &mut MockService,
+        property: &str,
+        value: MessageItem,
+    ) -> std::result::Result<Vec<Message>, String> {
+        let arguments = [
+            MessageItem::Str("com.example.Light".to_string()),
+            MessageItem::Str(property.to_string()),
+            MessageItem::Variant(Box::new(value)),
+        ];
+        let mut signals = Vec::new();
+        mock.reply(
+            "/light",
+            Some(PROPERTIES_INTERFACE),
+            "Set",
+            &arguments,
+            &mut signals,
+        )
+        .map_err(|fault| fault.name)?;
+        Ok(signals)
+    }
+
+    #[test]
+    fn replies_in_order_then_the_last_one() {
+        let mut mock = light(
+            "methods:
+  - method: com.example.Light.GetState
+    replies: [[true, 50], [false, 0]]",
+        );
+        let state = |on, level| Ok(vec![MessageItem::Bool(on), MessageItem::Int32(level)]);
+        assert_eq!(
+            call(&mut mock, "com.example.Light", "GetState", &[]),
+            state(true, 50)
+        );
+        assert_eq!(
+            call(&mut mock, "com.example.Light", "GetState", &[]),
+            state(false, 0)
+        );
+        assert_eq!(
+            call(&mut mock, "com.example.Light", "GetState", &[]),
+            state(false, 0)
+        );
+        // Without an interface, the method is looked up on every interface
+        assert_eq!(
+            mock.reply("/light", None, "GetState", &[], &mut Vec::new())
+                .map_err(|fault| fault.name),
+            state(false, 0)
+        );
+    }
+
+    #[test]
+    fn default_replies_and_errors() {
+        let mut mock = light(
+            "methods:
+  - method: com.example.Light.SetLevel
+    path: /light
+    error: com.example.Error.Busy
+    error_message: Try again later",
+        );
+        assert_eq!(
+            call(&mut mock, "com.example.Light", "GetState", &[]),
+            Ok(vec![MessageItem::Bool(false), MessageItem::Int32(0)])
+        );
+        assert_eq!(
+            call(&mut mock, "com.example.Light", "Describe", &[]),
+            Ok(vec![MessageItem::Struct(vec![
+                MessageItem::Str(String::new()),
+                array_item(Vec::new(), &DbusType::Array(Box::new(DbusType::ObjectPath))).unwrap(),
+            ])])
+        );
+        assert_eq!(
+            call(
+                &mut mock,
+                "com.example.Light",
+                "SetLevel",
+                &[MessageItem::Int32(5)]
+            ),
+            Err("com.example.Error.Busy".to_string())
+        );
+        assert_eq!(
+            call(&mut mock, "com.example.Light", "Toggle", &[]),
+            Err("org.freedesktop.DBus.Error.UnknownMethod".to_string())
+        );
+        assert_eq!(
+            mock.reply("/dark", None, "GetState", &[], &mut Vec::new())
+                .map_err(|fault| fault.name),
+            Err("org.freedesktop.DBus.Error.UnknownObject".to_string())
+        );
+    }
+
+    #[test]
+    fn arguments_must_match_the_signature() {
+        let mut mock = light("{}");
+        for arguments in [
+            vec![],
+            vec![MessageItem::UInt32(5)],
+            vec![MessageItem::Int32(5), MessageItem::Int32(6)],
+        ] {
+            assert_eq!(
+                call(&mut mock, "com.example.Light", "SetLevel", &arguments),
+                Err("org.freedesktop.DBus.Error.InvalidArgs".to_string()),
+                "{arguments:?}"
+            );
+        }
+        assert_eq!(
+            call(
+                &mut mock,
+                "com.example.Light",
+                "SetLevel",
+                &[MessageItem::Int32(5)]
+            ),
+            Ok(vec![])
+        );
+    }
+
+    #[test]
+    fn property_access() {
+        let mut mock = light(
+            "properties:
+  - property: com.example.Light.Model
+    value: Lamp 2000",
+        );
+        assert_eq!(
+            get(&mut mock, "Model"),
+            Ok(MessageItem::Variant(Box::new(MessageItem::Str(
+                "Lamp 2000".to_string()
+            ))))
+        );
+        assert_eq!(
+            get(&mut mock, "Secret"),
+            Err("org.freedesktop.DBus.Error.AccessDenied".to_string())
+        );
+        assert_eq!(
+            get(&mut mock, "Color"),
+            Err("org.freedesktop.DBus.Error.UnknownProperty".to_string())
+        );
+        assert_eq!(
+            set(
+                &mut mock,
+                "Model",
+                MessageItem::Str("Lamp 3000".to_string())
+            )
+            .err(),
+            Some("org.freedesktop.DBus.Error.PropertyReadOnly".to_string())
+        );
+        assert_eq!(
+            set(&mut mock, "Level", MessageItem::UInt32(7)).err(),
+            Some("org.freedesktop.DBus.Error.InvalidArgs".to_string())
+        );
+
+        // Writes are kept and announced
+        let signals = set(&mut mock, "Level", MessageItem::Int32(7)).unwrap();
+        assert_eq!(signals.len(), 1);
+        assert_eq!(signals[0].member().as_deref(), Some("PropertiesChanged"));
+        assert_eq!(
+            get(&mut mock, "Level"),
+            Ok(MessageItem::Variant(Box::new(MessageItem::Int32(7))))
+        );
+        assert!(set(&mut mock, "Secret", MessageItem::Str("x".to_string())).is_ok());
+
+        // GetAll leaves out what cannot be read
+        let all = call(
+            &mut mock,
+            PROPERTIES_INTERFACE,
+            "GetAll",
+            &[MessageItem::Str("com.example.Light".to_string())],
+        )
+        .unwrap();
+        let MessageItem::Dict(dict) = &all[0] else {
+            panic!("Expected a dict but got {all:?}");
+        };
+        let names: Vec<_> = dict.iter().map(|(name, _)| name.clone()).collect();
+        assert_eq!(
+            names,
+            vec![
+                MessageItem::Str("Level".to_string()),
+                MessageItem::Str("Model".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn parent_nodes_are_synthesized() {
+        let mock = mock(
+            vec![
+                object("/com/example/light", LIGHT),
+                object("/com/example/lights/2", LIGHT),
+            ],
+            "{}",
+        );
+        let children = |path: &str| {
+            let xml = mock.introspect(path)?;
+            let (interfaces, children) = parse_introspection_xml(&xml).unwrap();
+            assert!(interfaces.is_empty());
+            Some(children)
+        };
+        assert_eq!(children("/"), Some(vec!["com".to_string()]));
+        assert_eq!(
+            children("/com/example"),
+            Some(vec!["light".to_string(), "lights".to_string()])
+        );
+        assert_eq!(children("/com/example/lights"), Some(vec!["2".to_string()]));
+        assert_eq!(children("/org"), None);
+        assert_eq!(children("/com/exam"), None);
+        assert_eq!(
+            mock.introspect("/com/example/light").as_deref(),
+            Some(LIGHT)
+        );
+    }
+
+    #[test]
+    fn reply_values() {
+        assert_eq!(reply_items(&Value::Null, &[]).unwrap(), vec![]);
+        assert_eq!(
+            reply_items(&json!("on"), &["s"]).unwrap(),
+            vec![MessageItem::Str("on".to_string())]
+        );
+        // One array value is not mistaken for several values
+        assert_eq!(
+            reply_items(&json!([1, 2]), &["ai"]).unwrap(),
+            vec![array_item(
+                vec![MessageItem::Int32(1), MessageItem::Int32(2)],
+                &DbusType::Array(Box::new(DbusType::Int32))
+            )
+            .unwrap()]
+        );
+        assert_eq!(
+            reply_items(&json!([true, 50]), &["b", "i"]).unwrap(),
+            vec![MessageItem::Bool(true), MessageItem::Int32(50)]
+        );
+        assert_eq!(
+            reply_items(&json!([true]), &["b", "i"])
+                .unwrap_err()
+                .to_string(),
+            "Expected a reply of 2 value(s) of type bi"
+        );
+    }
+
+    #[test]
+    fn default_values() {
+        for (signature, value) in [
+            ("b", json!(false)),
+            ("y", json!(0)),
+            ("d", json!(0)),
+            ("s", json!("")),
+            ("g", json!("s")),
+            ("o", json!("/")),
+            ("v", json!({"type": "s", "value": ""})),
+            ("as", json!([])),
+            ("a{sv}", json!({})),
+            ("(ib(so))", json!([0, false, ["", "/"]])),
+        ] {
+            assert_eq!(
+                default_json(&parse_single_type(signature).unwrap()),
+                value,
+                "{signature}"
+            );
+            assert!(default_item(signature).is_ok(), "{signature}");
+        }
+    }
+}
@@ -0,0 +1,506 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use dbus::blocking::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    dbus_calls::{MethodCall, ObjectAction, PropertyWrite},
+    dbus_introspection::introspect_object,
+    value_view::typed_value_to_json,
+};
+
+/// The most recent method calls and property writes made through the explorer, oldest first.
+#[derive(Debug)]
+pub struct Recorder {
+    pub recording: bool,
+    capacity: usize,
+    steps: VecDeque<RecordedStep>,
+}
+
+impl Recorder {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            recording: true,
+            capacity: capacity.max(1),
+            steps: VecDeque::new(),
+        }
+    }
+
+    /// Remember an action, unless recording is paused or its inputs never made it onto the bus.
+    pub fn record(&mut self, service: &str, path: &str, action: &ObjectAction) {
+        if !self.recording {
+            return;
+        }
+        if let Some(step) = RecordedStep::from_action(service, path, action) {
+            if self.steps.len() == self.capacity {
+                self.steps.pop_front();
+            }
+            self.steps.push_back(step);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.steps.clear();
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn steps(&self) -> &VecDeque<RecordedStep> {
+        &self.steps
+    }
+
+    pub fn recording(&self) -> Recording {
+        Recording {
+            steps: self.steps.iter().cloned().collect(),
+        }
+    }
+}
+
+/// A sequence of steps that can be saved and replayed with `dbus_explorer replay`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub steps: Vec<RecordedStep>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepKind {
+    Call,
+    SetProperty,
+}
+
+/// One method call or property write, with the inputs as entered and how it was answered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedStep {
+    pub kind: StepKind,
+    pub service: String,
+    pub path: String,
+    pub interface: String,
+    /// The method or property.
+    pub member: String,
+    pub inputs: Vec<String>,
+    /// The reply values with their types, empty for property writes.
+    #[serde(default)]
+    pub reply: Vec<Value>,
+    #[serde(default)]
+    pub error: Option<RecordedError>,
+    /// Milliseconds since the Unix epoch.
+    #[serde(default)]
+    pub timestamp_ms: u64,
+    #[serde(default)]
+    pub duration_ms: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedError {
+    /// The D-Bus error name, if the error came from the bus.
+    pub name: Option<String>,
+    pub message: String,
+}
+
+impl RecordedStep {
+    /// `None` if the inputs could not be converted, so nothing was sent, or for signal emissions,
+    /// which have no outcome to compare on replay.
+    pub fn from_action(service: &str, path: &str, action: &ObjectAction) -> Option<Self> {
+        let (kind, interface, member, inputs, reply, error_name, duration) = match action {
+            ObjectAction::Call(MethodCall {
+                interface,
+                method,
+                inputs,
+                arguments,
+                reply,
+                error_name,
+                duration,
+            }) => {
+                arguments.as_ref()?;
+                let reply = reply
+                    .as_ref()
+                    .map(|items| items.iter().map(typed_value_to_json).collect());
+                (
+                    StepKind::Call,
+                    interface,
+                    method,
+                    inputs.clone(),
+                    reply,
+                    error_name,
+                    *duration,
+                )
+            }
+            ObjectAction::SetProperty(PropertyWrite {
+                interface,
+                property,
+                input,
+                value,
+                result,
+                error_name,
+                duration,
+            }) => {
+                value.as_ref()?;
+                (
+                    StepKind::SetProperty,
+                    interface,
+                    property,
+                    vec![input.clone()],
+                    result.as_ref().map(|_| Vec::new()),
+                    error_name,
+                    *duration,
+                )
+            }
+            ObjectAction::EmitSignal(_) => return None,
+        };
+
+        let (reply, error) = match reply {
+            Ok(reply) => (reply, None),
+            Err(message) => (
+                Vec::new(),
+                Some(RecordedError {
+                    name: error_name.clone(),
+                    message: message.clone(),
+                }),
+            ),
+        };
+        Some(Self {
+            kind,
+            service: service.to_string(),
+            path: path.to_string(),
+            interface: interface.clone(),
+            member: member.clone(),
+            inputs,
+            reply,
+            error,
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            duration_ms: duration_ms(duration),
+        })
+    }
+}
+
+/// The outcome of replaying one step.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayedStep {
+    pub expected: RecordedStep,
+    /// `Err` if the step could not be made at all, e.g. because the object is gone.
+    pub actual: std::result::Result<RecordedStep, String>,
+    /// How the outcome differs from the recording, empty if it does not.
+    pub divergences: Vec<String>,
+}
+
+/// Make every step of a recording again and compare the outcomes with the recorded ones.
+pub fn replay(conn: &Connection, recording: &Recording) -> Vec<ReplayedStep> {
+    recording
+        .steps
+        .iter()
+        .map(|expected| {
+            let actual = replay_step(conn, expected).map_err(|e| format!("{e:#}"));
+            let divergences = match &actual {
+                Ok(actual) => divergences(expected, actual),
+                Err(e) => vec![e.clone()],
+            };
+            ReplayedStep {
+                expected: expected.clone(),
+                actual,
+                divergences,
+            }
+        })
+        .collect()
+}
+
+fn replay_step(conn: &Connection, step: &RecordedStep) -> Result<RecordedStep> {
+    let object = introspect_object(conn, &step.service, &step.path)
+        .ok_or_else(|| anyhow!("Object not found: {}:{}", step.service, step.path))?;
+    if let Some(error) = &object.error {
+        return Err(anyhow!(
+            "Failed to introspect {}:{}: {error}",
+            step.service,
+            step.path
+        ));
+    }
+    let interface = object
+        .interfaces
+        .iter()
+        .find(|i| i.name == step.interface)
+        .ok_or_else(|| anyhow!("Interface not found: {}", step.interface))?;
+
+    let action = match step.kind {
+        StepKind::Call => {
+            let method = interface
+                .methods
+                .iter()
+                .find(|m| m.name == step.member)
+                .ok_or_else(|| anyhow!("Method not found: {}.{}", step.interface, step.member))?;
+            ObjectAction::Call(MethodCall::execute(
+                conn,
+                &step.service,
+                &step.path,
+                &step.interface,
+                method,
+                step.inputs.clone(),
+            ))
+        }
+        StepKind::SetProperty => {
+            let property = interface
+                .properties
+                .iter()
+                .find(|p| p.name == step.member)
+                .ok_or_else(|| anyhow!("Property not found: {}.{}", step.interface, step.member))?;
+            let input = step
+                .inputs
+                .first()
+                .ok_or_else(|| anyhow!("No value recorded for {}", step.member))?;
+            ObjectAction::SetProperty(PropertyWrite::execute(
+                conn,
+                &step.service,
+                &step.path,
+                &step.interface,
+                property,
+                input.clone(),
+            ))
+        }
+    };
+
+    RecordedStep::from_action(&step.service, &step.path, &action)
+        .ok_or_else(|| anyhow!("The recorded inputs no longer match the signature"))
+}
+
+/// Differences in outcome; error messages are not compared as they name connections.
+fn divergences(expected: &RecordedStep, actual: &RecordedStep) -> Vec<String> {
+    match (&expected.error, &actual.error) {
+        (None, None) if expected.reply != actual.reply => {
+            let text = |reply: &[Value]| Value::Array(reply.to_vec()).to_string();
+            vec![format!(
+                "Expected reply {} but got {}",
+                text(&expected.reply),
+                text(&actual.reply)
+            )]
+        }
+        (None, None) => Vec::new(),
+        (None, Some(error)) => vec![format!("Expected success but got {}", error.message)],
+        (Some(error), None) => vec![format!(
+            "Expected error {} but the step succeeded",
+            error.name.as_deref().unwrap_or(&error.message)
+        )],
+        (Some(expected), Some(actual)) if expected.name != actual.name => vec![format!(
+            "Expected error {} but got {}",
+            expected.name.as_deref().unwrap_or("without name"),
+            actual.name.as_deref().unwrap_or("without name")
+        )],
+        (Some(_), Some(_)) => Vec::new(),
+    }
+}
+
+fn duration_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use dbus::arg::messageitem::MessageItem;
+    use serde_json::json;
+
+    use super::*;
+    use crate::dbus_calls::SignalEmission;
+
+    fn call(method: &str, converted: bool, reply: std::result::Result<u32, &str>) -> ObjectAction {
+        ObjectAction::Call(MethodCall {
+            interface: "com.example.Thing".to_string(),
+            method: method.to_string(),
+            inputs: vec!["1".to_string()],
+            arguments: converted.then(|| vec![MessageItem::UInt32(1)]),
+            reply: reply
+                .map(|value| vec![MessageItem::UInt32(value)])
+                .map_err(str::to_string),
+            error_name: reply.err().map(|_| "com.example.Error.Failed".to_string()),
+            duration: Duration::from_millis(3),
+        })
+    }
+
+    fn step(reply: Vec<Value>, error: Option<(Option<&str>, &str)>) -> RecordedStep {
+        RecordedStep {
+            kind: StepKind::Call,
+            service: "com.example.Thing".to_string(),
+            path: "/thing".to_string(),
+            interface: "com.example.Thing".to_string(),
+            member: "Get".to_string(),
+            inputs: Vec::new(),
+            reply,
+            error: error.map(|(name, message)| RecordedError {
+                name: name.map(str::to_string),
+                message: message.to_string(),
+            }),
+            timestamp_ms: 0,
+            duration_ms: 0.0,
+        }
+    }
+
+    fn members(recorder: &Recorder) -> Vec<&str> {
+        recorder.steps().iter().map(|s| s.member.as_str()).collect()
+    }
+
+    #[test]
+    fn keeps_only_the_most_recent_steps() {
+        let mut recorder = Recorder::new(2);
+        for method in ["A", "B", "C"] {
+            recorder.record("com.example.Thing", "/thing", &call(method, true, Ok(1)));
+        }
+        assert_eq!(members(&recorder), ["B", "C"]);
+        assert_eq!(recorder.recording().steps.len(), 2);
+
+        recorder.clear();
+        assert!(recorder.steps().is_empty());
+        assert_eq!(Recorder::new(0).capacity(), 1);
+    }
+
+    #[test]
+    fn pausing_stops_recording() {
+        let mut recorder = Recorder::new(10);
+        recorder.recording = false;
+        recorder.record("com.example.Thing", "/thing", &call("A", true, Ok(1)));
+        assert!(recorder.steps().is_empty());
+
+        recorder.recording = true;
+        recorder.record("com.example.Thing", "/thing", &call("B", true, Ok(1)));
+        assert_eq!(members(&recorder), ["B"]);
+    }
+
+    #[test]
+    fn skips_unconverted_inputs_and_signals() {
+        let mut recorder = Recorder::new(10);
+        recorder.record("com.example.Thing", "/thing", &call("A", false, Ok(1)));
+        recorder.record(
+            "com.example.Thing",
+            "/thing",
+            &ObjectAction::SetProperty(PropertyWrite {
+                interface: "com.example.Thing".to_string(),
+                property: "Name".to_string(),
+                input: "[".to_string(),
+                value: None,
+                result: Err("Invalid value".to_string()),
+                error_name: None,
+                duration: Duration::ZERO,
+            }),
+        );
+        recorder.record(
+            "com.example.Thing",
+            "/thing",
+            &ObjectAction::EmitSignal(SignalEmission {
+                interface: "com.example.Thing".to_string(),
+                signal: "Changed".to_string(),
+                inputs: Vec::new(),
+                arguments: Some(Vec::new()),
+                result: Ok(()),
+            }),
+        );
+        assert!(recorder.steps().is_empty());
+    }
+
+    #[test]
+    fn steps_from_actions() {
+        let step =
+            RecordedStep::from_action("com.example.Thing", "/thing", &call("A", true, Ok(7)))
+                .unwrap();
+        assert_eq!(step.kind, StepKind::Call);
+        assert_eq!(
+            (step.service.as_str(), step.path.as_str()),
+            ("com.example.Thing", "/thing")
+        );
+        assert_eq!(step.inputs, ["1"]);
+        assert_eq!(step.reply, [typed_value_to_json(&MessageItem::UInt32(7))]);
+        assert_eq!(step.error, None);
+        assert_eq!(step.duration_ms, 3.0);
+
+        let step = RecordedStep::from_action(
+            "com.example.Thing",
+            "/thing",
+            &call("A", true, Err("It failed")),
+        )
+        .unwrap();
+        assert!(step.reply.is_empty());
+        assert_eq!(
+            step.error,
+            Some(RecordedError {
+                name: Some("com.example.Error.Failed".to_string()),
+                message: "It failed".to_string(),
+            })
+        );
+
+        let write = ObjectAction::SetProperty(PropertyWrite {
+            interface: "com.example.Thing".to_string(),
+            property: "Name".to_string(),
+            input: "thing".to_string(),
+            value: Some(MessageItem::Str("thing".to_string())),
+            result: Ok(()),
+            error_name: None,
+            duration: Duration::ZERO,
+        });
+        let step = RecordedStep::from_action("com.example.Thing", "/thing", &write).unwrap();
+        assert_eq!(step.kind, StepKind::SetProperty);
+        assert_eq!(
+            (step.member.as_str(), step.inputs.as_slice()),
+            ("Name", &["thing".to_string()][..])
+        );
+        assert!(step.reply.is_empty() && step.error.is_none());
+    }
+
+    #[test]
+    fn divergences_between_outcomes() {
+        let failed = |name| step(Vec::new(), Some((name, "It failed")));
+        let cases = [
+            (
+                step(vec![json!(1)], None),
+                step(vec![json!(1)], None),
+                vec![],
+            ),
+            (
+                step(vec![json!(1)], None),
+                step(vec![json!(2)], None),
+                vec!["Expected reply [1] but got [2]"],
+            ),
+            (
+                step(Vec::new(), None),
+                failed(Some("com.example.Error.Failed")),
+                vec!["Expected success but got It failed"],
+            ),
+            (
+                failed(Some("com.example.Error.Failed")),
+                step(Vec::new(), None),
+                vec!["Expected error com.example.Error.Failed but the step succeeded"],
+            ),
+            (
+                failed(None),
+                step(Vec::new(), None),
+                vec!["Expected error It failed but the step succeeded"],
+            ),
+            (
+                failed(Some("com.example.Error.Failed")),
+                failed(None),
+                vec!["Expected error com.example.Error.Failed but got without name"],
+            ),
+            (
+                failed(Some("com.example.Error.Failed")),
+                step(
+                    Vec::new(),
+                    Some((Some("com.example.Error.Failed"), "Other text")),
+                ),
+                vec![],
+            ),
+        ];
+        for (expected, actual, divergence) in cases {
+            assert_eq!(
+                divergences(&expected, &actual),
+                divergence,
+                "{:?} {:?}",
+                expected.error,
+                actual.error
+            );
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{rejection::RawPathParamsRejection, MatchedPath, RawPathParams, Request},
+    middleware::Next,
+    response::Response,
+};
+use log::{debug, info};
+use tokio::task::JoinHandle;
+
+use crate::metrics::METRICS;
+
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+tokio::task_local! {
+    static REQUEST: Arc<RequestContext>;
+}
+
+/// The request being handled, attached to everything logged while handling it.
+#[derive(Debug)]
+pub struct RequestContext {
+    pub id: u64,
+    /// The route pattern that matched, e.g. `/local/dbus_explorer/app/service/{service_name}`.
+    pub route: String,
+    pub service: Option<String>,
+    pub path: Option<String>,
+    /// Number of D-Bus calls made so far and the time spent waiting for them, from any thread.
+    dbus_calls: Mutex<(u32, Duration)>,
+}
+
+impl fmt::Display for RequestContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request {} route={}", self.id, self.route)?;
+        if let Some(service) = &self.service {
+            write!(f, " service={service}")?;
+        }
+        if let Some(path) = &self.path {
+            write!(f, " path={path}")?;
+        }
+        Ok(())
+    }
+}
+
+impl RequestContext {
+    fn calls(&self) -> (u32, Duration) {
+        *self.dbus_calls.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The request being handled by the current task, to hand to threads working on its behalf.
+pub fn current_request() -> Option<Arc<RequestContext>> {
+    REQUEST.try_with(Arc::clone).ok()
+}
+
+/// Run `work` as part of `request`, so that the D-Bus calls it makes are logged and counted with
+/// the request even on another thread.
+pub fn in_request<T>(request: Option<Arc<RequestContext>>, work: impl FnOnce() -> T) -> T {
+    match request {
+        Some(request) => REQUEST.sync_scope(request, work),
+        None => work(),
+    }
+}
+
+/// Like [`tokio::task::spawn_blocking`], but as part of the current request.
+pub fn spawn_blocking<T: Send + 'static>(
+    work: impl FnOnce() -> T + Send + 'static,
+) -> JoinHandle<T> {
+    let request = current_request();
+    tokio::task::spawn_blocking(move || in_request(request, work))
+}
+
+/// Give each request an ID, log its start and, as the access log, its outcome and duration.
+///
+/// Installed with `route_layer` so that the matched route and its parameters are known.
+pub async fn log_requests(
+    params: Result<RawPathParams, RawPathParamsRejection>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let param = |name: &str| {
+        params.as_ref().ok().and_then(|params| {
+            params
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        })
+    };
+    let context = RequestContext {
+        id: NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed),
+        route: request
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|path| path.as_str().to_string())
+            .unwrap_or_else(|| request.uri().path().to_string()),
+        service: param("service_name"),
+        path: param("object_path").map(|path| format!("/{path}")),
+        dbus_calls: Mutex::new((0, Duration::ZERO)),
+    };
+
+    let method = request.method().clone();
+    let uri = request.uri().clone();
+    debug!("{context} started");
+
+    let started = Instant::now();
+    REQUEST
+        .scope(Arc::new(context), async move {
+            let response = next.run(request).await;
+            REQUEST.with(|context| {
+                let elapsed = started.elapsed();
+                METRICS.record_request(&context.route, response.status().as_u16(), elapsed);
+                let (calls, waited) = context.calls();
+                info!(
+                    "{context} {method} {uri} {} in {:.1?}, {calls} D-Bus call(s) taking {waited:.1?}",
+                    response.status().as_u16(),
+                    elapsed
+                );
+            });
+            response
+        })
+        .await
+}
+
+/// Make a D-Bus call and log how long it took, attributed to the current request if there is one.
+pub fn timed_call<T, E>(
+    service: &str,
+    path: &str,
+    member: &str,
+    call: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    let started = Instant::now();
+    let result = call();
+    let elapsed = started.elapsed();
+    METRICS.record_dbus_call(service, result.is_ok(), elapsed);
+
+    let outcome = if result.is_ok() { "ok" } else { "failed" };
+    let logged = REQUEST.try_with(|context| {
+        {
+            let mut dbus_calls = context.dbus_calls.lock().unwrap_or_else(|e| e.into_inner());
+            dbus_calls.0 += 1;
+            dbus_calls.1 += elapsed;
+        }
+        debug!("{context} D-Bus call {service} {path} {member} {outcome} in {elapsed:.1?}");
+    });
+    if logged.is_err() {
+        debug!("D-Bus call {service} {path} {member} {outcome} in {elapsed:.1?}");
+    }
+
+    result
+}
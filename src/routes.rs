@@ -1,18 +1,52 @@
-use axum::{routing::get, Router};
+use axum::{middleware, routing::get, Router};
 
-use crate::handlers::{all_services_page, landing_page, object_page, service_page};
+use crate::{
+    error::json_errors,
+    handlers::{
+        all_services_page, health_page, interfaces_page, landing_page, lint_page, match_rule_page,
+        metrics_page, object_form, object_page, policy_page, recording_export, recording_form,
+        recording_page, service_lint_page, service_page, stats_page,
+    },
+    request_log::log_requests,
+    state::AppState,
+};
 
-pub fn create_routes() -> Router {
+pub fn create_routes(state: AppState) -> Router {
     Router::new()
+        .route("/metrics", get(metrics_page))
         .route("/local/dbus_explorer/app", get(landing_page))
         .route("/local/dbus_explorer/app/", get(landing_page))
         .route("/local/dbus_explorer/app/all", get(all_services_page))
+        .route("/local/dbus_explorer/app/interfaces", get(interfaces_page))
+        .route("/local/dbus_explorer/app/health", get(health_page))
+        .route("/local/dbus_explorer/app/lint", get(lint_page))
+        .route("/local/dbus_explorer/app/stats", get(stats_page))
+        .route("/local/dbus_explorer/app/match", get(match_rule_page))
+        .route(
+            "/local/dbus_explorer/app/recording",
+            get(recording_page).post(recording_form),
+        )
+        .route(
+            "/local/dbus_explorer/app/recording/export",
+            get(recording_export),
+        )
+        .route(
+            "/local/dbus_explorer/app/lint/{service_name}",
+            get(service_lint_page),
+        )
+        .route(
+            "/local/dbus_explorer/app/policy/{service_name}",
+            get(policy_page),
+        )
         .route(
             "/local/dbus_explorer/app/service/{service_name}",
             get(service_page),
         )
         .route(
             "/local/dbus_explorer/app/service/{service_name}/{*object_path}",
-            get(object_page),
+            get(object_page).post(object_form),
         )
+        .route_layer(middleware::from_fn(log_requests))
+        .layer(middleware::from_fn(json_errors))
+        .with_state(state)
 }
@@ -0,0 +1,412 @@
+use std::fmt;
+
+use thiserror::Error;
+
+/// A single complete D-Bus type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DbusType {
+    Byte,
+    Boolean,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Double,
+    String,
+    ObjectPath,
+    Signature,
+    UnixFd,
+    Variant,
+    Array(Box<DbusType>),
+    Dict(Box<DbusType>, Box<DbusType>),
+    Struct(Vec<DbusType>),
+}
+
+impl DbusType {
+    pub fn is_basic(&self) -> bool {
+        !matches!(
+            self,
+            DbusType::Variant | DbusType::Array(_) | DbusType::Dict(_, _) | DbusType::Struct(_)
+        )
+    }
+
+    /// Human-readable name, as used in the type reference.
+    pub fn describe(&self) -> String {
+        match self {
+            DbusType::Byte => "byte".to_string(),
+            DbusType::Boolean => "boolean".to_string(),
+            DbusType::Int16 => "int16".to_string(),
+            DbusType::UInt16 => "uint16".to_string(),
+            DbusType::Int32 => "int32".to_string(),
+            DbusType::UInt32 => "uint32".to_string(),
+            DbusType::Int64 => "int64".to_string(),
+            DbusType::UInt64 => "uint64".to_string(),
+            DbusType::Double => "double".to_string(),
+            DbusType::String => "string".to_string(),
+            DbusType::ObjectPath => "object path".to_string(),
+            DbusType::Signature => "signature".to_string(),
+            DbusType::UnixFd => "file descriptor".to_string(),
+            DbusType::Variant => "variant".to_string(),
+            DbusType::Array(element) => format!("array of {}", element.describe()),
+            DbusType::Dict(key, value) => {
+                format!("dict of {} to {}", key.describe(), value.describe())
+            }
+            DbusType::Struct(fields) => format!(
+                "struct of ({})",
+                fields
+                    .iter()
+                    .map(DbusType::describe)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+impl fmt::Display for DbusType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbusType::Byte => write!(f, "y"),
+            DbusType::Boolean => write!(f, "b"),
+            DbusType::Int16 => write!(f, "n"),
+            DbusType::UInt16 => write!(f, "q"),
+            DbusType::Int32 => write!(f, "i"),
+            DbusType::UInt32 => write!(f, "u"),
+            DbusType::Int64 => write!(f, "x"),
+            DbusType::UInt64 => write!(f, "t"),
+            DbusType::Double => write!(f, "d"),
+            DbusType::String => write!(f, "s"),
+            DbusType::ObjectPath => write!(f, "o"),
+            DbusType::Signature => write!(f, "g"),
+            DbusType::UnixFd => write!(f, "h"),
+            DbusType::Variant => write!(f, "v"),
+            DbusType::Array(element) => write!(f, "a{element}"),
+            DbusType::Dict(key, value) => write!(f, "a{{{key}{value}}}"),
+            DbusType::Struct(fields) => {
+                write!(f, "(")?;
+                for field in fields {
+                    write!(f, "{field}")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Invalid signature {signature:?} at position {position}: {message}")]
+pub struct SignatureError {
+    pub signature: String,
+    pub position: usize,
+    pub message: String,
+}
+
+const MAX_SIGNATURE_LENGTH: usize = 255;
+const MAX_NESTING: usize = 32;
+
+/// Parse a signature consisting of any number of complete types.
+pub fn parse_signature(signature: &str) -> Result<Vec<DbusType>, SignatureError> {
+    let mut parser = Parser {
+        signature,
+        bytes: signature.as_bytes(),
+        position: 0,
+    };
+
+    if signature.len() > MAX_SIGNATURE_LENGTH {
+        return Err(parser.error(format!("longer than {MAX_SIGNATURE_LENGTH} characters")));
+    }
+
+    let mut types = Vec::new();
+    while parser.position < parser.bytes.len() {
+        types.push(parser.parse_type(0, 0)?);
+    }
+    Ok(types)
+}
+
+/// Parse a signature consisting of exactly one complete type.
+pub fn parse_single_type(signature: &str) -> Result<DbusType, SignatureError> {
+    let mut types = parse_signature(signature)?;
+    match types.len() {
+        1 => Ok(types.remove(0)),
+        0 => Err(SignatureError {
+            signature: signature.to_string(),
+            position: 0,
+            message: "expected a single complete type but got nothing".to_string(),
+        }),
+        n => Err(SignatureError {
+            signature: signature.to_string(),
+            position: types[0].to_string().len(),
+            message: format!("expected a single complete type but got {n}"),
+        }),
+    }
+}
+
+struct Parser<'a> {
+    signature: &'a str,
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: String) -> SignatureError {
+        SignatureError {
+            signature: self.signature.to_string(),
+            position: self.position,
+            message,
+        }
+    }
+
+    fn parse_type(
+        &mut self,
+        array_depth: usize,
+        struct_depth: usize,
+    ) -> Result<DbusType, SignatureError> {
+        let Some(&code) = self.bytes.get(self.position) else {
+            return Err(self.error("unexpected end of signature".to_string()));
+        };
+
+        let basic = match code {
+            b'y' => Some(DbusType::Byte),
+            b'b' => Some(DbusType::Boolean),
+            b'n' => Some(DbusType::Int16),
+            b'q' => Some(DbusType::UInt16),
+            b'i' => Some(DbusType::Int32),
+            b'u' => Some(DbusType::UInt32),
+            b'x' => Some(DbusType::Int64),
+            b't' => Some(DbusType::UInt64),
+            b'd' => Some(DbusType::Double),
+            b's' => Some(DbusType::String),
+            b'o' => Some(DbusType::ObjectPath),
+            b'g' => Some(DbusType::Signature),
+            b'h' => Some(DbusType::UnixFd),
+            b'v' => Some(DbusType::Variant),
+            _ => None,
+        };
+        if let Some(basic) = basic {
+            self.position += 1;
+            return Ok(basic);
+        }
+
+        match code {
+            b'a' => {
+                if array_depth == MAX_NESTING {
+                    return Err(self.error(format!("arrays nested deeper than {MAX_NESTING}")));
+                }
+                self.position += 1;
+                if self.bytes.get(self.position) == Some(&b'{') {
+                    self.parse_dict_entry(array_depth + 1, struct_depth)
+                } else {
+                    let element = self.parse_type(array_depth + 1, struct_depth)?;
+                    Ok(DbusType::Array(Box::new(element)))
+                }
+            }
+            b'(' => {
+                if struct_depth == MAX_NESTING {
+                    return Err(self.error(format!("structs nested deeper than {MAX_NESTING}")));
+                }
+                self.position += 1;
+                let mut fields = Vec::new();
+                loop {
+                    match self.bytes.get(self.position) {
+                        Some(b')') if fields.is_empty() => {
+                            return Err(self.error("structs must have at least one field".into()))
+                        }
+                        Some(b')') => {
+                            self.position += 1;
+                            return Ok(DbusType::Struct(fields));
+                        }
+                        None => return Err(self.error("unterminated struct".to_string())),
+                        Some(_) => fields.push(self.parse_type(array_depth, struct_depth + 1)?),
+                    }
+                }
+            }
+            b'{' => Err(self.error("dict entries are only allowed directly in arrays".into())),
+            b')' => Err(self.error("unexpected ')'".to_string())),
+            b'}' => Err(self.error("unexpected '}'".to_string())),
+            other => Err(self.error(format!("unknown type code {:?}", other as char))),
+        }
+    }
+
+    fn parse_dict_entry(
+        &mut self,
+        array_depth: usize,
+        struct_depth: usize,
+    ) -> Result<DbusType, SignatureError> {
+        // Dict entries count as structs towards the nesting limit
+        if struct_depth == MAX_NESTING {
+            return Err(self.error(format!("structs nested deeper than {MAX_NESTING}")));
+        }
+        // Skip '{'
+        self.position += 1;
+
+        let key_position = self.position;
+        let key = self.parse_type(array_depth, struct_depth + 1)?;
+        if !key.is_basic() {
+            self.position = key_position;
+            return Err(self.error(format!(
+                "dict keys must be basic types, not {}",
+                key.describe()
+            )));
+        }
+
+        if self.bytes.get(self.position) == Some(&b'}') {
+            return Err(self.error("dict entries must have a value type".to_string()));
+        }
+        let value = self.parse_type(array_depth, struct_depth + 1)?;
+
+        match self.bytes.get(self.position) {
+            Some(b'}') => {
+                self.position += 1;
+                Ok(DbusType::Dict(Box::new(key), Box::new(value)))
+            }
+            Some(_) => Err(self.error("dict entries must have exactly two types".to_string())),
+            None => Err(self.error("unterminated dict entry".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Where and why `signature` is rejected.
+    fn error(signature: &str) -> (usize, String) {
+        let e = parse_signature(signature).unwrap_err();
+        (e.position, e.message)
+    }
+
+    #[test]
+    fn types_round_trip() {
+        for signature in [
+            "",
+            "s",
+            "ii",
+            "as",
+            "a{sv}",
+            "aa{oa{sv}}",
+            "(ia(sv))",
+            "a(ss)v",
+            "h",
+        ] {
+            let types = parse_signature(signature).unwrap();
+            assert_eq!(
+                types.iter().map(DbusType::to_string).collect::<String>(),
+                signature
+            );
+        }
+        assert_eq!(
+            parse_single_type("a{s(ib)}").unwrap(),
+            DbusType::Dict(
+                Box::new(DbusType::String),
+                Box::new(DbusType::Struct(vec![DbusType::Int32, DbusType::Boolean]))
+            )
+        );
+    }
+
+    #[test]
+    fn nesting_limits() {
+        let arrays = |depth: usize| format!("{}y", "a".repeat(depth));
+        let structs = |depth: usize| format!("{}y{}", "(".repeat(depth), ")".repeat(depth));
+
+        assert!(parse_signature(&arrays(32)).is_ok());
+        assert_eq!(
+            error(&arrays(33)),
+            (32, "arrays nested deeper than 32".to_string())
+        );
+        assert!(parse_signature(&structs(32)).is_ok());
+        assert_eq!(
+            error(&structs(33)),
+            (32, "structs nested deeper than 32".to_string())
+        );
+        // The limits are separate, so both may be reached at once
+        assert!(parse_signature(&format!("{}{}", "a".repeat(32), structs(32))).is_ok());
+        // Dict entries count as structs
+        let dicts = format!("{}s{}", "a{s".repeat(32), "}".repeat(32));
+        assert!(parse_signature(&dicts).is_ok());
+        assert_eq!(
+            error(&format!("({dicts})")),
+            (95, "structs nested deeper than 32".to_string())
+        );
+    }
+
+    #[test]
+    fn dict_entries() {
+        assert_eq!(
+            error("a{vs}"),
+            (2, "dict keys must be basic types, not variant".to_string())
+        );
+        assert_eq!(
+            error("a{(i)s}"),
+            (
+                2,
+                "dict keys must be basic types, not struct of (int32)".to_string()
+            )
+        );
+        assert_eq!(
+            error("a{s}"),
+            (3, "dict entries must have a value type".to_string())
+        );
+        assert_eq!(
+            error("a{sss}"),
+            (4, "dict entries must have exactly two types".to_string())
+        );
+        assert_eq!(error("a{sv"), (4, "unterminated dict entry".to_string()));
+        assert_eq!(
+            error("{sv}"),
+            (
+                0,
+                "dict entries are only allowed directly in arrays".to_string()
+            )
+        );
+        assert_eq!(
+            error("(a{sv}{sv})").1,
+            "dict entries are only allowed directly in arrays"
+        );
+        assert_eq!(error("a}"), (1, "unexpected '}'".to_string()));
+    }
+
+    #[test]
+    fn structs() {
+        assert_eq!(
+            error("()"),
+            (1, "structs must have at least one field".to_string())
+        );
+        assert_eq!(error("(ii"), (3, "unterminated struct".to_string()));
+        assert_eq!(error("i)"), (1, "unexpected ')'".to_string()));
+        assert_eq!(error("a"), (1, "unexpected end of signature".to_string()));
+        assert_eq!(error("iz"), (1, "unknown type code 'z'".to_string()));
+    }
+
+    #[test]
+    fn length_limit() {
+        assert_eq!(parse_signature(&"y".repeat(255)).unwrap().len(), 255);
+        assert_eq!(
+            error(&"y".repeat(256)),
+            (0, "longer than 255 characters".to_string())
+        );
+    }
+
+    #[test]
+    fn single_types() {
+        assert_eq!(
+            parse_single_type("as"),
+            Ok(DbusType::Array(Box::new(DbusType::String)))
+        );
+        let e = parse_single_type("").unwrap_err();
+        assert_eq!(
+            (e.position, e.message.as_str()),
+            (0, "expected a single complete type but got nothing")
+        );
+        let e = parse_single_type("a{sv}i").unwrap_err();
+        assert_eq!(
+            (e.position, e.message.as_str()),
+            (5, "expected a single complete type but got 2")
+        );
+        assert_eq!(
+            e.to_string(),
+            "Invalid signature \"a{sv}i\" at position 5: expected a single complete type but got 2"
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::LazyLock;
+
+use crate::dbus_introspection::{parse_introspection_xml, InterfaceInfo};
+
+/// Reference definitions of the standard interfaces and those of the message bus.
+static STANDARD_INTERFACES: LazyLock<Vec<InterfaceInfo>> = LazyLock::new(|| {
+    parse_introspection_xml(include_str!("standard_interfaces.xml"))
+        .map(|(interfaces, _)| interfaces)
+        .expect("bundled standard_interfaces.xml is valid")
+});
+
+/// The bundled definition of a standard interface.
+pub fn standard_interface(name: &str) -> Option<&'static InterfaceInfo> {
+    STANDARD_INTERFACES.iter().find(|i| i.name == name)
+}
+
+pub fn is_standard_interface(name: &str) -> bool {
+    standard_interface(name).is_some()
+}
+
+/// Fill in descriptions the live introspection data lacks from the bundled definitions.
+pub fn add_standard_docs(interface: &mut InterfaceInfo) {
+    let Some(reference) = standard_interface(&interface.name) else {
+        return;
+    };
+
+    if interface.description.is_none() {
+        interface.description = reference.description.clone();
+    }
+    for method in &mut interface.methods {
+        if method.description.is_none() {
+            method.description = reference
+                .methods
+                .iter()
+                .find(|m| m.name == method.name)
+                .and_then(|m| m.description.clone());
+        }
+    }
+    for property in &mut interface.properties {
+        if property.description.is_none() {
+            property.description = reference
+                .properties
+                .iter()
+                .find(|p| p.name == property.name)
+                .and_then(|p| p.description.clone());
+        }
+    }
+    for signal in &mut interface.signals {
+        if signal.description.is_none() {
+            signal.description = reference
+                .signals
+                .iter()
+                .find(|s| s.name == signal.name)
+                .and_then(|s| s.description.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_interfaces_parse() {
+        let names: Vec<&str> = STANDARD_INTERFACES
+            .iter()
+            .map(|i| i.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "org.freedesktop.DBus.Peer",
+                "org.freedesktop.DBus.Introspectable",
+                "org.freedesktop.DBus.Properties",
+                "org.freedesktop.DBus.ObjectManager",
+                "org.freedesktop.DBus",
+                "org.freedesktop.DBus.Monitoring",
+                "org.freedesktop.DBus.Debug.Stats",
+            ]
+        );
+
+        let properties = standard_interface("org.freedesktop.DBus.Properties").unwrap();
+        let methods: Vec<&str> = properties.methods.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(methods, ["Get", "GetAll", "Set"]);
+        assert!(properties.methods.iter().all(|m| m.description.is_some()));
+        assert!(!is_standard_interface("com.example.Thing"));
+    }
+}
@@ -0,0 +1,35 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    config::Config, health::HealthHistory, interface_views::RendererRegistry,
+    metrics::BusMetricsCache, recorder::Recorder,
+};
+
+/// State shared by all requests.
+#[derive(Debug, Clone)]
+pub struct AppState {
+    pub health: Arc<Mutex<HealthHistory>>,
+    pub renderers: Arc<RendererRegistry>,
+    /// Read on every request so that policy changes show up without a restart.
+    pub bus_config: PathBuf,
+    pub recorder: Arc<Mutex<Recorder>>,
+    pub bus_metrics: Arc<BusMetricsCache>,
+}
+
+impl AppState {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            health: Arc::new(Mutex::new(HealthHistory::new(config.health_history_len))),
+            renderers: Arc::new(RendererRegistry::with_builtins()),
+            bus_config: config.bus_config.clone(),
+            recorder: Arc::new(Mutex::new(Recorder::new(config.recording_len))),
+            bus_metrics: Arc::new(BusMetricsCache::new(Duration::from_secs(
+                config.bus_metrics_interval,
+            ))),
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use dbus::{arg::messageitem::MessageItem, blocking::Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dbus_calls::{call_method_with_timeout, vardict_reply},
+    dbus_introspection::ServiceInfo,
+};
+
+pub const SYSTEMD_SERVICE: &str = "org.freedesktop.systemd1";
+const MANAGER_PATH: &str = "/org/freedesktop/systemd1";
+const MANAGER_INTERFACE: &str = "org.freedesktop.systemd1.Manager";
+const UNIT_INTERFACE: &str = "org.freedesktop.systemd1.Unit";
+/// The unit is a hint next to a service, not worth holding up a page for.
+const UNIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The systemd unit a process belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemdUnit {
+    pub name: String,
+    pub object_path: String,
+    pub active_state: String,
+    pub sub_state: String,
+}
+
+/// Fill in the unit of a service from its process, for the pages that show it.
+///
+/// Not every system runs systemd, so a service without a unit is not an error.
+pub fn resolve_unit(conn: &Connection, service_info: &mut ServiceInfo) {
+    if let Some(pid) = service_info.pid {
+        service_info.unit = unit_for_pid(conn, pid).ok();
+    }
+}
+
+/// Ask systemd which unit a process belongs to and in what state that unit is.
+pub fn unit_for_pid(conn: &Connection, pid: u32) -> Result<SystemdUnit> {
+    let reply = call_method_with_timeout(
+        conn,
+        SYSTEMD_SERVICE,
+        MANAGER_PATH,
+        MANAGER_INTERFACE,
+        "GetUnitByPID",
+        &[MessageItem::UInt32(pid)],
+        UNIT_TIMEOUT,
+    )?;
+    let Some(MessageItem::ObjectPath(object_path)) = reply.into_iter().next() else {
+        return Err(anyhow!("Unexpected reply to GetUnitByPID"));
+    };
+
+    let reply = call_method_with_timeout(
+        conn,
+        SYSTEMD_SERVICE,
+        &object_path,
+        "org.freedesktop.DBus.Properties",
+        "GetAll",
+        &[MessageItem::Str(UNIT_INTERFACE.to_string())],
+        UNIT_TIMEOUT,
+    )?;
+    let properties = vardict_reply(reply, "GetAll")?;
+    let text = |name: &str| match properties.get(name) {
+        Some(MessageItem::Str(s)) => s.clone(),
+        _ => String::new(),
+    };
+
+    Ok(SystemdUnit {
+        name: text("Id"),
+        object_path: object_path.to_string(),
+        active_state: text("ActiveState"),
+        sub_state: text("SubState"),
+    })
+}
+
+/// The status class of the health page that matches the active state of a unit.
+pub fn active_state_class(active_state: &str) -> &'static str {
+    match active_state {
+        "active" | "reloading" => "status-responsive",
+        "activating" | "deactivating" => "status-slow",
+        "failed" => "status-erroring",
+        _ => "status-inactive",
+    }
+}
@@ -1,4 +1,16 @@
-use crate::dbus_introspection::ObjectInfo;
+use crate::{
+    command_lines::{
+        emit_signal_command_lines, get_property_command_lines, method_command_lines,
+        set_property_command_lines, CommandLines,
+    },
+    dbus_calls::{MethodCall, ObjectAction, PropertyValues, PropertyWrite, SignalEmission},
+    dbus_introspection::{MethodInfo, ObjectInfo, PropertyInfo, SignalInfo},
+    interface_views::{InterfaceContext, RendererRegistry},
+    signature::parse_single_type,
+    standard_interfaces::is_standard_interface,
+    utils::{html_escape, object_page_url},
+    value_view::{render_value, ValueFormat},
+};
 
 pub struct PageTemplate {
     pub title: String,
@@ -27,8 +39,42 @@ impl PageTemplate {
         code {{ background-color: #f0f0f0; padding: 2px 4px; border-radius: 3px; }}
         .navigation {{ margin-bottom: 20px; padding: 10px; background-color: #f8f9fa; border-radius: 4px; }}
         .error {{ color: #d32f2f; background: #ffebee; padding: 15px; border-radius: 4px; margin: 10px 0; }}
+        .interface > summary {{ cursor: pointer; }}
+        .interface > summary h4 {{ display: inline; }}
+        .standard {{ color: #757575; font-size: smaller; }}
         .interface {{ margin: 20px 0; padding: 15px; border: 1px solid #ddd; border-radius: 4px; }}
         .method, .property, .signal {{ margin: 10px 0; padding: 8px; background-color: #f8f9fa; border-radius: 3px; }}
+        pre.xml {{ background-color: #f8f9fa; padding: 10px; border-radius: 4px; overflow-x: auto; }}
+        .xml-tag {{ color: #1565c0; }}
+        .xml-attr {{ color: #6a1b9a; }}
+        .xml-value {{ color: #2e7d32; }}
+        .xml-comment {{ color: #757575; font-style: italic; }}
+        .call-form {{ margin: 8px 0; }}
+        .call-form input, .call-form select, .call-form textarea {{ font-family: inherit; margin-right: 8px; }}
+        .argument-editor {{ display: inline-block; vertical-align: top; }}
+        .editor-rows, .editor-struct {{ display: inline-block; border-left: 2px solid #ddd; padding: 2px 6px; margin: 2px 0; }}
+        .editor-row, .editor-field {{ margin: 2px 0; }}
+        .editor-button {{ font-family: inherit; }}
+        .editor-errors {{ color: #d32f2f; }}
+        .call-result {{ background-color: #e8f5e9; padding: 8px; border-radius: 3px; margin: 8px 0; }}
+        pre.command-lines {{ background-color: #263238; color: #eceff1; padding: 8px; border-radius: 3px; overflow-x: auto; }}
+        .status {{ display: inline-block; padding: 2px 6px; border-radius: 3px; color: white; }}
+        .status-responsive {{ background-color: #2e7d32; }}
+        .status-slow {{ background-color: #ef6c00; }}
+        .status-timing-out {{ background-color: #c62828; }}
+        .status-erroring {{ background-color: #6a1b9a; }}
+        .status-inactive {{ background-color: #757575; }}
+        table.interface-view {{ width: auto; margin: 10px 0; }}
+        .history .status {{ padding: 2px 3px; margin-right: 1px; }}
+        .value {{ margin: 4px 0; }}
+        .value ul {{ list-style: none; margin: 0; padding-left: 20px; }}
+        .value-tree summary {{ cursor: pointer; color: #555; }}
+        .value-string {{ color: #2e7d32; }}
+        .value-number, .value-bool {{ color: #1565c0; }}
+        .value-signature, .value-fd {{ color: #6a1b9a; }}
+        .value-type, .value-index, .value-empty {{ color: #757575; }}
+        pre.value-bytes {{ margin: 2px 0; }}
+        .warning {{ color: #e65100; background: #fff3e0; padding: 10px; border-radius: 4px; margin: 10px 0; }}
     </style>
 </head>
 <body>
@@ -62,34 +108,87 @@ pub fn render_service_list(service_names: &[String]) -> String {
         r#"</ul>
 <h2>All Services and Objects</h2>
 <p><a href="/local/dbus_explorer/app/all">View all services and objects (flattened)</a></p>
+<h2>Interfaces</h2>
+<p><a href="/local/dbus_explorer/app/interfaces">View where each interface is implemented</a></p>
+<h2>Health</h2>
+<p><a href="/local/dbus_explorer/app/health">Ping every service and view response history</a></p>
+<h2>Bus statistics</h2>
+<p><a href="/local/dbus_explorer/app/stats">Message counts, queued bytes and match rules per connection</a></p>
+<h2>Lint</h2>
+<p><a href="/local/dbus_explorer/app/lint">Check interfaces against the D-Bus API design guidelines</a></p>
+<h2>Match rules</h2>
+<p><a href="/local/dbus_explorer/app/match">Compose a match rule, check it and see the messages it matches</a></p>
+<h2>Recording</h2>
+<p><a href="/local/dbus_explorer/app/recording">Method calls and property writes made here, to export and replay</a></p>
 "#,
     );
 
     html
 }
 
-pub fn render_object_details(object: &ObjectInfo) -> String {
+pub fn render_object_details(
+    service_name: &str,
+    object: &ObjectInfo,
+    action: Option<&ObjectAction>,
+    property_values: &PropertyValues,
+    format: &ValueFormat,
+    renderers: &RendererRegistry,
+    collapse_standard: bool,
+) -> String {
     let mut html = String::new();
 
     if let Some(error) = &object.error {
+        let dbus_details = match &object.dbus_error {
+            Some(e) => format!(
+                "<table><tr><th>Error name</th><td><code>{}</code></td></tr><tr><th>Message</th><td>{}</td></tr></table>",
+                html_escape(&e.name),
+                html_escape(&e.message)
+            ),
+            None => String::new(),
+        };
         html.push_str(&format!(
-            r#"<div class="error"><strong>Error:</strong> {}</div>"#,
+            r#"<div class="error"><strong>Error:</strong> {}{dbus_details}</div>"#,
             html_escape(error)
         ));
         return html;
     }
 
     for interface in &object.interfaces {
+        // Standard interfaces are the same everywhere and mostly in the way
+        let is_standard = is_standard_interface(&interface.name);
         html.push_str(&format!(
-            r#"<div class="interface">
-<h4>Interface: {}</h4>"#,
-            html_escape(&interface.name)
+            r#"<details class="interface"{}>
+<summary><h4>Interface: {}</h4>{}</summary>"#,
+            if is_standard && collapse_standard {
+                ""
+            } else {
+                " open"
+            },
+            html_escape(&interface.name),
+            if is_standard {
+                r#" <span class="standard">standard</span>"#
+            } else {
+                ""
+            }
         ));
 
         if let Some(desc) = &interface.description {
             html.push_str(&format!(r#"<p><em>{}</em></p>"#, html_escape(desc)));
         }
 
+        if let Some(renderer) = renderers.get(&interface.name) {
+            let context = InterfaceContext {
+                interface,
+                properties: property_values
+                    .get(&interface.name)
+                    .and_then(|values| values.as_ref().ok()),
+                format,
+            };
+            if let Some(view) = renderer.render(&context) {
+                html.push_str(&view);
+            }
+        }
+
         // Check if interface is empty
         let is_empty = interface.methods.is_empty()
             && interface.properties.is_empty()
@@ -104,8 +203,10 @@ pub fn render_object_details(object: &ObjectInfo) -> String {
             html.push_str("<h5>Methods:</h5>");
             for method in &interface.methods {
                 html.push_str(&format!(
-                    r#"<div class="method">
+                    r#"<div class="method" id="{}.{}">
 <strong>{}({})</strong>"#,
+                    html_escape(&interface.name),
+                    html_escape(&method.name),
                     html_escape(&method.name),
                     method
                         .arguments
@@ -133,6 +234,32 @@ pub fn render_object_details(object: &ObjectInfo) -> String {
                 if let Some(desc) = &method.description {
                     html.push_str(&format!("<br><em>{}</em>", html_escape(desc)));
                 }
+
+                let call = match action {
+                    Some(ObjectAction::Call(call))
+                        if call.interface == interface.name && call.method == method.name =>
+                    {
+                        Some(call)
+                    }
+                    _ => None,
+                };
+                html.push_str(&render_call_form(
+                    service_name,
+                    &object.path,
+                    &interface.name,
+                    method,
+                    call,
+                ));
+                if let Some(call) = call {
+                    html.push_str(&render_call_result(call, format));
+                }
+                html.push_str(&render_command_lines(&method_command_lines(
+                    service_name,
+                    &object.path,
+                    &interface.name,
+                    method,
+                    call.and_then(|c| c.arguments.as_deref()),
+                )));
                 html.push_str("</div>");
             }
         }
@@ -140,6 +267,17 @@ pub fn render_object_details(object: &ObjectInfo) -> String {
         // Properties
         if !interface.properties.is_empty() {
             html.push_str("<h5>Properties:</h5>");
+            let values = match property_values.get(&interface.name) {
+                Some(Ok(values)) => Some(values),
+                Some(Err(e)) => {
+                    html.push_str(&format!(
+                        r#"<div class="error"><strong>Could not read properties:</strong> {}</div>"#,
+                        html_escape(e)
+                    ));
+                    None
+                }
+                None => None,
+            };
             for property in &interface.properties {
                 html.push_str(&format!(
                     r#"<div class="property">
@@ -152,6 +290,47 @@ pub fn render_object_details(object: &ObjectInfo) -> String {
                 if let Some(desc) = &property.description {
                     html.push_str(&format!("<br><em>{}</em>", html_escape(desc)));
                 }
+
+                if let Some(value) = values.and_then(|values| values.get(&property.name)) {
+                    html.push_str(&render_value(value, format));
+                }
+
+                if property.access.contains("read") {
+                    html.push_str(&render_command_lines(&get_property_command_lines(
+                        service_name,
+                        &object.path,
+                        &interface.name,
+                        property,
+                    )));
+                }
+                if property.access.contains("write") {
+                    let write = match action {
+                        Some(ObjectAction::SetProperty(write))
+                            if write.interface == interface.name
+                                && write.property == property.name =>
+                        {
+                            Some(write)
+                        }
+                        _ => None,
+                    };
+                    html.push_str(&render_set_form(
+                        service_name,
+                        &object.path,
+                        &interface.name,
+                        property,
+                        write,
+                    ));
+                    if let Some(write) = write {
+                        html.push_str(&render_write_result(write));
+                    }
+                    html.push_str(&render_command_lines(&set_property_command_lines(
+                        service_name,
+                        &object.path,
+                        &interface.name,
+                        property,
+                        write.and_then(|w| w.value.as_ref()),
+                    )));
+                }
                 html.push_str("</div>");
             }
         }
@@ -179,13 +358,332 @@ pub fn render_object_details(object: &ObjectInfo) -> String {
                 if let Some(desc) = &signal.description {
                     html.push_str(&format!("<br><em>{}</em>", html_escape(desc)));
                 }
+
+                let emission = match action {
+                    Some(ObjectAction::EmitSignal(emission))
+                        if emission.interface == interface.name
+                            && emission.signal == signal.name =>
+                    {
+                        Some(emission)
+                    }
+                    _ => None,
+                };
+                html.push_str(&render_emit_form(
+                    service_name,
+                    &object.path,
+                    &interface.name,
+                    signal,
+                    emission,
+                ));
+                if let Some(emission) = emission {
+                    html.push_str(&render_emit_result(emission));
+                }
+                html.push_str(&render_command_lines(&emit_signal_command_lines(
+                    &object.path,
+                    &interface.name,
+                    signal,
+                    emission.and_then(|e| e.arguments.as_deref()),
+                )));
                 html.push_str("</div>");
             }
         }
 
-        html.push_str("</div>");
+        html.push_str("</details>");
+    }
+
+    html
+}
+
+fn render_call_form(
+    service_name: &str,
+    object_path: &str,
+    interface: &str,
+    method: &MethodInfo,
+    call: Option<&MethodCall>,
+) -> String {
+    let anchor = format!("{interface}.{}", method.name);
+
+    let mut html = format!(
+        r#"<form class="call-form" method="post" action="{}#{}">
+<input type="hidden" name="interface" value="{}">
+<input type="hidden" name="method" value="{}">"#,
+        object_page_url(service_name, object_path),
+        urlencoding::encode(&anchor),
+        html_escape(interface),
+        html_escape(&method.name)
+    );
+
+    for (i, arg) in method.arguments.iter().enumerate() {
+        let name = arg.name.clone().unwrap_or_else(|| format!("arg{i}"));
+        let value = call
+            .and_then(|c| c.inputs.get(i))
+            .map(String::as_str)
+            .unwrap_or_default();
+        html.push_str(&format!(
+            "<label>{}: {} {}</label>",
+            html_escape(arg.name.as_deref().unwrap_or("_")),
+            html_escape(&arg.type_name),
+            render_argument_input(&name, &arg.type_name, value)
+        ));
+    }
+
+    html.push_str(r#"<button type="submit">Call</button><div class="editor-errors"></div></form>"#);
+    html
+}
+
+fn render_set_form(
+    service_name: &str,
+    object_path: &str,
+    interface: &str,
+    property: &PropertyInfo,
+    write: Option<&PropertyWrite>,
+) -> String {
+    let anchor = format!("{interface}.{}", property.name);
+
+    format!(
+        r#"<form class="call-form" id="{}" method="post" action="{}#{}">
+<input type="hidden" name="interface" value="{}">
+<input type="hidden" name="property" value="{}">
+<label>value: {} {}</label><button type="submit">Set</button><div class="editor-errors"></div></form>"#,
+        html_escape(&anchor),
+        object_page_url(service_name, object_path),
+        urlencoding::encode(&anchor),
+        html_escape(interface),
+        html_escape(&property.name),
+        html_escape(&property.type_name),
+        render_argument_input(
+            &property.name,
+            &property.type_name,
+            write.map(|w| w.input.as_str()).unwrap_or_default()
+        )
+    )
+}
+
+/// Signals are emitted from the explorer's connection, so handlers that check the sender
+/// will not see them as coming from the service.
+fn render_emit_form(
+    service_name: &str,
+    object_path: &str,
+    interface: &str,
+    signal: &SignalInfo,
+    emission: Option<&SignalEmission>,
+) -> String {
+    let anchor = format!("{interface}.{}", signal.name);
+
+    let mut html = format!(
+        r#"<form class="call-form" id="{}" method="post" action="{}#{}">
+<input type="hidden" name="interface" value="{}">
+<input type="hidden" name="signal" value="{}">"#,
+        html_escape(&anchor),
+        object_page_url(service_name, object_path),
+        urlencoding::encode(&anchor),
+        html_escape(interface),
+        html_escape(&signal.name)
+    );
+
+    for (i, arg) in signal.arguments.iter().enumerate() {
+        let name = arg.name.clone().unwrap_or_else(|| format!("arg{i}"));
+        let value = emission
+            .and_then(|e| e.inputs.get(i))
+            .map(String::as_str)
+            .unwrap_or_default();
+        html.push_str(&format!(
+            "<label>{}: {} {}</label>",
+            html_escape(arg.name.as_deref().unwrap_or("_")),
+            html_escape(&arg.type_name),
+            render_argument_input(&name, &arg.type_name, value)
+        ));
     }
 
+    html.push_str(
+        r#"<button type="submit" title="Sent from the explorer's own connection, not the service's">Emit</button><div class="editor-errors"></div></form>"#,
+    );
+    html
+}
+
+/// Basic types get a text box, containers and variants a structured editor that fills a
+/// hidden textarea with JSON (see `argument_editor.js`).
+fn render_argument_input(name: &str, type_name: &str, value: &str) -> String {
+    match parse_single_type(type_name) {
+        Ok(dbus_type) if !dbus_type.is_basic() => format!(
+            r#"<span class="argument-editor" data-signature="{}" data-name="{}"></span><textarea name="arg" rows="2">{}</textarea>"#,
+            html_escape(type_name),
+            html_escape(name),
+            html_escape(value)
+        ),
+        Ok(_) => format!(
+            r#"<input type="text" name="arg" value="{}" data-signature="{}" data-name="{}">"#,
+            html_escape(value),
+            html_escape(type_name),
+            html_escape(name)
+        ),
+        Err(_) => format!(
+            r#"<input type="text" name="arg" value="{}">"#,
+            html_escape(value)
+        ),
+    }
+}
+
+pub fn render_argument_editor_script() -> String {
+    format!("<script>{}</script>", include_str!("argument_editor.js"))
+}
+
+fn render_call_result(call: &MethodCall, format: &ValueFormat) -> String {
+    match &call.reply {
+        Ok(reply) if reply.is_empty() => {
+            r#"<div class="call-result"><strong>Reply:</strong> <em>empty</em></div>"#.to_string()
+        }
+        Ok(reply) => format!(
+            r#"<div class="call-result"><strong>Reply:</strong>{}</div>"#,
+            reply
+                .iter()
+                .map(|item| render_value(item, format))
+                .collect::<String>()
+        ),
+        Err(e) => format!(
+            r#"<div class="error"><strong>Call failed:</strong> {}</div>"#,
+            html_escape(e)
+        ),
+    }
+}
+
+fn render_write_result(write: &PropertyWrite) -> String {
+    match &write.result {
+        Ok(()) => r#"<div class="call-result"><strong>Property set.</strong></div>"#.to_string(),
+        Err(e) => format!(
+            r#"<div class="error"><strong>Set failed:</strong> {}</div>"#,
+            html_escape(e)
+        ),
+    }
+}
+
+fn render_emit_result(emission: &SignalEmission) -> String {
+    match &emission.result {
+        Ok(()) => r#"<div class="call-result"><strong>Signal emitted.</strong></div>"#.to_string(),
+        Err(e) => format!(
+            r#"<div class="error"><strong>Emit failed:</strong> {}</div>"#,
+            html_escape(e)
+        ),
+    }
+}
+
+fn render_command_lines(command_lines: &CommandLines) -> String {
+    let dbus_send = command_lines
+        .dbus_send
+        .clone()
+        .unwrap_or_else(|| "# not expressible with dbus-send".to_string());
+
+    format!(
+        r#"<details><summary>Command lines</summary><pre class="command-lines">{}
+{}
+{}</pre></details>"#,
+        html_escape(&command_lines.busctl),
+        html_escape(&dbus_send),
+        html_escape(&command_lines.gdbus)
+    )
+}
+
+pub fn render_object_xml(object: &ObjectInfo) -> String {
+    let Some(xml) = &object.xml else {
+        return String::new();
+    };
+
+    let mut html = String::new();
+
+    // Point at the offending spot so that broken documents can be fixed at the source
+    if let Some(xml_error) = &object.xml_error {
+        let line = xml.lines().nth(xml_error.line - 1).unwrap_or_default();
+        let caret = format!("{}^", " ".repeat(xml_error.column.saturating_sub(1)));
+        html.push_str(&format!(
+            r#"<div class="error"><strong>XML parse error at line {}, column {}:</strong> {}<pre>{}
+{}</pre></div>"#,
+            xml_error.line,
+            xml_error.column,
+            html_escape(&xml_error.message),
+            html_escape(line),
+            caret
+        ));
+    }
+
+    html.push_str(&format!(
+        r#"<details{}>
+<summary>Raw introspection XML</summary>
+<pre class="xml">{}</pre>
+</details>"#,
+        if object.xml_error.is_some() {
+            " open"
+        } else {
+            ""
+        },
+        highlight_xml(xml)
+    ));
+
+    html
+}
+
+/// Escape XML and wrap tags, attributes, values and comments in spans for coloring.
+fn highlight_xml(xml: &str) -> String {
+    let mut html = String::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        html.push_str(&html_escape(&rest[..start]));
+        rest = &rest[start..];
+
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |i| i + 3);
+            html.push_str(&format!(
+                r#"<span class="xml-comment">{}</span>"#,
+                html_escape(&rest[..end])
+            ));
+            rest = &rest[end..];
+            continue;
+        }
+
+        let end = rest.find('>').map_or(rest.len(), |i| i + 1);
+        html.push_str(&highlight_xml_tag(&rest[..end]));
+        rest = &rest[end..];
+    }
+
+    html.push_str(&html_escape(rest));
+    html
+}
+
+fn highlight_xml_tag(tag: &str) -> String {
+    let name_end = tag
+        .char_indices()
+        .skip(2)
+        .find(|(_, c)| c.is_whitespace() || *c == '>' || *c == '/')
+        .map_or(tag.len(), |(i, _)| i);
+
+    let mut html = format!(
+        r#"<span class="xml-tag">{}</span>"#,
+        html_escape(&tag[..name_end])
+    );
+
+    let mut rest = &tag[name_end..];
+    while !rest.is_empty() {
+        let Some(quote_start) = rest.find(['"', '\'']) else {
+            break;
+        };
+        let quote = &rest[quote_start..quote_start + 1];
+        let Some(quote_len) = rest[quote_start + 1..].find(quote) else {
+            break;
+        };
+        let value_end = quote_start + quote_len + 2;
+
+        html.push_str(&format!(
+            r#"<span class="xml-attr">{}</span><span class="xml-value">{}</span>"#,
+            html_escape(&rest[..quote_start]),
+            html_escape(&rest[quote_start..value_end])
+        ));
+        rest = &rest[value_end..];
+    }
+
+    html.push_str(&format!(
+        r#"<span class="xml-tag">{}</span>"#,
+        html_escape(rest)
+    ));
     html
 }
 
@@ -215,11 +713,3 @@ pub fn render_dbus_types_reference() -> String {
 <p><em>Common patterns: <code>as</code> = string array, <code>a{sv}</code> = property map, <code>a(ssss)</code> = array of 4-string structs</em></p>
 "#.to_string()
 }
-
-fn html_escape(text: &str) -> String {
-    text.replace('&', "&amp;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;")
-        .replace('"', "&quot;")
-        .replace('\'', "&#x27;")
-}
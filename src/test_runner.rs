@@ -0,0 +1,701 @@
+use std::{
+    fmt::Write,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use dbus::{arg::messageitem::MessageItem, blocking::Connection, message::MatchRule};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    dbus_calls::{call_method, convert_inputs, get_property},
+    dbus_introspection::introspect_object,
+    error::AppError,
+    utils::{read_yaml_or_json, split_member_name, validate_error_name, validate_object_path},
+    value_view::{typed_value_to_json, value_to_json},
+};
+
+const DEFAULT_SIGNAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A file of smoke tests, with the service and object the steps use unless they say otherwise.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SuiteSpec {
+    /// Defaults to the file name.
+    pub name: Option<String>,
+    pub service: Option<String>,
+    pub path: Option<String>,
+    pub tests: Vec<TestSpec>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestSpec {
+    pub name: String,
+    pub service: Option<String>,
+    pub path: Option<String>,
+    pub steps: Vec<StepSpec>,
+}
+
+/// One of `call`, `get` or `wait_for_signal`, each naming a member as `Interface.Member`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StepSpec {
+    pub service: Option<String>,
+    /// For signals, any path matches unless one is given here.
+    pub path: Option<String>,
+    pub call: Option<String>,
+    pub get: Option<String>,
+    pub wait_for_signal: Option<String>,
+    /// Arguments of a call, as on the command line; values that are not strings are given as JSON.
+    #[serde(default)]
+    pub args: Vec<Value>,
+    pub expect: Option<Expectation>,
+    /// How long to wait for a signal.
+    pub timeout_ms: Option<u64>,
+}
+
+/// What the outcome of a step must look like; every matcher given must hold.
+///
+/// Values are compared in the JSON encoding of `--json` without types. A reply with one value
+/// is that value, a reply with several values is an array of them.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectation {
+    pub equals: Option<Value>,
+    /// An array element, a substring, or keys and values of a dict.
+    pub contains: Option<Value>,
+    /// A regular expression for the value, or for its JSON text if it is not a string.
+    pub matches: Option<String>,
+    pub signature: Option<String>,
+    /// The step must fail with this D-Bus error name.
+    pub error: Option<String>,
+}
+
+/// The outcome of a step: reply values, or the D-Bus error name and message.
+type StepOutcome = std::result::Result<Vec<MessageItem>, (Option<String>, String)>;
+
+impl Expectation {
+    fn check(&self, outcome: &StepOutcome) -> std::result::Result<(), String> {
+        let items = match (outcome, &self.error) {
+            (Ok(_), Some(error)) => return Err(format!("Expected error {error} but succeeded")),
+            (Err((name, _)), Some(error)) if name.as_deref() == Some(error) => return Ok(()),
+            (Err((name, message)), Some(error)) => {
+                return Err(format!(
+                    "Expected error {error} but got {}: {message}",
+                    name.as_deref().unwrap_or("an error without name")
+                ))
+            }
+            (Err((_, message)), None) => return Err(message.clone()),
+            (Ok(items), None) => items,
+        };
+
+        let value = reply_value(items);
+        if let Some(signature) = &self.signature {
+            let actual: String = items.iter().map(|i| i.signature().to_string()).collect();
+            if *signature != actual {
+                return Err(format!("Expected signature {signature} but got {actual}"));
+            }
+        }
+        if let Some(expected) = &self.equals {
+            if *expected != value {
+                return Err(format!("Expected {expected} but got {value}"));
+            }
+        }
+        if let Some(expected) = &self.contains {
+            if !contains(&value, expected) {
+                return Err(format!("Expected {value} to contain {expected}"));
+            }
+        }
+        if let Some(pattern) = &self.matches {
+            let regex =
+                Regex::new(pattern).map_err(|e| format!("Invalid pattern {pattern}: {e}"))?;
+            let text = match &value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            if !regex.is_match(&text) {
+                return Err(format!("Expected {text} to match {pattern}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn reply_value(items: &[MessageItem]) -> Value {
+    match items {
+        [item] => value_to_json(item),
+        items => Value::Array(items.iter().map(value_to_json).collect()),
+    }
+}
+
+fn contains(value: &Value, expected: &Value) -> bool {
+    match (value, expected) {
+        (Value::Array(elements), expected) => elements.contains(expected),
+        (Value::String(s), Value::String(expected)) => s.contains(expected.as_str()),
+        (Value::Object(entries), Value::Object(expected)) => expected
+            .iter()
+            .all(|(key, value)| entries.get(key) == Some(value)),
+        _ => false,
+    }
+}
+
+/// Read a suite from YAML, or from JSON if the file name ends in `.json`.
+pub fn load_suite(path: &Path) -> Result<SuiteSpec> {
+    let mut suite: SuiteSpec = read_yaml_or_json(path)?;
+    if suite.name.is_none() {
+        suite.name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned());
+    }
+    // Catch misspelt error names now rather than as a confusing mismatch later
+    for test in &suite.tests {
+        for error in test
+            .steps
+            .iter()
+            .filter_map(|step| step.expect.as_ref()?.error.as_ref())
+        {
+            validate_error_name(error)
+                .with_context(|| format!("Invalid expected error in test {}", test.name))?;
+        }
+    }
+    Ok(suite)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SuiteReport {
+    pub name: String,
+    pub tests: Vec<TestReport>,
+    pub duration_ms: f64,
+}
+
+impl SuiteReport {
+    pub fn failures(&self) -> usize {
+        self.tests.iter().filter(|test| !test.passed).count()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TestReport {
+    pub name: String,
+    pub passed: bool,
+    /// The steps that ran; a test stops at its first failing step.
+    pub steps: Vec<StepReport>,
+    pub duration_ms: f64,
+}
+
+impl TestReport {
+    pub fn failure(&self) -> Option<&StepReport> {
+        self.steps.iter().find(|step| step.failure.is_some())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StepReport {
+    /// e.g. `call org.freedesktop.DBus.ListNames on org.freedesktop.DBus /org/freedesktop/DBus`.
+    pub description: String,
+    /// The reply, property value or signal arguments with their types.
+    pub values: Vec<Value>,
+    pub failure: Option<String>,
+    pub duration_ms: f64,
+}
+
+/// Run every test of a suite; tests are independent, steps of a test run in order.
+pub fn run_suite(conn: &Connection, suite: &SuiteSpec) -> SuiteReport {
+    let started = Instant::now();
+    let tests = suite
+        .tests
+        .iter()
+        .map(|test| run_test(conn, suite, test))
+        .collect();
+    SuiteReport {
+        name: suite.name.clone().unwrap_or_default(),
+        tests,
+        duration_ms: duration_ms(started.elapsed()),
+    }
+}
+
+/// A signal received while a test runs.
+struct ReceivedSignal {
+    interface: String,
+    member: String,
+    items: Vec<MessageItem>,
+}
+
+fn run_test(conn: &Connection, suite: &SuiteSpec, test: &TestSpec) -> TestReport {
+    let started = Instant::now();
+    let mut steps = Vec::new();
+
+    // Listen for every awaited signal from the start, so that steps can trigger them
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let mut tokens = Vec::new();
+    let mut subscription_error = None;
+    for step in &test.steps {
+        let Some(signal) = &step.wait_for_signal else {
+            continue;
+        };
+        let service = step
+            .service
+            .as_ref()
+            .or(test.service.as_ref())
+            .or(suite.service.as_ref());
+        match subscribe(conn, signal, service, step.path.as_deref(), &received) {
+            Ok(token) => tokens.push(token),
+            Err(e) => subscription_error = Some(format!("{e:#}")),
+        }
+    }
+
+    if let Some(error) = subscription_error {
+        steps.push(StepReport {
+            description: "subscribe to signals".to_string(),
+            values: Vec::new(),
+            failure: Some(error),
+            duration_ms: 0.0,
+        });
+    } else {
+        let mut consumed = 0;
+        for step in &test.steps {
+            let report = run_step(conn, suite, test, step, &received, &mut consumed);
+            let failed = report.failure.is_some();
+            steps.push(report);
+            if failed {
+                break;
+            }
+        }
+    }
+
+    for token in tokens {
+        let _ = conn.remove_match(token);
+    }
+
+    TestReport {
+        name: test.name.clone(),
+        passed: steps.iter().all(|step| step.failure.is_none()),
+        steps,
+        duration_ms: duration_ms(started.elapsed()),
+    }
+}
+
+fn subscribe(
+    conn: &Connection,
+    signal: &str,
+    service: Option<&String>,
+    path: Option<&str>,
+    received: &Arc<Mutex<Vec<ReceivedSignal>>>,
+) -> Result<dbus::channel::Token> {
+    let (interface, member) = split_member_name(signal)?;
+    let mut rule = MatchRule::new_signal(interface.clone(), member.clone());
+    if let Some(service) = service {
+        rule = rule.with_sender(service.clone());
+    }
+    if let Some(path) = path {
+        validate_object_path(path)?;
+        rule = rule.with_path(path.to_string());
+    }
+
+    let received = received.clone();
+    conn.add_match(rule, move |(), _, message| {
+        received
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(ReceivedSignal {
+                interface: interface.clone(),
+                member: member.clone(),
+                items: message.get_items(),
+            });
+        true
+    })
+    .with_context(|| format!("Failed to subscribe to {signal}"))
+}
+
+fn run_step(
+    conn: &Connection,
+    suite: &SuiteSpec,
+    test: &TestSpec,
+    step: &StepSpec,
+    received: &Mutex<Vec<ReceivedSignal>>,
+    consumed: &mut usize,
+) -> StepReport {
+    let started = Instant::now();
+    let service = step
+        .service
+        .as_ref()
+        .or(test.service.as_ref())
+        .or(suite.service.as_ref());
+    let path = step
+        .path
+        .as_ref()
+        .or(test.path.as_ref())
+        .or(suite.path.as_ref());
+    let expectation = step.expect.clone().unwrap_or_default();
+
+    let (description, result) = match (&step.call, &step.get, &step.wait_for_signal) {
+        (Some(method), None, None) => (
+            format!("call {method}{}", location(service, path)),
+            run_call(conn, service, path, method, &step.args)
+                .and_then(|outcome| check(&expectation, outcome)),
+        ),
+        (None, Some(property), None) => (
+            format!("get {property}{}", location(service, path)),
+            run_get(conn, service, path, property).and_then(|outcome| check(&expectation, outcome)),
+        ),
+        (None, None, Some(signal)) => (
+            format!(
+                "wait for signal {signal}{}",
+                location(service, step.path.as_ref())
+            ),
+            wait_for_signal(
+                conn,
+                signal,
+                &expectation,
+                step.timeout_ms
+                    .map(Duration::from_millis)
+                    .unwrap_or(DEFAULT_SIGNAL_TIMEOUT),
+                received,
+                consumed,
+            ),
+        ),
+        _ => (
+            "invalid step".to_string(),
+            Err(anyhow!(
+                "A step needs exactly one of call, get and wait_for_signal"
+            )),
+        ),
+    };
+
+    let (values, failure) = match result {
+        Ok(items) => (items.iter().map(typed_value_to_json).collect(), None),
+        Err(e) => (Vec::new(), Some(format!("{e:#}"))),
+    };
+    StepReport {
+        description,
+        values,
+        failure,
+        duration_ms: duration_ms(started.elapsed()),
+    }
+}
+
+fn check(expectation: &Expectation, outcome: StepOutcome) -> Result<Vec<MessageItem>> {
+    expectation.check(&outcome).map_err(|e| anyhow!(e))?;
+    Ok(outcome.unwrap_or_default())
+}
+
+fn run_call(
+    conn: &Connection,
+    service: Option<&String>,
+    path: Option<&String>,
+    method: &str,
+    args: &[Value],
+) -> Result<StepOutcome> {
+    let (service, path) = target(service, path)?;
+    let (interface, method) = split_member_name(method)?;
+    let object = introspect_object(conn, service, path)
+        .ok_or_else(|| anyhow!("Object not found: {service}:{path}"))?;
+    let method_info = object
+        .interfaces
+        .iter()
+        .filter(|i| i.name == interface)
+        .flat_map(|i| &i.methods)
+        .find(|m| m.name == method)
+        .ok_or_else(|| anyhow!("Method not found: {interface}.{method}"))?;
+
+    let inputs: Vec<String> = args
+        .iter()
+        .map(|arg| match arg {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+        .collect();
+    let arguments = convert_inputs(method_info, &inputs)?;
+
+    Ok(outcome(call_method(
+        conn, service, path, &interface, &method, &arguments,
+    )))
+}
+
+fn run_get(
+    conn: &Connection,
+    service: Option<&String>,
+    path: Option<&String>,
+    property: &str,
+) -> Result<StepOutcome> {
+    let (service, path) = target(service, path)?;
+    let (interface, property) = split_member_name(property)?;
+    Ok(outcome(
+        get_property(conn, service, path, &interface, &property).map(|value| vec![value]),
+    ))
+}
+
+/// Wait for a signal not yet consumed by an earlier step that meets the expectation.
+fn wait_for_signal(
+    conn: &Connection,
+    signal: &str,
+    expectation: &Expectation,
+    timeout: Duration,
+    received: &Mutex<Vec<ReceivedSignal>>,
+    consumed: &mut usize,
+) -> Result<Vec<MessageItem>> {
+    let (interface, member) = split_member_name(signal)?;
+    let deadline = Instant::now() + timeout;
+    let mut last_mismatch = None;
+    loop {
+        {
+            let received = received.lock().unwrap_or_else(|e| e.into_inner());
+            while *consumed < received.len() {
+                let signal = &received[*consumed];
+                *consumed += 1;
+                if signal.interface != interface || signal.member != member {
+                    continue;
+                }
+                match expectation.check(&Ok(signal.items.clone())) {
+                    Ok(()) => return Ok(signal.items.clone()),
+                    Err(e) => last_mismatch = Some(e),
+                }
+            }
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(match last_mismatch {
+                Some(mismatch) => {
+                    anyhow!("No matching {signal} within {timeout:?}, last one: {mismatch}")
+                }
+                None => anyhow!("No {signal} within {timeout:?}"),
+            });
+        }
+        conn.process(deadline - now)
+            .with_context(|| format!("Failed to wait for {signal}"))?;
+    }
+}
+
+fn outcome(result: Result<Vec<MessageItem>>) -> StepOutcome {
+    result.map_err(|e| {
+        let name = e
+            .chain()
+            .find_map(|e| e.downcast_ref::<AppError>())
+            .and_then(AppError::dbus_error)
+            .map(|e| e.name);
+        (name, format!("{e:#}"))
+    })
+}
+
+fn target<'a>(service: Option<&'a String>, path: Option<&'a String>) -> Result<(&'a str, &'a str)> {
+    let service = service.ok_or_else(|| anyhow!("No service given for the step"))?;
+    let path = path.ok_or_else(|| anyhow!("No path given for the step"))?;
+    validate_object_path(path)?;
+    Ok((service, path))
+}
+
+fn location(service: Option<&String>, path: Option<&String>) -> String {
+    let mut text = String::new();
+    if let Some(service) = service {
+        text.push_str(&format!(" on {service}"));
+    }
+    if let Some(path) = path {
+        text.push_str(&format!(" {path}"));
+    }
+    text
+}
+
+/// The reports in the JUnit XML format understood by CI servers, one test suite per file.
+pub fn junit_xml(reports: &[SuiteReport]) -> String {
+    let tests: usize = reports.iter().map(|r| r.tests.len()).sum();
+    let failures: usize = reports.iter().map(SuiteReport::failures).sum();
+    let time: f64 = reports.iter().map(|r| r.duration_ms).sum::<f64>() / 1000.0;
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        r#"<testsuites tests="{tests}" failures="{failures}" time="{time:.3}">"#
+    );
+    for report in reports {
+        let _ = writeln!(
+            xml,
+            r#"  <testsuite name="{}" tests="{}" failures="{}" time="{:.3}">"#,
+            xml_escape(&report.name),
+            report.tests.len(),
+            report.failures(),
+            report.duration_ms / 1000.0
+        );
+        for test in &report.tests {
+            let _ = write!(
+                xml,
+                r#"    <testcase name="{}" classname="{}" time="{:.3}""#,
+                xml_escape(&test.name),
+                xml_escape(&report.name),
+                test.duration_ms / 1000.0
+            );
+            match test.failure() {
+                Some(step) => {
+                    let _ = writeln!(
+                        xml,
+                        r#">
+      <failure message="{}">{}</failure>
+    </testcase>"#,
+                        xml_escape(step.failure.as_deref().unwrap_or_default()),
+                        xml_escape(&step.description)
+                    );
+                }
+                None => {
+                    let _ = writeln!(xml, "/>");
+                }
+            }
+        }
+        let _ = writeln!(xml, "  </testsuite>");
+    }
+    xml.push_str("</testsuites>\n");
+
+    xml
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn duration_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn expect(yaml: &str) -> Expectation {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn error(name: Option<&str>, message: &str) -> StepOutcome {
+        Err((name.map(str::to_string), message.to_string()))
+    }
+
+    #[test]
+    fn errors_and_successes() {
+        let busy = expect("error: com.example.Error.Busy");
+        assert_eq!(
+            busy.check(&error(Some("com.example.Error.Busy"), "Try again")),
+            Ok(())
+        );
+        assert_eq!(
+            busy.check(&Ok(vec![])),
+            Err("Expected error com.example.Error.Busy but succeeded".to_string())
+        );
+        assert_eq!(
+            busy.check(&error(Some("com.example.Error.Failed"), "Broken")),
+            Err(
+                "Expected error com.example.Error.Busy but got com.example.Error.Failed: Broken"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            busy.check(&error(None, "Timed out")),
+            Err(
+                "Expected error com.example.Error.Busy but got an error without name: Timed out"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            Expectation::default().check(&error(None, "Timed out")),
+            Err("Timed out".to_string())
+        );
+        assert_eq!(Expectation::default().check(&Ok(vec![])), Ok(()));
+    }
+
+    #[test]
+    fn reply_matchers() {
+        let reply: StepOutcome = Ok(vec![MessageItem::Bool(true), MessageItem::UInt32(50)]);
+        assert_eq!(
+            expect("{equals: [true, 50], signature: bu}").check(&reply),
+            Ok(())
+        );
+        assert_eq!(
+            expect("signature: b").check(&reply),
+            Err("Expected signature b but got bu".to_string())
+        );
+        assert_eq!(
+            expect("equals: [false, 0]").check(&reply),
+            Err("Expected [false,0] but got [true,50]".to_string())
+        );
+
+        let name: StepOutcome = Ok(vec![MessageItem::Str("org.freedesktop.DBus".to_string())]);
+        assert_eq!(
+            expect("matches: ^org\\.freedesktop\\.").check(&name),
+            Ok(())
+        );
+        assert_eq!(
+            expect("matches: ^com").check(&name),
+            Err("Expected org.freedesktop.DBus to match ^com".to_string())
+        );
+        assert_eq!(expect("matches: '^\\[true,50\\]$'").check(&reply), Ok(()));
+        assert!(expect("matches: '('").check(&name).is_err());
+    }
+
+    #[test]
+    fn containment() {
+        assert!(contains(&json!([1, "two", [3]]), &json!("two")));
+        assert!(contains(&json!([1, "two", [3]]), &json!([3])));
+        assert!(!contains(&json!([1, "two", [3]]), &json!(3)));
+
+        assert!(contains(&json!("org.freedesktop.DBus"), &json!("desktop")));
+        assert!(!contains(&json!("org.freedesktop.DBus"), &json!("Desktop")));
+        assert!(!contains(&json!("12345"), &json!(234)));
+
+        let dict = json!({"Name": "light", "Level": 50, "On": true});
+        assert!(contains(&dict, &json!({"Level": 50, "On": true})));
+        assert!(contains(&dict, &json!({})));
+        assert!(!contains(&dict, &json!({"Level": 51})));
+        assert!(!contains(&dict, &json!({"Color": "red"})));
+        assert!(!contains(&dict, &json!("Name")));
+    }
+
+    #[test]
+    fn junit_reports() {
+        let step = |failure: Option<&str>| StepReport {
+            description: "call com.example.A.B on <com.example> /".to_string(),
+            values: vec![],
+            failure: failure.map(str::to_string),
+            duration_ms: 1.0,
+        };
+        let reports = [SuiteReport {
+            name: "Quotes \"&\" <tags>".to_string(),
+            tests: vec![
+                TestReport {
+                    name: "it's fine".to_string(),
+                    passed: true,
+                    steps: vec![step(None)],
+                    duration_ms: 1.0,
+                },
+                TestReport {
+                    name: "broken".to_string(),
+                    passed: false,
+                    steps: vec![step(None), step(Some("Expected 1 but got 2 & 3"))],
+                    duration_ms: 2500.0,
+                },
+            ],
+            duration_ms: 2501.0,
+        }];
+
+        assert_eq!(
+            junit_xml(&reports),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites tests="2" failures="1" time="2.501">
+  <testsuite name="Quotes &quot;&amp;&quot; &lt;tags&gt;" tests="2" failures="1" time="2.501">
+    <testcase name="it&apos;s fine" classname="Quotes &quot;&amp;&quot; &lt;tags&gt;" time="0.001"/>
+    <testcase name="broken" classname="Quotes &quot;&amp;&quot; &lt;tags&gt;" time="2.500">
+      <failure message="Expected 1 but got 2 &amp; 3">call com.example.A.B on &lt;com.example&gt; /</failure>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+}
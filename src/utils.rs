@@ -1,10 +1,41 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::Context;
+use serde::de::DeserializeOwned;
 
 use crate::{
     dbus_introspection::ObjectInfo,
     error::{AppError, Result},
+    signature::parse_signature,
 };
 
+/// Longest bus, interface, member or error name the specification allows.
+const MAX_NAME_LENGTH: usize = 255;
+
+/// Split `org.example.Interface.Member` into a valid interface and member name.
+pub fn split_member_name(text: &str) -> Result<(String, String)> {
+    let (interface, member) = text.rsplit_once('.').ok_or_else(|| {
+        AppError::InvalidInput(format!("Expected Interface.Member but got {text}"))
+    })?;
+    validate_interface_name(interface)?;
+    validate_member_name(member)?;
+    Ok((interface.to_string(), member.to_string()))
+}
+
+/// Read a file written in YAML, or in JSON if its name ends in `.json`.
+pub fn read_yaml_or_json<T: DeserializeOwned>(path: &Path) -> anyhow::Result<T> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let value = if path.extension().is_some_and(|e| e == "json") {
+        serde_json::from_str(&text).map_err(anyhow::Error::from)
+    } else {
+        serde_yaml::from_str(&text).map_err(anyhow::Error::from)
+    };
+    value.with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Validate a bus name, either a unique connection name like `:1.42` or a well-known name like
+/// `org.freedesktop.DBus`.
 pub fn validate_service_name(service_name: &str) -> Result<()> {
     if service_name.is_empty() {
         return Err(AppError::InvalidInput(
@@ -12,23 +43,27 @@ pub fn validate_service_name(service_name: &str) -> Result<()> {
         ));
     }
 
-    if service_name.len() > 255 {
-        return Err(AppError::InvalidInput("Service name too long".to_string()));
-    }
-
-    // Basic validation for D-Bus service names
-    if !service_name
-        .chars()
-        .all(|c| c.is_alphanumeric() || c == '.' || c == '_' || c == '-')
-    {
-        return Err(AppError::InvalidInput(
-            "Invalid characters in service name".to_string(),
-        ));
+    if service_name.len() > MAX_NAME_LENGTH {
+        return Err(AppError::InvalidInput(format!(
+            "Service name {service_name:?} is longer than {MAX_NAME_LENGTH} characters"
+        )));
     }
 
-    Ok(())
+    // Elements of unique names may start with a digit, those of well-known names may not
+    let (unique, elements) = match service_name.strip_prefix(':') {
+        Some(rest) => (true, rest),
+        None => (false, service_name),
+    };
+    validate_elements(
+        "Service name",
+        service_name,
+        elements,
+        |c| c.is_ascii_alphanumeric() || c == '_' || c == '-',
+        !unique,
+    )
 }
 
+/// Validate an object path: `/` or `/`-separated non-empty elements of `[A-Za-z0-9_]`.
 pub fn validate_object_path(object_path: &str) -> Result<()> {
     if object_path.is_empty() {
         return Err(AppError::InvalidInput(
@@ -37,28 +72,194 @@ pub fn validate_object_path(object_path: &str) -> Result<()> {
     }
 
     if !object_path.starts_with('/') {
-        return Err(AppError::InvalidInput(
-            "Object path must start with '/'".to_string(),
-        ));
+        return Err(AppError::InvalidInput(format!(
+            "Object path {object_path:?} must start with '/'"
+        )));
     }
 
     if object_path.len() > 1024 {
         return Err(AppError::InvalidInput("Object path too long".to_string()));
     }
 
-    // Basic validation for D-Bus object paths
-    if !object_path
+    if object_path == "/" {
+        return Ok(());
+    }
+    if object_path.ends_with('/') {
+        return Err(AppError::InvalidInput(format!(
+            "Object path {object_path:?} must not end with '/'"
+        )));
+    }
+    if object_path.contains("//") {
+        return Err(AppError::InvalidInput(format!(
+            "Object path {object_path:?} contains an empty element"
+        )));
+    }
+    if let Some(c) = object_path
         .chars()
-        .all(|c| c.is_alphanumeric() || c == '/' || c == '_' || c == '-')
+        .find(|&c| !(c.is_ascii_alphanumeric() || c == '_' || c == '/'))
     {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid character {c:?} in object path {object_path:?}"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Validate an interface name like `org.freedesktop.DBus.Properties`.
+pub fn validate_interface_name(interface_name: &str) -> Result<()> {
+    validate_dotted_name("Interface name", interface_name)
+}
+
+/// Validate an error name, which follows the rules for interface names.
+pub fn validate_error_name(error_name: &str) -> Result<()> {
+    validate_dotted_name("Error name", error_name)
+}
+
+/// Validate the namespace of an `arg0namespace` match rule: a well-known bus or interface name,
+/// or its leading elements, down to a single one like `com`.
+pub fn validate_namespace(namespace: &str) -> Result<()> {
+    if namespace.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Namespace cannot be empty".to_string(),
+        ));
+    }
+
+    if namespace.len() > MAX_NAME_LENGTH {
+        return Err(AppError::InvalidInput(format!(
+            "Namespace {namespace:?} is longer than {MAX_NAME_LENGTH} characters"
+        )));
+    }
+
+    for element in namespace.split('.') {
+        validate_element(
+            "Namespace",
+            namespace,
+            element,
+            |c| c.is_ascii_alphanumeric() || c == '_' || c == '-',
+            true,
+        )?;
+    }
+    Ok(())
+}
+
+/// Validate the name of a method, property or signal.
+pub fn validate_member_name(member_name: &str) -> Result<()> {
+    if member_name.is_empty() {
         return Err(AppError::InvalidInput(
-            "Invalid characters in object path".to_string(),
+            "Member name cannot be empty".to_string(),
         ));
     }
 
+    if member_name.len() > MAX_NAME_LENGTH {
+        return Err(AppError::InvalidInput(format!(
+            "Member name {member_name:?} is longer than {MAX_NAME_LENGTH} characters"
+        )));
+    }
+
+    if let Some(c) = member_name
+        .chars()
+        .find(|&c| !(c.is_ascii_alphanumeric() || c == '_'))
+    {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid character {c:?} in member name {member_name:?}"
+        )));
+    }
+
+    if member_name.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(AppError::InvalidInput(format!(
+            "Member name {member_name:?} must not start with a digit"
+        )));
+    }
+
     Ok(())
 }
 
+/// Validate a signature of any number of complete types.
+pub fn validate_signature(signature: &str) -> Result<()> {
+    parse_signature(signature)
+        .map(|_| ())
+        .map_err(|e| AppError::InvalidInput(e.to_string()))
+}
+
+fn validate_dotted_name(what: &str, name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(AppError::InvalidInput(format!("{what} cannot be empty")));
+    }
+
+    if name.len() > MAX_NAME_LENGTH {
+        return Err(AppError::InvalidInput(format!(
+            "{what} {name:?} is longer than {MAX_NAME_LENGTH} characters"
+        )));
+    }
+
+    validate_elements(
+        what,
+        name,
+        name,
+        |c| c.is_ascii_alphanumeric() || c == '_',
+        true,
+    )
+}
+
+/// Check the `.`-separated elements of a name: at least two, none empty, all made of allowed
+/// characters and, if `no_leading_digit`, none starting with a digit.
+fn validate_elements(
+    what: &str,
+    name: &str,
+    elements: &str,
+    allowed: impl Fn(char) -> bool,
+    no_leading_digit: bool,
+) -> Result<()> {
+    if !elements.contains('.') {
+        return Err(AppError::InvalidInput(format!(
+            "{what} {name:?} must have at least two elements separated by '.'"
+        )));
+    }
+
+    for element in elements.split('.') {
+        validate_element(what, name, element, &allowed, no_leading_digit)?;
+    }
+
+    Ok(())
+}
+
+fn validate_element(
+    what: &str,
+    name: &str,
+    element: &str,
+    allowed: impl Fn(char) -> bool,
+    no_leading_digit: bool,
+) -> Result<()> {
+    if element.is_empty() {
+        return Err(AppError::InvalidInput(format!(
+            "{what} {name:?} contains an empty element"
+        )));
+    }
+    if let Some(c) = element.chars().find(|&c| !allowed(c)) {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid character {c:?} in {} {name:?}",
+            what.to_lowercase()
+        )));
+    }
+    if no_leading_digit && element.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(AppError::InvalidInput(format!(
+            "Element {element:?} of {} {name:?} must not start with a digit",
+            what.to_lowercase()
+        )));
+    }
+
+    Ok(())
+}
+
+pub fn object_page_url(service_name: &str, object_path: &str) -> String {
+    format!(
+        "/local/dbus_explorer/app/service/{}/{}",
+        urlencoding::encode(service_name),
+        urlencoding::encode(object_path.strip_prefix('/').unwrap_or(object_path))
+    )
+}
+
 pub fn build_breadcrumb_navigation(service_name: &str, object_path: &str) -> String {
     let mut breadcrumb_links = Vec::new();
 
@@ -229,10 +430,175 @@ pub fn find_child_objects<'a>(objects: &'a [ObjectInfo], parent_path: &str) -> V
     children
 }
 
-fn html_escape(text: &str) -> String {
+/// Escape text for use in HTML content and attribute values.
+pub fn html_escape(text: &str) -> String {
     text.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
         .replace('"', "&quot;")
         .replace('\'', "&#x27;")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run a validator over `(input, valid)` cases.
+    fn check(validate: fn(&str) -> Result<()>, cases: &[(&str, bool)]) {
+        for &(input, valid) in cases {
+            let result = validate(input);
+            assert_eq!(result.is_ok(), valid, "{input:?}: {result:?}");
+        }
+    }
+
+    #[test]
+    fn service_names() {
+        let too_long = format!("a.{}", "b".repeat(254));
+        check(
+            validate_service_name,
+            &[
+                ("org.freedesktop.DBus", true),
+                ("com.example.foo-bar", true),
+                ("org._private.Name", true),
+                (":1.42", true),
+                (":1.42.7", true),
+                (":a-b.c_d", true),
+                ("", false),
+                ("org", false),
+                (":1", false),
+                ("org..freedesktop", false),
+                (".org.freedesktop", false),
+                ("org.freedesktop.", false),
+                ("org.1freedesktop", false),
+                ("org.free desktop", false),
+                ("org.frée.desktop", false),
+                ("org.freedesktop/DBus", false),
+                (&too_long, false),
+            ],
+        );
+    }
+
+    #[test]
+    fn object_paths() {
+        check(
+            validate_object_path,
+            &[
+                ("/", true),
+                ("/org/freedesktop/DBus", true),
+                ("/org/freedesktop/systemd1/unit/dbus_2eservice", true),
+                ("/_1/2", true),
+                ("", false),
+                ("org/freedesktop", false),
+                ("/org/freedesktop/", false),
+                ("//", false),
+                ("/org//freedesktop", false),
+                ("/org/free-desktop", false),
+                ("/org/free.desktop", false),
+                ("/org/frée", false),
+            ],
+        );
+    }
+
+    #[test]
+    fn interface_and_error_names() {
+        let too_long = format!("a.{}", "b".repeat(254));
+        let cases = [
+            ("org.freedesktop.DBus.Properties", true),
+            ("org.freedesktop.DBus.Error.UnknownMethod", true),
+            ("a._1", true),
+            ("", false),
+            ("Properties", false),
+            ("org.freedesktop.", false),
+            ("org..DBus", false),
+            ("org.freedesktop.1DBus", false),
+            ("org.free-desktop.DBus", false),
+            (":1.42", false),
+            (too_long.as_str(), false),
+        ];
+        check(validate_interface_name, &cases);
+        check(validate_error_name, &cases);
+    }
+
+    #[test]
+    fn namespaces() {
+        check(
+            validate_namespace,
+            &[
+                ("com", true),
+                ("com.example", true),
+                ("org.freedesktop.DBus", true),
+                ("com.example-app", true),
+                ("", false),
+                (":1.42", false),
+                ("com.", false),
+                ("com..example", false),
+                ("com.1example", false),
+                ("com.example/App", false),
+            ],
+        );
+    }
+
+    #[test]
+    fn member_names() {
+        let too_long = "a".repeat(256);
+        check(
+            validate_member_name,
+            &[
+                ("GetAll", true),
+                ("get_all", true),
+                ("_Private2", true),
+                ("", false),
+                ("2Fast", false),
+                ("Get.All", false),
+                ("Get-All", false),
+                ("Gét", false),
+                (&too_long, false),
+            ],
+        );
+    }
+
+    #[test]
+    fn signatures() {
+        check(
+            validate_signature,
+            &[
+                ("", true),
+                ("s", true),
+                ("a{sv}", true),
+                ("(ia(sv))as", true),
+                ("a{oa{sa{sv}}}", true),
+                ("a", false),
+                ("a{vs}", false),
+                ("{sv}", false),
+                ("()", false),
+                ("(s", false),
+                ("z", false),
+            ],
+        );
+    }
+
+    #[test]
+    fn messages_are_specific() {
+        let message = |result: Result<()>| match result {
+            Err(AppError::InvalidInput(message)) => message,
+            other => panic!("expected invalid input, got {other:?}"),
+        };
+
+        assert_eq!(
+            message(validate_object_path("/org/free-desktop")),
+            r#"Invalid character '-' in object path "/org/free-desktop""#
+        );
+        assert_eq!(
+            message(validate_object_path("/org/")),
+            r#"Object path "/org/" must not end with '/'"#
+        );
+        assert_eq!(
+            message(validate_service_name("org.1example")),
+            r#"Element "1example" of service name "org.1example" must not start with a digit"#
+        );
+        assert_eq!(
+            message(validate_interface_name("Properties")),
+            r#"Interface name "Properties" must have at least two elements separated by '.'"#
+        );
+    }
+}
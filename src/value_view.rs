@@ -0,0 +1,343 @@
+use std::os::unix::io::AsRawFd;
+
+use dbus::arg::messageitem::MessageItem;
+use serde_json::{json, Value};
+
+use crate::utils::{html_escape, object_page_url};
+
+/// Containers with more elements than this start out collapsed.
+const COLLAPSE_LENGTH: usize = 20;
+/// Containers nested deeper than this start out collapsed.
+const COLLAPSE_DEPTH: usize = 2;
+
+/// How byte arrays are shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BytesFormat {
+    /// As a string when the bytes are printable text, as a hex dump otherwise.
+    #[default]
+    Auto,
+    Hex,
+    /// Like any other array.
+    List,
+}
+
+impl BytesFormat {
+    pub const ALL: [BytesFormat; 3] = [BytesFormat::Auto, BytesFormat::Hex, BytesFormat::List];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BytesFormat::Auto => "auto",
+            BytesFormat::Hex => "hex",
+            BytesFormat::List => "list",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.name() == name)
+    }
+}
+
+/// Options for showing received values.
+#[derive(Debug, Clone, Default)]
+pub struct ValueFormat {
+    pub bytes: BytesFormat,
+    /// The service the values came from, object paths link to its object pages.
+    pub service_name: Option<String>,
+}
+
+impl ValueFormat {
+    pub fn for_service(service_name: &str) -> Self {
+        Self {
+            service_name: Some(service_name.to_string()),
+            ..Self::default()
+        }
+    }
+}
+
+/// Render a received value as a collapsible HTML tree.
+pub fn render_value(item: &MessageItem, format: &ValueFormat) -> String {
+    format!(
+        r#"<div class="value">{}</div>"#,
+        render_item(item, format, 0)
+    )
+}
+
+fn render_item(item: &MessageItem, format: &ValueFormat, depth: usize) -> String {
+    match item {
+        MessageItem::Array(array) => {
+            if let Some(bytes) = byte_array(item) {
+                if format.bytes != BytesFormat::List {
+                    return render_bytes(&bytes, format.bytes);
+                }
+            }
+            let elements = array
+                .iter()
+                .enumerate()
+                .map(|(i, element)| {
+                    format!(
+                        r#"<li><span class="value-index">{i}:</span> {}</li>"#,
+                        render_item(element, format, depth + 1)
+                    )
+                })
+                .collect();
+            render_container(
+                &format!("array of {} ({})", array.len(), item.signature()),
+                elements,
+                depth,
+            )
+        }
+        MessageItem::Dict(dict) => {
+            let entries = dict
+                .iter()
+                .map(|(key, value)| {
+                    format!(
+                        "<li>{} → {}</li>",
+                        render_item(key, format, depth + 1),
+                        render_item(value, format, depth + 1)
+                    )
+                })
+                .collect();
+            render_container(
+                &format!("dict of {} ({})", dict.len(), item.signature()),
+                entries,
+                depth,
+            )
+        }
+        MessageItem::Struct(fields) => {
+            let fields = fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    format!(
+                        r#"<li><span class="value-index">{i}:</span> {}</li>"#,
+                        render_item(field, format, depth + 1)
+                    )
+                })
+                .collect();
+            render_container(&format!("struct {}", item.signature()), fields, depth)
+        }
+        MessageItem::Variant(inner) => format!(
+            r#"<span class="value-type">&lt;{}&gt;</span> {}"#,
+            html_escape(&inner.signature()),
+            render_item(inner, format, depth)
+        ),
+        MessageItem::Str(s) => format!(
+            r#"<span class="value-string">"{}"</span>"#,
+            html_escape(&s.escape_debug().to_string())
+        ),
+        MessageItem::ObjectPath(path) => match &format.service_name {
+            Some(service_name) => format!(
+                r#"<a class="value-path" href="{}">{}</a>"#,
+                object_page_url(service_name, path),
+                html_escape(path)
+            ),
+            None => format!(r#"<span class="value-path">{}</span>"#, html_escape(path)),
+        },
+        MessageItem::Signature(signature) => format!(
+            r#"<span class="value-signature">{}</span>"#,
+            html_escape(signature)
+        ),
+        MessageItem::Bool(b) => format!(r#"<span class="value-bool">{b}</span>"#),
+        MessageItem::UnixFd(fd) => {
+            format!(r#"<span class="value-fd">fd {}</span>"#, fd.as_raw_fd())
+        }
+        number => format!(
+            r#"<span class="value-number">{}</span>"#,
+            plain_number(number).unwrap_or_default()
+        ),
+    }
+}
+
+fn render_container(summary: &str, children: Vec<String>, depth: usize) -> String {
+    if children.is_empty() {
+        return format!(
+            r#"<span class="value-empty">{} (empty)</span>"#,
+            html_escape(summary)
+        );
+    }
+
+    let open = depth < COLLAPSE_DEPTH && children.len() <= COLLAPSE_LENGTH;
+    format!(
+        r#"<details class="value-tree"{}><summary>{}</summary><ul>{}</ul></details>"#,
+        if open { " open" } else { "" },
+        html_escape(summary),
+        children.join("")
+    )
+}
+
+fn render_bytes(bytes: &[u8], format: BytesFormat) -> String {
+    if format == BytesFormat::Auto {
+        if let Some(text) = printable_text(bytes) {
+            return format!(
+                r#"<span class="value-string">b"{}"</span> <span class="value-type">({} bytes)</span>"#,
+                html_escape(&text.escape_debug().to_string()),
+                bytes.len()
+            );
+        }
+    }
+
+    if bytes.is_empty() {
+        return r#"<span class="value-empty">0 bytes</span>"#.to_string();
+    }
+    format!(
+        r#"<details class="value-tree" open><summary>{} bytes</summary><pre class="value-bytes">{}</pre></details>"#,
+        bytes.len(),
+        html_escape(&hex_dump(bytes))
+    )
+}
+
+/// The bytes as text if they are UTF-8 without control characters, ignoring a terminating nul.
+fn printable_text(bytes: &[u8]) -> Option<&str> {
+    let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+    let text = std::str::from_utf8(bytes).ok()?;
+    let printable = !text.is_empty()
+        && text
+            .chars()
+            .all(|c| !c.is_control() || c == '\n' || c == '\t');
+    printable.then_some(text)
+}
+
+/// Offset, hex and ASCII columns, 16 bytes per line, like `hexdump -C`.
+fn hex_dump(bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!("{:08x}  {:<47}  |{ascii}|", i * 16, hex.join(" "))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn byte_array(item: &MessageItem) -> Option<Vec<u8>> {
+    let MessageItem::Array(array) = item else {
+        return None;
+    };
+    if &*item.signature() != "ay" {
+        return None;
+    }
+    array
+        .iter()
+        .map(|element| match element {
+            MessageItem::Byte(b) => Some(*b),
+            _ => None,
+        })
+        .collect()
+}
+
+fn plain_number(item: &MessageItem) -> Option<String> {
+    let text = match item {
+        MessageItem::Byte(n) => n.to_string(),
+        MessageItem::Int16(n) => n.to_string(),
+        MessageItem::UInt16(n) => n.to_string(),
+        MessageItem::Int32(n) => n.to_string(),
+        MessageItem::UInt32(n) => n.to_string(),
+        MessageItem::Int64(n) => n.to_string(),
+        MessageItem::UInt64(n) => n.to_string(),
+        MessageItem::Double(n) => n.to_string(),
+        _ => return None,
+    };
+    Some(text)
+}
+
+/// A received value with its type, in the JSON encoding used for variants by
+/// [`json_to_item`](crate::values::json_to_item), so that nothing is lost.
+pub fn typed_value_to_json(item: &MessageItem) -> Value {
+    json!({
+        "type": item.signature().to_string(),
+        "value": value_to_json(item),
+    })
+}
+
+/// Convert a received value to the JSON encoding of [`json_to_item`](crate::values::json_to_item).
+///
+/// Together with the signature this is lossless: 64-bit integers that JSON numbers cannot hold
+/// exactly and non-finite doubles are written as strings, and dict keys as text.
+pub fn value_to_json(item: &MessageItem) -> Value {
+    const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+    match item {
+        MessageItem::Array(array) => Value::Array(array.iter().map(value_to_json).collect()),
+        MessageItem::Struct(fields) => Value::Array(fields.iter().map(value_to_json).collect()),
+        MessageItem::Variant(inner) => typed_value_to_json(inner),
+        MessageItem::Dict(dict) => Value::Object(
+            dict.iter()
+                .map(|(key, value)| (key_text(key), value_to_json(value)))
+                .collect(),
+        ),
+        MessageItem::ObjectPath(path) => json!(path.to_string()),
+        MessageItem::Signature(signature) => json!(signature.to_string()),
+        MessageItem::Str(s) => json!(s),
+        MessageItem::Bool(b) => json!(b),
+        MessageItem::Byte(n) => json!(n),
+        MessageItem::Int16(n) => json!(n),
+        MessageItem::UInt16(n) => json!(n),
+        MessageItem::Int32(n) => json!(n),
+        MessageItem::UInt32(n) => json!(n),
+        MessageItem::Int64(n) if n.unsigned_abs() <= MAX_SAFE_INTEGER as u64 => json!(n),
+        MessageItem::Int64(n) => json!(n.to_string()),
+        MessageItem::UInt64(n) if *n <= MAX_SAFE_INTEGER as u64 => json!(n),
+        MessageItem::UInt64(n) => json!(n.to_string()),
+        MessageItem::Double(n) if n.is_finite() => json!(n),
+        MessageItem::Double(n) => json!(n.to_string()),
+        MessageItem::UnixFd(fd) => json!(fd.as_raw_fd()),
+    }
+}
+
+fn key_text(key: &MessageItem) -> String {
+    match key {
+        MessageItem::Str(s) => s.clone(),
+        MessageItem::ObjectPath(path) => path.to_string(),
+        MessageItem::Signature(signature) => signature.to_string(),
+        MessageItem::Bool(b) => b.to_string(),
+        other => plain_number(other).unwrap_or_else(|| value_to_json(other).to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_integers_are_strings() {
+        let safe = (1i64 << 53) - 1;
+        assert_eq!(value_to_json(&MessageItem::Int64(safe)), json!(safe));
+        assert_eq!(value_to_json(&MessageItem::Int64(-safe)), json!(-safe));
+        assert_eq!(
+            value_to_json(&MessageItem::Int64(safe + 1)),
+            json!((safe + 1).to_string())
+        );
+        assert_eq!(
+            value_to_json(&MessageItem::Int64(i64::MIN)),
+            json!(i64::MIN.to_string())
+        );
+        assert_eq!(
+            value_to_json(&MessageItem::UInt64(u64::MAX)),
+            json!(u64::MAX.to_string())
+        );
+    }
+
+    #[test]
+    fn strings_keep_non_ascii_text() {
+        let html = render_item(
+            &MessageItem::Str("é\t\"<b>\u{7}".to_string()),
+            &ValueFormat::default(),
+            0,
+        );
+        assert_eq!(
+            html,
+            r#"<span class="value-string">"é\t\&quot;&lt;b&gt;\u{7}"</span>"#
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use anyhow::Result;
+use dbus::{
+    arg::messageitem::{MessageItem, MessageItemArray, MessageItemDict},
+    strings::{Path, Signature},
+};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::{
+    gvariant::parse_gvariant,
+    signature::{parse_single_type, DbusType},
+};
+
+/// Convert text, as typed on a command line or in a form, to a message item of the given type.
+///
+/// Basic types are given as plain text, or in the GVariant text format when plain text does not
+/// fit, so that both `5` and `uint32 5` work. Quoted strings are always read as GVariant text;
+/// a string that should keep its quotes needs a second pair.
+///
+/// Containers and variants are given in the JSON encoding of [`json_to_item`] if the text is
+/// valid JSON, and in the GVariant text format of [`parse_gvariant`] otherwise.
+pub fn parse_argument(text: &str, type_name: &str, name: &str) -> Result<MessageItem> {
+    let dbus_type = parse_single_type(type_name)?;
+    let error = |message: String| ValueError {
+        location: name.to_string(),
+        message,
+    };
+
+    if dbus_type.is_basic() && !text.starts_with(['\'', '"']) {
+        // Report the plain text error, it is the more helpful one for unquoted input
+        return parse_basic(text, &dbus_type)
+            .or_else(|message| parse_gvariant(text, &dbus_type, name).map_err(|_| error(message)))
+            .map_err(Into::into);
+    }
+    if dbus_type.is_basic() {
+        return Ok(parse_gvariant(text, &dbus_type, name)?);
+    }
+
+    match serde_json::from_str::<Value>(text) {
+        Ok(value) => Ok(json_to_item(&value, &dbus_type, name)?),
+        Err(_) => Ok(parse_gvariant(text, &dbus_type, name)?),
+    }
+}
+
+/// A value that does not match the expected type, and where in the value this happened.
+#[derive(Error, Debug, Clone)]
+#[error("{location}: {message}")]
+pub struct ValueError {
+    pub location: String,
+    pub message: String,
+}
+
+/// Convert JSON to a message item of the given type.
+///
+/// Arrays and structs are JSON arrays, dicts are JSON objects with the keys written as text,
+/// and variants are objects like `{"type": "u", "value": 42}`. Integers may be given as strings
+/// to avoid the precision loss of JSON numbers.
+///
+/// `location` names the value in error messages, nested values extend it.
+pub fn json_to_item(
+    value: &Value,
+    dbus_type: &DbusType,
+    location: &str,
+) -> std::result::Result<MessageItem, ValueError> {
+    let error = |message: String| ValueError {
+        location: location.to_string(),
+        message,
+    };
+
+    match dbus_type {
+        DbusType::Array(element) => {
+            let Value::Array(elements) = value else {
+                return Err(error(format!(
+                    "expected a JSON array for {}",
+                    dbus_type.describe()
+                )));
+            };
+            let items = elements
+                .iter()
+                .enumerate()
+                .map(|(i, element_value)| {
+                    json_to_item(element_value, element, &format!("{location}[{i}]"))
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            array_item(items, dbus_type).map_err(error)
+        }
+        DbusType::Dict(key_type, value_type) => {
+            let Value::Object(entries) = value else {
+                return Err(error(format!(
+                    "expected a JSON object for {}",
+                    dbus_type.describe()
+                )));
+            };
+            let items = entries
+                .iter()
+                .map(|(key, entry_value)| {
+                    let entry_location = format!("{location}[{key:?}]");
+                    let key = parse_basic(key, key_type).map_err(|message| ValueError {
+                        location: entry_location.clone(),
+                        message: format!("invalid key: {message}"),
+                    })?;
+                    let entry_value = json_to_item(entry_value, value_type, &entry_location)?;
+                    Ok((key, entry_value))
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            dict_item(items, key_type, value_type).map_err(error)
+        }
+        DbusType::Struct(field_types) => {
+            let Value::Array(fields) = value else {
+                return Err(error(format!(
+                    "expected a JSON array for {}",
+                    dbus_type.describe()
+                )));
+            };
+            if fields.len() != field_types.len() {
+                return Err(error(format!(
+                    "expected {} fields but got {}",
+                    field_types.len(),
+                    fields.len()
+                )));
+            }
+            let items = fields
+                .iter()
+                .zip(field_types)
+                .enumerate()
+                .map(|(i, (field, field_type))| {
+                    json_to_item(field, field_type, &format!("{location}.{i}"))
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            Ok(MessageItem::Struct(items))
+        }
+        DbusType::Variant => {
+            let (Some(Value::String(signature)), Some(inner)) =
+                (value.get("type"), value.get("value"))
+            else {
+                return Err(error(
+                    r#"expected a JSON object like {"type": "s", "value": "text"} for variant"#
+                        .to_string(),
+                ));
+            };
+            let inner_type = parse_single_type(signature).map_err(|e| ValueError {
+                location: format!("{location}.type"),
+                message: e.to_string(),
+            })?;
+            let inner = json_to_item(inner, &inner_type, &format!("{location}.value"))?;
+            Ok(MessageItem::Variant(Box::new(inner)))
+        }
+        basic => match value {
+            Value::String(text) => parse_basic(text, basic).map_err(error),
+            Value::Bool(_) | Value::Number(_) => {
+                parse_basic(&value.to_string(), basic).map_err(error)
+            }
+            _ => Err(error(format!("expected {}", basic.describe()))),
+        },
+    }
+}
+
+/// Build an array of type `array_type` from already converted elements.
+pub fn array_item(
+    items: Vec<MessageItem>,
+    array_type: &DbusType,
+) -> std::result::Result<MessageItem, String> {
+    let signature = Signature::new(array_type.to_string())?;
+    let array = MessageItemArray::new(items, signature).map_err(|e| format!("{e:?}"))?;
+    Ok(MessageItem::Array(array))
+}
+
+/// Build a dict from already converted entries.
+pub fn dict_item(
+    items: Vec<(MessageItem, MessageItem)>,
+    key_type: &DbusType,
+    value_type: &DbusType,
+) -> std::result::Result<MessageItem, String> {
+    let key_signature = Signature::new(key_type.to_string())?;
+    let value_signature = Signature::new(value_type.to_string())?;
+    let dict = MessageItemDict::new(items, key_signature, value_signature)
+        .map_err(|e| format!("{e:?}"))?;
+    Ok(MessageItem::Dict(dict))
+}
+
+/// Convert plain text to a message item of a basic type.
+pub fn parse_basic(text: &str, dbus_type: &DbusType) -> std::result::Result<MessageItem, String> {
+    let item = match dbus_type {
+        DbusType::Boolean => match text {
+            "true" | "1" => MessageItem::Bool(true),
+            "false" | "0" => MessageItem::Bool(false),
+            _ => return Err(format!("expected true or false but got {text:?}")),
+        },
+        DbusType::Byte => MessageItem::Byte(parse_number(text, dbus_type)?),
+        DbusType::Int16 => MessageItem::Int16(parse_number(text, dbus_type)?),
+        DbusType::UInt16 => MessageItem::UInt16(parse_number(text, dbus_type)?),
+        DbusType::Int32 => MessageItem::Int32(parse_number(text, dbus_type)?),
+        DbusType::UInt32 => MessageItem::UInt32(parse_number(text, dbus_type)?),
+        DbusType::Int64 => MessageItem::Int64(parse_number(text, dbus_type)?),
+        DbusType::UInt64 => MessageItem::UInt64(parse_number(text, dbus_type)?),
+        DbusType::Double => MessageItem::Double(parse_number(text, dbus_type)?),
+        DbusType::String => MessageItem::Str(text.to_string()),
+        DbusType::ObjectPath => MessageItem::ObjectPath(Path::new(text.to_string())?),
+        DbusType::Signature => MessageItem::Signature(Signature::new(text.to_string())?),
+        other => {
+            return Err(format!(
+                "values of type {} cannot be sent",
+                other.describe()
+            ))
+        }
+    };
+    Ok(item)
+}
+
+fn parse_number<T: std::str::FromStr>(
+    text: &str,
+    dbus_type: &DbusType,
+) -> std::result::Result<T, String> {
+    text.trim()
+        .parse()
+        .map_err(|_| format!("expected {} but got {text:?}", dbus_type.describe()))
+}